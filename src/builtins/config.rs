@@ -0,0 +1,491 @@
+use clap::{CommandFactory, Parser, Subcommand};
+use crossterm::style::Stylize;
+use log::{error, info};
+use serde_yaml::{Mapping, Value};
+
+use super::{parse_args, Builtin, ExitStatus, Io, ShellState};
+use crate::config_editor;
+use crate::layers::{self, PROJECT_CONFIG};
+use crate::setup::{self, YamlConfiguration};
+use std::env;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.10", about = "Edit the config file of Yarsh", long_about = None)]
+#[command(args_conflicts_with_subcommands = true)]
+struct ConfigArgs {
+    #[arg(short = 'l', long = "list", help = "List all values of the configs, like without arguments")]
+    list_opt: bool,
+
+    #[arg(long = "show-origin", help = "List the values with the file or the environment variable they come from")]
+    show_origin_opt: bool,
+
+    #[arg(short = 'e', long = "edit", help = "Edit the config file in a full screen editor")]
+    edit_opt: bool,
+
+    #[command(subcommand)]
+    action: Option<ConfigAction>,
+}
+
+#[derive(Subcommand, Debug)]
+enum ConfigAction {
+    #[command(about = "Print the value at PATH, like terminal_config.alias.ll")]
+    Get { path: String },
+    #[command(about = "Change the value at PATH, `+` as the last step adds an item to a list")]
+    Set { path: String, value: String },
+    #[command(about = "Remove a key of a map or an item of a list")]
+    Unset { path: String },
+    #[command(about = "Use the project config at PATH, by default the .yarsh.yml of this directory or of its parents")]
+    Trust { path: Option<PathBuf> },
+    #[command(about = "Stop using the project config at PATH")]
+    Untrust { path: Option<PathBuf> },
+    #[command(about = "Read the configs again and use them in this shell")]
+    Reload,
+}
+
+/// Splits a dotted path like `theme.colors."*.log"` in its steps: the keys of the
+/// maps and the positions in the lists. A key with dots can be quoted.
+fn parse_path(path: &str) -> Result<Vec<String>, String> {
+    let mut steps = vec![];
+    let mut step = String::new();
+    let mut quoted = false;
+    for c in path.chars() {
+        match c {
+            '"' => quoted = !quoted,
+            '.' if !quoted => steps.push(std::mem::take(&mut step)),
+            _ => step.push(c),
+        }
+    }
+    if quoted {
+        return Err(format!("Unclosed quote in {path}"));
+    }
+    steps.push(step);
+    if steps.iter().any(|step| step.is_empty()) {
+        return Err(format!("Empty step in {path}"));
+    }
+    Ok(steps)
+}
+
+/// The position `step` points to in a list of `len` items. `+` and `len` point
+/// after the last one when `append` is true.
+fn list_index(step: &str, len: usize, append: bool) -> Result<usize, String> {
+    let index = match step {
+        "+" if append => len,
+        _ => step.parse::<usize>().map_err(|_| format!("{step} is not a position in a list"))?,
+    };
+    if index < len || (append && index == len) {
+        Ok(index)
+    } else {
+        Err(format!("There is no item {index}, the list has {len}"))
+    }
+}
+
+fn get_value<'a>(tree: &'a Value, path: &[String]) -> Result<&'a Value, String> {
+    let mut node = tree;
+    for (depth, step) in path.iter().enumerate() {
+        node = match node {
+            Value::Mapping(map) => map.get(step.as_str()),
+            Value::Sequence(list) => list.get(list_index(step, list.len(), false)?),
+            _ => return Err(format!("{} is not a map or a list", path[..depth].join("."))),
+        }
+        .ok_or_else(|| format!("No such field: {}", path[..=depth].join(".")))?;
+    }
+    Ok(node)
+}
+
+/// The map or list that holds the last step of `path`. The maps that don't exist
+/// yet are created.
+fn parent_mut<'a>(tree: &'a mut Value, path: &[String]) -> Result<&'a mut Value, String> {
+    let mut node = tree;
+    for (depth, step) in path[..path.len() - 1].iter().enumerate() {
+        node = match node {
+            Value::Mapping(map) => map.entry(Value::from(step.as_str())).or_insert(Value::Mapping(Mapping::new())),
+            Value::Sequence(list) => {
+                let index = list_index(step, list.len(), false)?;
+                &mut list[index]
+            }
+            _ => return Err(format!("{} is not a map or a list", path[..depth].join("."))),
+        };
+    }
+    Ok(node)
+}
+
+/// Sets the value at `path` and returns the path it ended up at: `+` becomes the
+/// position of the new item.
+fn set_value(tree: &mut Value, path: &[String], value: Value) -> Result<Vec<String>, String> {
+    let mut path = path.to_vec();
+    let last = path.len() - 1;
+    match parent_mut(tree, &path)? {
+        Value::Mapping(map) => {
+            map.insert(Value::from(path[last].as_str()), value);
+        }
+        Value::Sequence(list) => match list_index(&path[last], list.len(), true)? {
+            index if index == list.len() => {
+                list.push(value);
+                path[last] = index.to_string();
+            }
+            index => list[index] = value,
+        },
+        _ => return Err(format!("{} is not a map or a list", path[..last].join("."))),
+    }
+    Ok(path)
+}
+
+fn unset_value(tree: &mut Value, path: &[String]) -> Result<(), String> {
+    let last = &path[path.len() - 1];
+    let removed = match parent_mut(tree, path)? {
+        Value::Mapping(map) => map.remove(last.as_str()).is_some(),
+        Value::Sequence(list) => {
+            list.remove(list_index(last, list.len(), false)?);
+            true
+        }
+        _ => false,
+    };
+    if removed {
+        Ok(())
+    } else {
+        Err(format!("No such field: {}", path.join(".")))
+    }
+}
+
+/// Reads the edited tree back as a configuration, so the values have the types of
+/// its fields.
+fn check(tree: Value, path: &[String]) -> Result<YamlConfiguration, String> {
+    let configs: YamlConfiguration = serde_yaml::from_value(tree).map_err(|err| format!("{}: {err}", path.join(".")))?;
+    Ok(configs)
+}
+
+fn to_tree(configs: &YamlConfiguration) -> Result<Value, String> {
+    serde_yaml::to_value(configs).map_err(|err| format!("Cannot read the configs: {err}"))
+}
+
+/// Refuses the paths that can't be changed: the version tells which migrations the
+/// file needs, it only changes when the file is upgraded.
+fn check_editable(path: &[String]) -> Result<(), String> {
+    if path[0] == "version" {
+        return Err(String::from("version can't be changed, it is the version of the config file"));
+    }
+    Ok(())
+}
+
+/// Sets `path` to `value`. It is read as YAML, so lists and maps can be written like
+/// `[a.lua, b.lua]`, and as text if the field is a string.
+fn set(configs: &YamlConfiguration, path: &[String], value: &str) -> Result<YamlConfiguration, String> {
+    check_editable(path)?;
+    let tree = to_tree(configs)?;
+    let attempt = |value: Value| {
+        let mut tree = tree.clone();
+        let path = set_value(&mut tree, path, value)?;
+        let configs = check(tree, &path)?;
+        // The keys that aren't fields are dropped when the tree is read
+        get_value(&to_tree(&configs)?, &path)?;
+        Ok::<_, String>(configs)
+    };
+    match serde_yaml::from_str::<Value>(value) {
+        Ok(parsed) if !parsed.is_string() => attempt(parsed).or_else(|err| attempt(Value::from(value)).map_err(|_| err)),
+        _ => attempt(Value::from(value)),
+    }
+}
+
+fn unset(configs: &YamlConfiguration, path: &[String]) -> Result<YamlConfiguration, String> {
+    check_editable(path)?;
+    let mut tree = to_tree(configs)?;
+    unset_value(&mut tree, path)?;
+    check(tree, path)
+}
+
+/// A value that isn't a map or a list, as it is written in the config file.
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Mapping(map) if map.is_empty() => String::from("{}"),
+        Value::Sequence(list) if list.is_empty() => String::from("[]"),
+        _ => serde_yaml::to_string(value).map_or(String::new(), |text| text.trim_end().to_string()),
+    }
+}
+
+fn is_tree(value: &Value) -> bool {
+    match value {
+        Value::Mapping(map) => !map.is_empty(),
+        Value::Sequence(list) => !list.is_empty(),
+        _ => false,
+    }
+}
+
+/// Prints the items of a map or a list, each level indented by two spaces. The keys
+/// are colored when the output is a terminal.
+fn print_tree(value: &Value, indent: usize, io: &mut Io) {
+    let color = io.stdout.is_terminal();
+    let padding = " ".repeat(indent);
+    let items: Vec<(String, &Value)> = match value {
+        Value::Mapping(map) => map.iter().map(|(key, value)| (scalar_text(key), value)).collect(),
+        Value::Sequence(list) => list.iter().map(|value| (String::from("-"), value)).collect(),
+        _ => return io.out(format!("{padding}{}", scalar_text(value))),
+    };
+    let is_list = value.is_sequence();
+    for (key, value) in items {
+        let key = match (color, is_list) {
+            (true, false) => key.green().to_string(),
+            _ => key,
+        };
+        let separator = if is_list { "" } else { ":" };
+        if is_tree(value) {
+            io.out(format!("{padding}{key}{separator}"));
+            print_tree(value, indent + 2, io);
+        } else {
+            io.out(format!("{padding}{key}{separator} {}", scalar_text(value)));
+        }
+    }
+}
+
+/// `path` written like the paths `get` and `set` take, with the steps that have
+/// dots quoted.
+fn path_text(path: &[String]) -> String {
+    let steps: Vec<String> = path
+        .iter()
+        .map(|step| if step.contains('.') { format!("\"{step}\"") } else { step.clone() })
+        .collect();
+    steps.join(".")
+}
+
+/// The values that aren't maps, with their paths. The lists are one value, because
+/// a layer replaces them as a whole.
+fn leaves(value: &Value, path: &mut Vec<String>, found: &mut Vec<(Vec<String>, String)>) {
+    match value {
+        Value::Mapping(map) if !map.is_empty() => {
+            for (key, value) in map {
+                path.push(scalar_text(key));
+                leaves(value, path, found);
+                path.pop();
+            }
+        }
+        Value::Sequence(list) => {
+            let items: Vec<String> = list.iter().map(scalar_text).collect();
+            found.push((path.clone(), format!("[{}]", items.join(", "))));
+        }
+        _ => found.push((path.clone(), scalar_text(value))),
+    }
+}
+
+/// Prints every value with where it comes from, like `git config --show-origin`.
+fn show_origin(io: &mut Io) -> Result<(), String> {
+    let (layers, _) = layers::read_layers();
+    let tree = to_tree(&layers::merge(&layers))?;
+    let mut found = vec![];
+    leaves(&tree, &mut vec![], &mut found);
+    for (path, value) in found {
+        let origin = layers::origin(&layers, &path);
+        let origin = if io.stdout.is_terminal() { origin.dark_grey().to_string() } else { origin };
+        io.out(format!("{origin}\t{}={value}", path_text(&path)));
+    }
+    Ok(())
+}
+
+/// Tells when the value just changed in the config file is overridden by a later
+/// layer, so the change won't be seen.
+fn warn_overridden(path: &[String], io: &mut Io) {
+    let (layers, _) = layers::read_layers();
+    if let Some(origin) = layers::overridden(&layers, path) {
+        io.err(format!("{}: {} is overridden by {origin}", "config".blue(), path.join(".")));
+    }
+}
+
+/// The project config `path` points to: the file itself or the `.yarsh.yml` of a
+/// directory. Without a path, the one of the current directory or of its parents.
+fn project_path(path: &Option<PathBuf>) -> Result<PathBuf, String> {
+    match path {
+        Some(path) if path.is_dir() => Ok(path.join(PROJECT_CONFIG)),
+        Some(path) => Ok(path.clone()),
+        None => {
+            let cwd = env::current_dir().map_err(|err| err.to_string())?;
+            layers::find_project(&cwd).ok_or_else(|| format!("There is no {PROJECT_CONFIG} here or in the parent directories"))
+        }
+    }
+}
+
+pub struct ConfigCmd;
+
+impl Builtin for ConfigCmd {
+    fn name(&self) -> &'static str {
+        "config"
+    }
+
+    fn command(&self) -> clap::Command {
+        ConfigArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let args: ConfigArgs = match parse_args(args, io) {
+            Ok(args) => args,
+            Err(status) => return status,
+        };
+        // Only listing and getting use the configs, the changes read the file themselves
+        let configs = || {
+            info!("builtins::config::ConfigCmd::run(): Reading config file...");
+            to_tree(&setup::load_conf())
+        };
+
+        let result = match &args.action {
+            None if args.edit_opt => {
+                if io.stdout.is_terminal() && io.stdin.is_terminal() {
+                    config_editor::edit()
+                } else {
+                    Err(String::from("The editor needs a terminal"))
+                }
+            }
+            None if args.show_origin_opt => show_origin(io),
+            None => configs().map(|tree| print_tree(&tree, 0, io)),
+            Some(ConfigAction::Get { path }) => parse_path(path).and_then(|path| {
+                let tree = configs()?;
+                let value = get_value(&tree, &path)?;
+                if is_tree(value) {
+                    print_tree(value, 0, io);
+                } else {
+                    io.out(scalar_text(value));
+                }
+                Ok(())
+            }),
+            Some(ConfigAction::Set { path, value }) => parse_path(path).and_then(|path| {
+                info!("builtins::config::ConfigCmd::run(): Setting {} to {value}", path.join("."));
+                setup::update_conf(|configs| {
+                    *configs = set(configs, &path, value)?;
+                    Ok(())
+                })?;
+                warn_overridden(&path, io);
+                Ok(())
+            }),
+            Some(ConfigAction::Unset { path }) => parse_path(path).and_then(|path| {
+                info!("builtins::config::ConfigCmd::run(): Removing {}", path.join("."));
+                setup::update_conf(|configs| {
+                    *configs = unset(configs, &path)?;
+                    Ok(())
+                })?;
+                warn_overridden(&path, io);
+                Ok(())
+            }),
+            Some(ConfigAction::Trust { path }) => project_path(path).and_then(|path| {
+                let path = layers::set_trust(&path, true)?;
+                io.out(format!("Trusted {}", path.to_string_lossy()));
+                Ok(())
+            }),
+            Some(ConfigAction::Untrust { path }) => project_path(path).and_then(|path| {
+                let path = layers::set_trust(&path, false)?;
+                io.out(format!("{} is not trusted anymore", path.to_string_lossy()));
+                Ok(())
+            }),
+            // The main loop reads them, because it applies them to the editor too
+            Some(ConfigAction::Reload) => {
+                if state.subshell {
+                    Err(String::from("The configs can't be reloaded from a pipeline"))
+                } else {
+                    state.reload_requested = true;
+                    Ok(())
+                }
+            }
+        };
+        if let Err(err) = result {
+            error!("builtins::config::ConfigCmd::run(): {err}");
+            io.err(format!("{}: {err}", "config".blue()));
+            return ExitStatus::FAILURE;
+        }
+        ExitStatus::SUCCESS
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn steps(path: &str) -> Vec<String> {
+        parse_path(path).unwrap()
+    }
+
+    #[test]
+    fn splits_the_dotted_paths() {
+        let cases: [(&str, &[&str]); 5] = [
+            ("version", &["version"]),
+            ("terminal_config.prompt", &["terminal_config", "prompt"]),
+            ("theme.colors.\"*.log\"", &["theme", "colors", "*.log"]),
+            ("a.\"b.c\".d", &["a", "b.c", "d"]),
+            ("scripts_config.files.0", &["scripts_config", "files", "0"]),
+        ];
+        for (path, expected) in cases {
+            assert_eq!(steps(path), expected, "{path}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_paths() {
+        let cases = [
+            ("", "Empty step in "),
+            ("a..b", "Empty step in a..b"),
+            ("a.", "Empty step in a."),
+            ("theme.\"*.log", "Unclosed quote in theme.\"*.log"),
+        ];
+        for (path, message) in cases {
+            assert_eq!(parse_path(path).unwrap_err(), message, "{path}");
+        }
+    }
+
+    #[test]
+    fn sets_the_values_with_the_type_of_their_field() {
+        let configs = YamlConfiguration::default();
+        let configs = set(&configs, &steps("terminal_config.auto_cd"), "true").unwrap();
+        assert!(configs.terminal_config.auto_cd);
+        // A string field takes the text even if it reads as another type
+        let configs = set(&configs, &steps("terminal_config.prompt"), "42").unwrap();
+        assert_eq!(configs.terminal_config.prompt, "42");
+        let configs = set(&configs, &steps("terminal_config.edit_mode"), "vi").unwrap();
+        assert_eq!(configs.terminal_config.edit_mode, setup::EditMode::Vi);
+        let configs = set(&configs, &steps("theme.colors.\"*.log\""), "dim").unwrap();
+        assert_eq!(configs.theme.colors.get("*.log").map(String::as_str), Some("dim"));
+        let configs = set(&configs, &steps("terminal_config.alias.ll"), "ls -l").unwrap();
+        assert_eq!(configs.terminal_config.alias.get("ll").map(String::as_str), Some("ls -l"));
+    }
+
+    #[test]
+    fn sets_and_appends_list_items() {
+        let configs = set(&YamlConfiguration::default(), &steps("scripts_config.files"), "[a.lua, b.lua]").unwrap();
+        assert_eq!(configs.scripts_config.files, [Path::new("a.lua"), Path::new("b.lua")]);
+        let configs = set(&configs, &steps("scripts_config.files.+"), "c.lua").unwrap();
+        let configs = set(&configs, &steps("scripts_config.files.0"), "z.lua").unwrap();
+        assert_eq!(configs.scripts_config.files, [Path::new("z.lua"), Path::new("b.lua"), Path::new("c.lua")]);
+        let err = set(&configs, &steps("scripts_config.files.5"), "d.lua").unwrap_err();
+        assert_eq!(err, "There is no item 5, the list has 3");
+        let err = set(&configs, &steps("scripts_config.files.last"), "d.lua").unwrap_err();
+        assert_eq!(err, "last is not a position in a list");
+    }
+
+    #[test]
+    fn refuses_unknown_fields_and_wrong_types() {
+        let configs = YamlConfiguration::default();
+        let err = set(&configs, &steps("terminal_config.colour"), "red").unwrap_err();
+        assert_eq!(err, "No such field: terminal_config.colour");
+        let err = set(&configs, &steps("terminal_config.prompt.text"), "x").unwrap_err();
+        assert_eq!(err, "terminal_config.prompt is not a map or a list");
+        let err = set(&configs, &steps("terminal_config.auto_cd"), "maybe").unwrap_err();
+        assert!(err.starts_with("terminal_config.auto_cd: "), "{err}");
+        let err = set(&configs, &steps("terminal_config.edit_mode"), "nano").unwrap_err();
+        assert!(err.starts_with("terminal_config.edit_mode: unknown variant `nano`"), "{err}");
+        let err = set(&configs, &steps("version"), "2").unwrap_err();
+        assert_eq!(err, "version can't be changed, it is the version of the config file");
+        let err = unset(&configs, &steps("version")).unwrap_err();
+        assert_eq!(err, "version can't be changed, it is the version of the config file");
+    }
+
+    #[test]
+    fn unset_fields_take_their_default_value() {
+        let configs = set(&YamlConfiguration::default(), &steps("terminal_config.prompt"), "$ ").unwrap();
+        let configs = set(&configs, &steps("scripts_config.files"), "[a.lua, b.lua]").unwrap();
+        let configs = unset(&configs, &steps("terminal_config.prompt")).unwrap();
+        assert_eq!(configs.terminal_config.prompt, "{}");
+        let configs = unset(&configs, &steps("scripts_config.files.0")).unwrap();
+        assert_eq!(configs.scripts_config.files, [Path::new("b.lua")]);
+
+        let err = unset(&configs, &steps("terminal_config.colour")).unwrap_err();
+        assert_eq!(err, "No such field: terminal_config.colour");
+        let err = unset(&configs, &steps("scripts_config.files.1")).unwrap_err();
+        assert_eq!(err, "There is no item 1, the list has 1");
+    }
+}
@@ -0,0 +1,768 @@
+use clap::{CommandFactory, Parser};
+use crossterm::style::Stylize;
+use log::{error, info};
+use std::ffi::CString;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{self, Path, PathBuf};
+use std::{env, fs, io};
+
+use super::{parse_args, Builtin, CompletionHint, ExitStatus, Io, ShellState};
+use crate::frecency::{self, FrecencyDatabase};
+use crate::pipeline::describe;
+use crate::suggest;
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Change the working directory", long_about = None)]
+struct CdArgs {
+    #[arg(
+        short = 'L',
+        help = "Resolve '..' against PWD, keeping symbolic links (default)",
+        overrides_with = "physical_opt"
+    )]
+    logical_opt: bool,
+
+    #[arg(
+        short = 'P',
+        help = "Use the physical directory structure, resolving symbolic links",
+        overrides_with = "logical_opt"
+    )]
+    physical_opt: bool,
+
+    #[arg(name = "DIR | -")]
+    dir: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Print the current working directory", long_about = None)]
+struct PwdArgs {
+    #[arg(
+        short = 'L',
+        long = "logical",
+        help = "Print the value of PWD if it names the current working directory (default)",
+        overrides_with = "physical_opt"
+    )]
+    logical_opt: bool,
+
+    #[arg(
+        short = 'P',
+        long = "physical",
+        help = "Print the physical directory, with all symbolic links resolved",
+        overrides_with = "logical_opt"
+    )]
+    physical_opt: bool,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Add a directory to the directory stack", long_about = None, allow_negative_numbers = true)]
+struct PushdArgs {
+    #[arg(
+        short = 'n',
+        help = "Only manipulate the stack, don't change the working directory"
+    )]
+    no_cd_opt: bool,
+
+    #[arg(name = "DIR | +N | -N")]
+    target: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Remove a directory from the directory stack", long_about = None, allow_negative_numbers = true)]
+struct PopdArgs {
+    #[arg(
+        short = 'n',
+        help = "Only manipulate the stack, don't change the working directory"
+    )]
+    no_cd_opt: bool,
+
+    #[arg(name = "+N | -N")]
+    target: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Show the directory stack", long_about = None, allow_negative_numbers = true)]
+struct DirsArgs {
+    #[arg(short = 'c', help = "Clear the directory stack")]
+    clear_opt: bool,
+
+    #[arg(short = 'l', help = "Show full paths instead of using ~ for the home directory")]
+    long_opt: bool,
+
+    #[arg(short = 'p', help = "Print one entry per line")]
+    per_line_opt: bool,
+
+    #[arg(short = 'v', help = "Print one entry per line, prefixed with its position in the stack")]
+    verbose_opt: bool,
+
+    #[arg(name = "+N | -N")]
+    target: Option<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Jump to a frequently and recently used directory", long_about = None)]
+struct ZArgs {
+    #[arg(short = 'l', long = "list", help = "List the matching directories with their scores")]
+    list_opt: bool,
+
+    #[arg(short = 'i', long = "interactive", help = "Always choose the directory from a list")]
+    interactive_opt: bool,
+
+    #[arg(name = "FRAGMENTS")]
+    fragments: Vec<String>,
+}
+
+/// Directories saved with `pushd`. The working directory itself is not stored, it
+/// is always the implicit entry 0 of the stack (like in bash).
+#[derive(Clone, Debug, Default)]
+pub struct DirStack {
+    entries: Vec<PathBuf>,
+    /// The working directory of a subshell, which `pushd` and `popd` don't change
+    /// for the whole process. `None` in the shell itself.
+    cwd: Option<PathBuf>,
+}
+
+impl DirStack {
+    pub fn new() -> Self {
+        DirStack { entries: vec![], cwd: None }
+    }
+
+    /// The full stack as shown by `dirs`: the working directory followed by the saved entries.
+    fn listing(&self) -> Result<Vec<PathBuf>, String> {
+        let cwd = match &self.cwd {
+            Some(cwd) => cwd.clone(),
+            None => logical_cwd()?,
+        };
+        let mut listing = vec![cwd];
+        listing.extend(self.entries.iter().cloned());
+        Ok(listing)
+    }
+
+    /// Converts a `+N`/`-N` argument into an index of `listing()`.
+    fn parse_index(arg: &str, len: usize) -> Option<Result<usize, String>> {
+        let (from_left, number) = if let Some(number) = arg.strip_prefix('+') {
+            (true, number)
+        } else if let Some(number) = arg.strip_prefix('-') {
+            (false, number)
+        } else {
+            return None;
+        };
+        let Ok(number) = number.parse::<usize>() else {
+            return Some(Err(format!("{arg}: invalid number")));
+        };
+        if number >= len {
+            return Some(Err(format!("{arg}: directory stack index out of range")));
+        }
+        Some(Ok(if from_left { number } else { len - 1 - number }))
+    }
+
+    fn display_path(path: &Path, long: bool) -> String {
+        if !long {
+            if let Some(home) = env::var_os("HOME") {
+                if let Ok(rest) = path.strip_prefix(&home) {
+                    if rest.as_os_str().is_empty() {
+                        return String::from("~");
+                    }
+                    return format!("~/{}", rest.to_string_lossy());
+                }
+            }
+        }
+        path.to_string_lossy().to_string()
+    }
+
+    fn print(&self, io: &mut Io, long: bool, per_line: bool, verbose: bool) -> Result<(), String> {
+        let listing = self.listing()?;
+        if verbose {
+            for (index, dir) in listing.iter().enumerate() {
+                io.out(format!("{:>2}  {}", index, DirStack::display_path(dir, long)));
+            }
+        } else if per_line {
+            for dir in listing.iter() {
+                io.out(DirStack::display_path(dir, long));
+            }
+        } else {
+            io.out(
+                listing
+                    .iter()
+                    .map(|dir| DirStack::display_path(dir, long))
+                    .collect::<Vec<String>>()
+                    .join(" "),
+            );
+        }
+        Ok(())
+    }
+}
+
+pub fn getcwd() -> Result<PathBuf, String> {
+    info!("builtins::dirs::getcwd(): Retrieving the working directory...");
+    match env::current_dir() {
+        Ok(wkd) => Ok(wkd),
+        Err(err) => {
+            error!("builtins::dirs::getcwd(): Cannot get the current work directory because this error:");
+            error!("builtins::dirs::getcwd(): {err}");
+            Err(describe(&err))
+        }
+    }
+}
+
+/// Makes sure `PWD` names the working directory the shell was started in.
+pub fn init_pwd() {
+    if let Ok(wkd) = getcwd() {
+        if !names_cwd(env::var_os("PWD").map(PathBuf::from)) {
+            env::set_var("PWD", wkd);
+        }
+    }
+}
+
+/// Returns true if `path` is an absolute path to the current working directory.
+fn names_cwd(path: Option<PathBuf>) -> bool {
+    let Some(path) = path else {
+        return false;
+    };
+    if !path.is_absolute() {
+        return false;
+    }
+    match (fs::metadata(&path), fs::metadata(".")) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+/// The working directory as the user reached it: `PWD` while it still names the
+/// working directory, the physical path otherwise.
+pub fn logical_cwd() -> Result<PathBuf, String> {
+    let pwd = env::var_os("PWD").map(PathBuf::from);
+    if names_cwd(pwd.clone()) {
+        return Ok(pwd.unwrap());
+    }
+    getcwd()
+}
+
+/// Resolves `.` and `..` components without touching the filesystem.
+fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            path::Component::CurDir => {}
+            path::Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// The path `PWD` should get when going to `target`, or `None` when the physical
+/// path has to be used.
+fn logical_target(target: &Path, physical: bool, old_pwd: Option<&Path>) -> Option<PathBuf> {
+    if physical {
+        return None;
+    }
+    let joined = match old_pwd {
+        Some(pwd) if target.is_relative() => pwd.join(target),
+        _ => target.to_path_buf(),
+    };
+    let normalized = normalize_path(&joined);
+    // If the lexical path doesn't lead anywhere, fall back to the physical one
+    if normalized.is_dir() {
+        Some(normalized)
+    } else {
+        None
+    }
+}
+
+/// Changes the working directory and keeps `PWD` and `OLDPWD` up to date.
+///
+/// Unless `physical` is set, `..` is resolved against `PWD` instead of the real
+/// parent of a symlinked directory, like the `-L` mode of a POSIX `cd`.
+pub fn change_dir(target: &Path, physical: bool) -> Result<PathBuf, String> {
+    info!("builtins::dirs::change_dir(): Changing directory to {}", target.to_string_lossy());
+    let old_pwd = logical_cwd().ok();
+    let logical_target = logical_target(target, physical, old_pwd.as_deref());
+
+    let destination = logical_target.as_deref().unwrap_or(target);
+    if let Err(err) = env::set_current_dir(destination) {
+        error!("builtins::dirs::change_dir(): Cannot change the working directory");
+        error!("builtins::dirs::change_dir(): {err}");
+        return Err(describe(&err));
+    }
+
+    let new_pwd = match logical_target {
+        Some(logical) => logical,
+        None => getcwd()?,
+    };
+    if let Some(old_pwd) = old_pwd {
+        env::set_var("OLDPWD", old_pwd);
+    }
+    env::set_var("PWD", &new_pwd);
+    Ok(new_pwd)
+}
+
+/// Checks that `change_dir` could go to `target` and returns the `PWD` it would
+/// set, without changing the working directory or the environment.
+pub fn check_dir(target: &Path, physical: bool) -> Result<PathBuf, String> {
+    info!("builtins::dirs::check_dir(): Checking the directory {}", target.to_string_lossy());
+    let logical_target = logical_target(target, physical, logical_cwd().ok().as_deref());
+
+    let destination = logical_target.as_deref().unwrap_or(target);
+    if destination.exists() && !destination.is_dir() {
+        return Err(describe(&io::Error::from_raw_os_error(libc::ENOTDIR)));
+    }
+    let Ok(c_destination) = CString::new(destination.as_os_str().as_bytes()) else {
+        return Err(describe(&io::Error::from_raw_os_error(libc::ENOENT)));
+    };
+    // Entering a directory needs the search permission on it
+    if unsafe { libc::access(c_destination.as_ptr(), libc::X_OK) } != 0 {
+        let err = io::Error::last_os_error();
+        error!("builtins::dirs::check_dir(): {err}");
+        return Err(describe(&err));
+    }
+
+    match logical_target {
+        Some(logical) => Ok(logical),
+        None => fs::canonicalize(destination).map_err(|err| describe(&err)),
+    }
+}
+
+/// Goes to `target` with `change_dir`, or only checks it with `check_dir` when the
+/// builtin runs in a subshell, so the shell stays where it is.
+fn enter_dir(state: &ShellState, target: &Path, physical: bool) -> Result<PathBuf, String> {
+    if state.subshell {
+        check_dir(target, physical)
+    } else {
+        change_dir(target, physical)
+    }
+}
+
+/// Finds the directory `cd` should go to. The boolean is true when the directory
+/// was found through a non-empty `CDPATH` entry and should be printed.
+fn resolve_cd_target(dir: &str) -> (PathBuf, bool) {
+    let target = PathBuf::from(dir);
+    let first_component = target.components().next();
+    let skip_cdpath = target.is_absolute()
+        || matches!(
+            first_component,
+            Some(path::Component::CurDir) | Some(path::Component::ParentDir)
+        );
+
+    if !skip_cdpath {
+        if let Some(cdpath) = env::var_os("CDPATH") {
+            for entry in env::split_paths(&cdpath) {
+                let from_entry = entry.as_os_str().is_empty();
+                let candidate = if from_entry {
+                    target.clone()
+                } else {
+                    entry.join(&target)
+                };
+                if candidate.is_dir() {
+                    info!("builtins::dirs::resolve_cd_target(): Found {} through CDPATH", candidate.to_string_lossy());
+                    return (candidate, !from_entry);
+                }
+            }
+        }
+    }
+    (target, false)
+}
+
+/// Shows a numbered list of directories and returns the one the user picks.
+fn pick_directory(io: &mut Io, candidates: &[(f64, PathBuf)]) -> Option<PathBuf> {
+    for (index, (score, dir)) in candidates.iter().enumerate() {
+        io.out(format!("{:>2}) {:<8.1} {}", index + 1, score, dir.to_string_lossy()));
+    }
+    io.flush();
+    let answer = io.ask(&format!("{}: choose a directory [1-{}]: ", "z".green(), candidates.len()))?;
+    let choice = answer.parse::<usize>().ok()?;
+    candidates
+        .get(choice.checked_sub(1)?)
+        .map(|(_, dir)| dir.clone())
+}
+
+pub struct CdCmd;
+
+impl Builtin for CdCmd {
+    fn name(&self) -> &'static str {
+        "cd"
+    }
+
+    fn command(&self) -> clap::Command {
+        CdArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Directories
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: CdArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+
+        let (target, print_dir) = match opts.dir.as_deref() {
+            None => match env::var_os("HOME") {
+                Some(home) if !home.is_empty() => (PathBuf::from(home), false),
+                _ => {
+                    io.err("cd: HOME not set");
+                    return ExitStatus::FAILURE;
+                }
+            },
+            Some("-") => match env::var_os("OLDPWD") {
+                Some(old_pwd) if !old_pwd.is_empty() => (PathBuf::from(old_pwd), true),
+                _ => {
+                    io.err("cd: OLDPWD not set");
+                    return ExitStatus::FAILURE;
+                }
+            },
+            Some("") => return ExitStatus::SUCCESS,
+            Some(dir) => resolve_cd_target(dir),
+        };
+
+        // The correction is only offered when someone can answer it
+        let result = match enter_dir(state, &target, opts.physical_opt) {
+            Err(err) if !target.exists() && io.stdin.is_terminal() => match suggest::correct_dir_path(&target) {
+                Some(corrected) => {
+                    let question = format!(
+                        "cd: {}: No such directory. Did you mean {}?",
+                        target.to_string_lossy(),
+                        corrected.to_string_lossy().bold()
+                    );
+                    if io.confirm(&question) {
+                        enter_dir(state, &corrected, opts.physical_opt).map_err(|err| (corrected, err))
+                    } else {
+                        return ExitStatus::FAILURE;
+                    }
+                }
+                None => Err((target, err)),
+            },
+            other => other.map_err(|err| (target, err)),
+        };
+
+        match result {
+            Ok(new_dir) => {
+                if print_dir {
+                    io.out(new_dir.to_string_lossy());
+                }
+                if !state.subshell {
+                    frecency::record_visit(&new_dir);
+                }
+                ExitStatus::SUCCESS
+            }
+            Err((target, err)) => {
+                io.err(format!("cd: {}: {err}", target.to_string_lossy()));
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+pub struct PwdCmd;
+
+impl Builtin for PwdCmd {
+    fn name(&self) -> &'static str {
+        "pwd"
+    }
+
+    fn command(&self) -> clap::Command {
+        PwdArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, _state: &mut ShellState) -> ExitStatus {
+        let opts: PwdArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        if !opts.physical_opt {
+            if let Ok(pwd) = logical_cwd() {
+                io.out(pwd.to_string_lossy());
+                return ExitStatus::SUCCESS;
+            }
+        }
+        match getcwd().and_then(|wkd| wkd.canonicalize().map_err(|err| describe(&err))) {
+            Ok(wkd) => {
+                io.out(wkd.to_string_lossy());
+                ExitStatus::SUCCESS
+            }
+            Err(err) => {
+                io.err(format!("pwd: {err}"));
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+pub struct PushdCmd;
+
+impl Builtin for PushdCmd {
+    fn name(&self) -> &'static str {
+        "pushd"
+    }
+
+    fn command(&self) -> clap::Command {
+        PushdArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Directories
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: PushdArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let mut stack = state.dir_stack.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut listing = match stack.listing() {
+            Ok(listing) => listing,
+            Err(err) => {
+                io.err(format!("pushd: {err}"));
+                return ExitStatus::FAILURE;
+            }
+        };
+
+        match opts.target {
+            None => {
+                if listing.len() < 2 {
+                    io.err("pushd: no other directory");
+                    return ExitStatus::FAILURE;
+                }
+                listing.swap(0, 1);
+            }
+            Some(target) => match DirStack::parse_index(&target, listing.len()) {
+                Some(Ok(index)) => listing.rotate_left(index),
+                Some(Err(err)) => {
+                    io.err(format!("pushd: {err}"));
+                    return ExitStatus::FAILURE;
+                }
+                None => listing.insert(0, PathBuf::from(target)),
+            },
+        }
+
+        if opts.no_cd_opt {
+            // The working directory stays on top, the new entry goes right below it
+            let cwd = listing.remove(1);
+            listing.insert(0, cwd);
+        } else {
+            match enter_dir(state, &listing[0], false) {
+                Ok(new_dir) => listing[0] = new_dir,
+                Err(err) => {
+                    io.err(format!("pushd: {}: {err}", listing[0].to_string_lossy()));
+                    return ExitStatus::FAILURE;
+                }
+            }
+        }
+        stack.entries = listing.split_off(1);
+        if state.subshell {
+            stack.cwd = listing.pop();
+        }
+
+        if let Err(err) = stack.print(io, false, false, false) {
+            io.err(format!("pushd: {err}"));
+            return ExitStatus::FAILURE;
+        }
+        ExitStatus::SUCCESS
+    }
+}
+
+pub struct PopdCmd;
+
+impl Builtin for PopdCmd {
+    fn name(&self) -> &'static str {
+        "popd"
+    }
+
+    fn command(&self) -> clap::Command {
+        PopdArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: PopdArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let mut stack = state.dir_stack.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut listing = match stack.listing() {
+            Ok(listing) => listing,
+            Err(err) => {
+                io.err(format!("popd: {err}"));
+                return ExitStatus::FAILURE;
+            }
+        };
+        if listing.len() < 2 {
+            io.err("popd: directory stack empty");
+            return ExitStatus::FAILURE;
+        }
+
+        let index = match opts.target {
+            None => 0,
+            Some(target) => match DirStack::parse_index(&target, listing.len()) {
+                Some(Ok(index)) => index,
+                Some(Err(err)) => {
+                    io.err(format!("popd: {err}"));
+                    return ExitStatus::FAILURE;
+                }
+                None => {
+                    io.err(format!("popd: {target}: invalid argument"));
+                    return ExitStatus::USAGE;
+                }
+            },
+        };
+
+        if index == 0 && opts.no_cd_opt {
+            // Without changing directory the working directory can't be dropped
+            listing.remove(1);
+        } else {
+            listing.remove(index);
+            if index == 0 {
+                match enter_dir(state, &listing[0], false) {
+                    Ok(new_dir) => listing[0] = new_dir,
+                    Err(err) => {
+                        io.err(format!("popd: {}: {err}", listing[0].to_string_lossy()));
+                        return ExitStatus::FAILURE;
+                    }
+                }
+            }
+        }
+        stack.entries = listing.split_off(1);
+        if state.subshell {
+            stack.cwd = listing.pop();
+        }
+
+        if let Err(err) = stack.print(io, false, false, false) {
+            io.err(format!("popd: {err}"));
+            return ExitStatus::FAILURE;
+        }
+        ExitStatus::SUCCESS
+    }
+}
+
+pub struct DirsCmd;
+
+impl Builtin for DirsCmd {
+    fn name(&self) -> &'static str {
+        "dirs"
+    }
+
+    fn command(&self) -> clap::Command {
+        DirsArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: DirsArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let mut stack = state.dir_stack.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if opts.clear_opt {
+            stack.entries.clear();
+            return ExitStatus::SUCCESS;
+        }
+        if let Some(target) = opts.target {
+            let listing = match stack.listing() {
+                Ok(listing) => listing,
+                Err(err) => {
+                    io.err(format!("dirs: {err}"));
+                    return ExitStatus::FAILURE;
+                }
+            };
+            return match DirStack::parse_index(&target, listing.len()) {
+                Some(Ok(index)) => {
+                    io.out(DirStack::display_path(&listing[index], opts.long_opt));
+                    ExitStatus::SUCCESS
+                }
+                Some(Err(err)) => {
+                    io.err(format!("dirs: {err}"));
+                    ExitStatus::FAILURE
+                }
+                None => {
+                    io.err(format!("dirs: {target}: invalid argument"));
+                    ExitStatus::USAGE
+                }
+            };
+        }
+        if let Err(err) = stack.print(io, opts.long_opt, opts.per_line_opt, opts.verbose_opt) {
+            io.err(format!("dirs: {err}"));
+            return ExitStatus::FAILURE;
+        }
+        ExitStatus::SUCCESS
+    }
+}
+
+pub struct ZCmd;
+
+impl Builtin for ZCmd {
+    fn name(&self) -> &'static str {
+        "z"
+    }
+
+    fn command(&self) -> clap::Command {
+        ZArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: ZArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let database = FrecencyDatabase::load();
+        let mut candidates = database.matches(&opts.fragments);
+
+        if opts.list_opt || opts.fragments.is_empty() {
+            for (score, dir) in candidates.iter().rev() {
+                io.out(format!("{:<10.1} {}", score, dir.to_string_lossy()));
+            }
+            return ExitStatus::SUCCESS;
+        }
+        if candidates.is_empty() {
+            io.err(format!("z: no match for {}", opts.fragments.join(" ")));
+            return ExitStatus::FAILURE;
+        }
+
+        // The best match wins, the list is only shown when asked for or when the best
+        // matches have the same score, and only if someone can answer it
+        candidates.truncate(9);
+        let tied = candidates.len() > 1 && candidates[0].0 == candidates[1].0;
+        let target = if (opts.interactive_opt || tied) && io.stdin.is_terminal() {
+            match pick_directory(io, &candidates) {
+                Some(dir) => dir,
+                None => return ExitStatus::FAILURE,
+            }
+        } else {
+            candidates[0].1.clone()
+        };
+
+        match enter_dir(state, &target, false) {
+            Ok(new_dir) => {
+                if !state.subshell {
+                    frecency::record_visit(&new_dir);
+                }
+                ExitStatus::SUCCESS
+            }
+            Err(err) => {
+                io.err(format!("z: {}: {err}", target.to_string_lossy()));
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_indexes_count_from_both_ends() {
+        let cases = [
+            ("+0", Some(Ok(0))),
+            ("+3", Some(Ok(3))),
+            ("-0", Some(Ok(3))),
+            ("-3", Some(Ok(0))),
+            ("+4", Some(Err(String::from("+4: directory stack index out of range")))),
+            ("-4", Some(Err(String::from("-4: directory stack index out of range")))),
+            ("+x", Some(Err(String::from("+x: invalid number")))),
+            ("dir", None),
+        ];
+        for (arg, expected) in cases {
+            assert_eq!(DirStack::parse_index(arg, 4), expected, "{arg}");
+        }
+    }
+}
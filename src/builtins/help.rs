@@ -0,0 +1,99 @@
+use clap::{CommandFactory, Parser};
+use crossterm::style::Stylize;
+
+use super::{parse_args, registry, Builtin, CompletionHint, ExitStatus, Io, ShellState};
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Show the builtins and functions, or the usage of one of them", long_about = None)]
+struct HelpArgs {
+    #[arg(name = "NAME")]
+    name: Option<String>,
+}
+
+pub struct HelpCmd;
+
+impl HelpCmd {
+    fn list(io: &mut Io, state: &ShellState) {
+        let functions: Vec<(String, String)> = state
+            .functions()
+            .into_iter()
+            .map(|(name, doc)| {
+                let summary = doc.and_then(|doc| doc.lines().next().map(String::from)).unwrap_or_default();
+                (name, summary)
+            })
+            .collect();
+
+        let builtins: Vec<(&str, String)> = registry()
+            .iter()
+            .map(|builtin| (builtin.name(), builtin.summary()))
+            .collect();
+        let width = builtins
+            .iter()
+            .map(|(name, _)| name.len())
+            .chain(functions.iter().map(|(name, _)| name.len()))
+            .max()
+            .unwrap_or(0);
+
+        // Styled only on a terminal, so the list can be piped to other commands
+        let color = io.stdout.is_terminal();
+        let heading = |text: &str| if color { text.bold().to_string() } else { text.to_string() };
+        io.out(heading("Builtins:"));
+        for (name, summary) in builtins {
+            let name = format!("{name:<width$}");
+            let name = if color { name.green().to_string() } else { name };
+            io.out(format!("  {name}  {summary}").trim_end());
+        }
+        if !functions.is_empty() {
+            io.out("");
+            io.out(heading("Functions:"));
+            for (name, summary) in functions {
+                let name = format!("{name:<width$}");
+                let name = if color { name.cyan().to_string() } else { name };
+                io.out(format!("  {name}  {summary}").trim_end());
+            }
+        }
+        io.out("");
+        io.out("Use 'help NAME' to see the usage of a builtin or the description of a function");
+    }
+}
+
+impl Builtin for HelpCmd {
+    fn name(&self) -> &'static str {
+        "help"
+    }
+
+    fn command(&self) -> clap::Command {
+        HelpArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: HelpArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let Some(name) = opts.name else {
+            HelpCmd::list(io, state);
+            return ExitStatus::SUCCESS;
+        };
+
+        if let Some(builtin) = registry().get(&name) {
+            io.out(builtin.usage().trim_end());
+            return ExitStatus::SUCCESS;
+        }
+        if let Some(doc) = state.functions().remove(&name) {
+            let shown = if io.stdout.is_terminal() { name.as_str().cyan().to_string() } else { name.clone() };
+            io.out(format!("{shown}: function registered by a script"));
+            if let Some(doc) = doc {
+                io.out("");
+                io.out(doc);
+            }
+            return ExitStatus::SUCCESS;
+        }
+        io.err(format!("help: no help topics match `{name}'"));
+        ExitStatus::FAILURE
+    }
+}
@@ -0,0 +1,386 @@
+use clap::{CommandFactory, Parser};
+use crossterm::style::Stylize;
+use std::sync::atomic::Ordering;
+
+use super::{parse_args, registry, Builtin, CompletionHint, ExitStatus, Io, ShellState};
+use crate::commands::{ExecError, ExternalCommands};
+use crate::pipeline;
+use crate::resolve::{CommandKind, Lookup};
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Remember or show the location of commands", long_about = None)]
+struct HashArgs {
+    #[arg(short = 'r', help = "Forget every remembered location and scan PATH again on the next command")]
+    reset_opt: bool,
+
+    #[arg(short = 'd', help = "Forget the remembered location of each NAME")]
+    delete_opt: bool,
+
+    #[arg(short = 't', help = "Print the full path of each NAME")]
+    print_opt: bool,
+
+    #[arg(name = "NAME")]
+    names: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Scan the PATH directories again", long_about = None)]
+struct RehashArgs {}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Show how each name would be interpreted as a command", long_about = None)]
+struct TypeArgs {
+    #[arg(short = 'a', help = "Show every alias, function, builtin and file named NAME")]
+    all_opt: bool,
+
+    #[arg(short = 't', help = "Print only the kind: alias, function, builtin or file")]
+    type_opt: bool,
+
+    #[arg(short = 'p', help = "Print only the path of NAME if it is a file")]
+    path_opt: bool,
+
+    #[arg(name = "NAME", required = true)]
+    names: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Show the full path of commands", long_about = None)]
+struct WhichArgs {
+    #[arg(short = 'a', help = "Show every match instead of only the first one")]
+    all_opt: bool,
+
+    #[arg(name = "NAME", required = true)]
+    names: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Run a command skipping aliases and functions, or describe it", long_about = None, disable_version_flag = true)]
+struct CommandArgs {
+    #[arg(short = 'v', help = "Print the path or the name that would run")]
+    short_opt: bool,
+
+    #[arg(short = 'V', help = "Describe how NAME would be interpreted, like type")]
+    verbose_opt: bool,
+
+    #[arg(name = "NAME [ARGS]", trailing_var_arg = true, allow_hyphen_values = true)]
+    names: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Run a shell builtin, even if a function or alias has the same name", long_about = None)]
+struct BuiltinArgs {
+    #[arg(name = "BUILTIN [ARGS]", trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+fn describe_kind(name: &str, kind: &CommandKind) -> String {
+    match kind {
+        CommandKind::Alias(value) => format!("{name} is aliased to `{value}'"),
+        CommandKind::Function => format!("{name} is a function"),
+        CommandKind::Builtin => format!("{name} is a shell builtin"),
+        CommandKind::External(path) => format!("{name} is {}", path.to_string_lossy()),
+    }
+}
+
+/// Runs `words` as an external command and waits for it.
+fn run_external(words: &[String], io: &mut Io, state: &ShellState) -> ExitStatus {
+    let spawned = state
+        .external
+        .resolve_command(&words[0])
+        .and_then(|path| ExternalCommands::spawn(&path, &words[1..], io));
+    match spawned {
+        Ok(mut child) => {
+            state.foreground_pid.store(child.id(), Ordering::SeqCst);
+            let status = pipeline::wait_child(&mut child);
+            state.foreground_pid.store(0, Ordering::SeqCst);
+            status
+        }
+        Err(err @ ExecError::NotFound(_)) => {
+            io.err(format!("command: {err}"));
+            ExitStatus(127)
+        }
+        Err(err) => {
+            io.err(format!("command: {err}"));
+            ExitStatus(126)
+        }
+    }
+}
+
+pub struct TypeCmd;
+
+impl Builtin for TypeCmd {
+    fn name(&self) -> &'static str {
+        "type"
+    }
+
+    fn command(&self) -> clap::Command {
+        TypeArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: TypeArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let resolver = state.resolver();
+        let mut status = ExitStatus::SUCCESS;
+        for name in opts.names.iter() {
+            let mut kinds = resolver.resolve_all(name, Lookup::ALL);
+            if kinds.is_empty() {
+                if !opts.type_opt && !opts.path_opt {
+                    io.err(format!("type: {name}: not found"));
+                }
+                status = ExitStatus::FAILURE;
+                continue;
+            }
+            if !opts.all_opt {
+                kinds.truncate(1);
+            }
+            for kind in kinds.iter() {
+                if opts.path_opt {
+                    if let CommandKind::External(path) = kind {
+                        io.out(path.to_string_lossy());
+                    }
+                } else if opts.type_opt {
+                    let kind_name = match kind {
+                        CommandKind::Alias(_) => "alias",
+                        CommandKind::Function => "function",
+                        CommandKind::Builtin => "builtin",
+                        CommandKind::External(_) => "file",
+                    };
+                    io.out(kind_name);
+                } else {
+                    io.out(describe_kind(name, kind));
+                }
+            }
+        }
+        status
+    }
+}
+
+pub struct WhichCmd;
+
+impl Builtin for WhichCmd {
+    fn name(&self) -> &'static str {
+        "which"
+    }
+
+    fn command(&self) -> clap::Command {
+        WhichArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: WhichArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let resolver = state.resolver();
+        let mut status = ExitStatus::SUCCESS;
+        for name in opts.names.iter() {
+            let mut kinds = resolver.resolve_all(name, Lookup::ALL);
+            if kinds.is_empty() {
+                io.err(format!("which: {name}: not found"));
+                status = ExitStatus::FAILURE;
+                continue;
+            }
+            if !opts.all_opt {
+                kinds.truncate(1);
+            }
+            for kind in kinds.iter() {
+                match kind {
+                    CommandKind::Alias(value) => io.out(format!("{name}: aliased to {value}")),
+                    CommandKind::Function => io.out(format!("{name}: shell function")),
+                    CommandKind::Builtin => io.out(format!("{name}: shell built-in command")),
+                    CommandKind::External(path) => io.out(path.to_string_lossy()),
+                }
+            }
+        }
+        status
+    }
+}
+
+/// `command name args` runs `name` skipping aliases and functions, `command -v` and
+/// `command -V` describe it.
+pub struct CommandCmd;
+
+impl Builtin for CommandCmd {
+    fn name(&self) -> &'static str {
+        "command"
+    }
+
+    fn command(&self) -> clap::Command {
+        CommandArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: CommandArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+
+        if !opts.short_opt && !opts.verbose_opt {
+            let Some(name) = opts.names.first() else {
+                return ExitStatus::SUCCESS;
+            };
+            return match state.resolver().resolve(name, Lookup::COMMAND) {
+                Some(CommandKind::Builtin) => match registry().get(name) {
+                    Some(builtin) => builtin.run(opts.names.clone(), io, state),
+                    None => ExitStatus::FAILURE,
+                },
+                _ => run_external(&opts.names, io, state),
+            };
+        }
+
+        let resolver = state.resolver();
+        let mut status = ExitStatus::SUCCESS;
+        for name in opts.names.iter() {
+            let kind = resolver.resolve(name, Lookup::ALL);
+            match (kind, opts.verbose_opt) {
+                (None, true) => {
+                    io.err(format!("command: {name}: not found"));
+                    status = ExitStatus::FAILURE;
+                }
+                (None, false) => status = ExitStatus::FAILURE,
+                (Some(kind), true) => io.out(describe_kind(name, &kind)),
+                (Some(CommandKind::Alias(value)), false) => io.out(format!("alias {name}='{value}'")),
+                (Some(CommandKind::External(path)), false) => io.out(path.to_string_lossy()),
+                (Some(_), false) => io.out(name),
+            }
+        }
+        status
+    }
+}
+
+/// `builtin name args` runs the builtin `name`, skipping functions and aliases.
+pub struct BuiltinCmd;
+
+impl Builtin for BuiltinCmd {
+    fn name(&self) -> &'static str {
+        "builtin"
+    }
+
+    fn command(&self) -> clap::Command {
+        BuiltinArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: BuiltinArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let Some(name) = opts.command.first() else {
+            return ExitStatus::SUCCESS;
+        };
+        match registry().get(name) {
+            Some(builtin) => builtin.run(opts.command.clone(), io, state),
+            None => {
+                io.err(format!("builtin: {name}: not a shell builtin"));
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+pub struct HashCmd;
+
+impl Builtin for HashCmd {
+    fn name(&self) -> &'static str {
+        "hash"
+    }
+
+    fn command(&self) -> clap::Command {
+        HashArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Commands
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: HashArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        let external = &state.external;
+        let mut status = ExitStatus::SUCCESS;
+
+        if opts.reset_opt {
+            external.clear_index();
+        }
+        if opts.delete_opt {
+            for name in opts.names.iter() {
+                if !external.forget(name) {
+                    io.err(format!("hash: {name}: not found"));
+                    status = ExitStatus::FAILURE;
+                }
+            }
+            return status;
+        }
+        for name in opts.names.iter() {
+            match external.find_executable_command(name) {
+                Some(path) => {
+                    if opts.print_opt {
+                        io.out(path.to_string_lossy());
+                    }
+                }
+                None => {
+                    io.err(format!("hash: {name}: not found"));
+                    status = ExitStatus::FAILURE;
+                }
+            }
+        }
+
+        if opts.names.is_empty() && !opts.reset_opt {
+            let remembered = external.remembered();
+            if remembered.is_empty() {
+                io.err("hash: hash table empty");
+                return status;
+            }
+            if io.stdout.is_terminal() {
+                io.out(format!("{}\t{}", "hits".bold(), "command".bold()));
+            } else {
+                io.out("hits\tcommand");
+            }
+            for (_, hits, path) in remembered {
+                io.out(format!("{:>4}\t{}", hits, path.to_string_lossy()));
+            }
+        }
+        status
+    }
+}
+
+pub struct RehashCmd;
+
+impl Builtin for RehashCmd {
+    fn name(&self) -> &'static str {
+        "rehash"
+    }
+
+    fn command(&self) -> clap::Command {
+        RehashArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        if let Err(status) = parse_args::<RehashArgs>(args, io) {
+            return status;
+        }
+        state.external.rehash();
+        ExitStatus::SUCCESS
+    }
+}
@@ -0,0 +1,689 @@
+use chrono::{DateTime, Local};
+use clap::{ArgAction, CommandFactory, Parser, ValueEnum};
+use crossterm::style::Stylize;
+use log::{error, info};
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ffi::{CStr, OsStr, OsString};
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use super::{parse_args, Builtin, CompletionHint, ExitStatus, Io, ShellState};
+use crate::colors::FileColors;
+use crate::commands::columnize_text;
+use crate::git::{FileStatus, Repository};
+use crate::pipeline::describe;
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum SortBy {
+    Name,
+    Size,
+    Time,
+    Extension,
+    None,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.1.0", about = "List files", long_about = None, disable_help_flag = true)]
+struct LsArgs {
+    #[arg(short = 'l', help = "Use a long listing format: mode, links, owner, group, size and modification time")]
+    long_opt: bool,
+
+    #[arg(short = 'a', long = "all", help = "Show hidden entries, including . and ..")]
+    all_opt: bool,
+
+    #[arg(short = 'A', long = "almost-all", help = "Show hidden entries, except . and ..")]
+    almost_all_opt: bool,
+
+    #[arg(short = 'h', long = "human-readable", help = "Print sizes like 1K, 234M or 2G")]
+    human_opt: bool,
+
+    #[arg(long = "sort", value_enum, value_name = "WORD", default_value_t = SortBy::Name, help = "Sort the entries by WORD")]
+    sort: SortBy,
+
+    #[arg(short = 'S', help = "Sort by size, largest first")]
+    size_opt: bool,
+
+    #[arg(short = 't', help = "Sort by modification time, newest first")]
+    time_opt: bool,
+
+    #[arg(short = 'X', help = "Sort by extension")]
+    extension_opt: bool,
+
+    #[arg(short = 'r', long = "reverse", help = "Reverse the order of the sort")]
+    reverse_opt: bool,
+
+    #[arg(short = 'R', long = "recursive", help = "List the subdirectories recursively")]
+    recursive_opt: bool,
+
+    #[arg(short = 'd', long = "directory", help = "List the directories themselves, not their contents")]
+    directory_opt: bool,
+
+    #[arg(long = "git", help = "Show the git status of each entry: staged and unstaged changes, N for untracked and I for ignored")]
+    git_opt: bool,
+
+    #[arg(long = "tree", value_name = "DEPTH", num_args = 0..=1, require_equals = true, help = "Show the directories as a tree, DEPTH levels deep. The files ignored by git are left out")]
+    tree: Option<Option<usize>>,
+
+    #[arg(long = "help", action = ArgAction::Help, help = "Print help")]
+    help: Option<bool>,
+
+    #[arg(name = "PATH", default_value = ".")]
+    paths: Vec<PathBuf>,
+}
+
+impl LsArgs {
+    fn sort_by(&self) -> SortBy {
+        if self.size_opt {
+            SortBy::Size
+        } else if self.time_opt {
+            SortBy::Time
+        } else if self.extension_opt {
+            SortBy::Extension
+        } else {
+            self.sort
+        }
+    }
+}
+
+/// A file to be listed. `metadata` is the one of the entry itself, not of the
+/// target of a symlink.
+struct Entry {
+    name: OsString,
+    path: PathBuf,
+    metadata: Option<Metadata>,
+}
+
+impl Entry {
+    fn new(name: OsString, path: PathBuf) -> Self {
+        let metadata = fs::symlink_metadata(&path).ok();
+        Entry { name, path, metadata }
+    }
+
+    fn is_symlink(&self) -> bool {
+        self.metadata.as_ref().is_some_and(|md| md.file_type().is_symlink())
+    }
+
+    /// Directories to go into with `-R`. Symlinks are not followed.
+    fn is_real_dir(&self) -> bool {
+        self.metadata.as_ref().is_some_and(|md| md.is_dir()) && self.name != "." && self.name != ".."
+    }
+
+    fn size(&self) -> u64 {
+        self.metadata.as_ref().map_or(0, |md| md.len())
+    }
+
+    fn modified(&self) -> SystemTime {
+        self.metadata
+            .as_ref()
+            .and_then(|md| md.modified().ok())
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+
+    fn extension(&self) -> String {
+        let name = self.name.to_string_lossy();
+        match name.trim_start_matches('.').rsplit_once('.') {
+            Some((_, extension)) => extension.to_string(),
+            None => String::new(),
+        }
+    }
+}
+
+/// `name` as it can be shown in the terminal: the bytes that aren't UTF-8 and the
+/// control characters are escaped, like `\xff` and `\n`.
+fn escape_name(name: &OsStr) -> String {
+    let mut escaped = String::new();
+    for chunk in name.as_bytes().utf8_chunks() {
+        for c in chunk.valid().chars() {
+            if c.is_control() {
+                escaped.extend(c.escape_default());
+            } else {
+                escaped.push(c);
+            }
+        }
+        for byte in chunk.invalid() {
+            escaped.push_str(&format!("\\x{byte:02x}"));
+        }
+    }
+    escaped
+}
+
+fn compare_names(a: &Entry, b: &Entry) -> Ordering {
+    let key = |entry: &Entry| entry.name.to_string_lossy().trim_start_matches('.').to_lowercase();
+    key(a).cmp(&key(b)).then_with(|| a.name.cmp(&b.name))
+}
+
+fn sort_entries(entries: &mut [Entry], opts: &LsArgs) {
+    match opts.sort_by() {
+        SortBy::Name => entries.sort_by(compare_names),
+        SortBy::Size => entries.sort_by(|a, b| b.size().cmp(&a.size()).then_with(|| compare_names(a, b))),
+        SortBy::Time => entries.sort_by(|a, b| b.modified().cmp(&a.modified()).then_with(|| compare_names(a, b))),
+        SortBy::Extension => {
+            entries.sort_by(|a, b| a.extension().cmp(&b.extension()).then_with(|| compare_names(a, b)))
+        }
+        SortBy::None => {}
+    }
+    if opts.reverse_opt {
+        entries.reverse();
+    }
+}
+
+/// The `drwxr-xr-x` string of `ls -l`.
+pub(super) fn mode_string(metadata: &Metadata) -> String {
+    let file_type = metadata.file_type();
+    let kind = if file_type.is_dir() {
+        'd'
+    } else if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+    let mode = metadata.mode();
+    let bit = |mask: u32, ch: char| if mode & mask != 0 { ch } else { '-' };
+    // The execute bit shares its place with setuid, setgid and sticky
+    let special = |exec_mask: u32, special_mask: u32, set: char, unset: char| {
+        match (mode & exec_mask != 0, mode & special_mask != 0) {
+            (true, true) => set,
+            (false, true) => unset,
+            (true, false) => 'x',
+            (false, false) => '-',
+        }
+    };
+    [
+        kind,
+        bit(0o400, 'r'),
+        bit(0o200, 'w'),
+        special(0o100, 0o4000, 's', 'S'),
+        bit(0o040, 'r'),
+        bit(0o020, 'w'),
+        special(0o010, 0o2000, 's', 'S'),
+        bit(0o004, 'r'),
+        bit(0o002, 'w'),
+        special(0o001, 0o1000, 't', 'T'),
+    ]
+    .iter()
+    .collect()
+}
+
+/// Name of the user `uid`, or the number if it has no name.
+pub(super) fn user_name(uid: u32) -> String {
+    let passwd = unsafe { libc::getpwuid(uid) };
+    if passwd.is_null() {
+        return uid.to_string();
+    }
+    unsafe { CStr::from_ptr((*passwd).pw_name) }.to_string_lossy().to_string()
+}
+
+/// Name of the group `gid`, or the number if it has no name.
+pub(super) fn group_name(gid: u32) -> String {
+    let group = unsafe { libc::getgrgid(gid) };
+    if group.is_null() {
+        return gid.to_string();
+    }
+    unsafe { CStr::from_ptr((*group).gr_name) }.to_string_lossy().to_string()
+}
+
+/// Sizes like `ls -h`: one decimal below 10, rounded up like GNU does.
+pub(super) fn human_size(size: u64) -> String {
+    const UNITS: [&str; 6] = ["K", "M", "G", "T", "P", "E"];
+    if size < 1024 {
+        return size.to_string();
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    loop {
+        let rounded = if value < 10.0 { (value * 10.0).ceil() / 10.0 } else { value.ceil() };
+        // Rounding up can reach the next unit, 1023.9K is 1.0M
+        if rounded >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        } else if rounded < 10.0 {
+            return format!("{rounded:.1}{}", UNITS[unit]);
+        } else {
+            return format!("{rounded:.0}{}", UNITS[unit]);
+        }
+    }
+}
+
+/// The modification time of `ls -l`: the hour for recent files, the year for the
+/// ones older than six months or in the future.
+fn format_mtime(time: SystemTime) -> String {
+    const SIX_MONTHS: Duration = Duration::from_secs(182 * 24 * 60 * 60);
+    let datetime: DateTime<Local> = time.into();
+    let recent = SystemTime::now()
+        .duration_since(time)
+        .is_ok_and(|age| age < SIX_MONTHS);
+    if recent {
+        datetime.format("%b %e %H:%M").to_string()
+    } else {
+        datetime.format("%b %e  %Y").to_string()
+    }
+}
+
+/// The options of a run of `ls` and the colors to show the names with.
+struct Lister {
+    opts: LsArgs,
+    colors: FileColors,
+    /// The output is not a terminal: the names are written as they are, without
+    /// colors or escapes, so the next command of a pipeline gets the real names.
+    raw: bool,
+    /// The repositories of the directories listed so far, `None` when a directory
+    /// isn't in one.
+    repositories: RefCell<HashMap<PathBuf, Option<Rc<Repository>>>>,
+}
+
+/// The number of directories and files `ls --tree` showed.
+#[derive(Default)]
+struct TreeCount {
+    dirs: usize,
+    files: usize,
+}
+
+impl Lister {
+    fn new(opts: LsArgs, colors: FileColors, raw: bool) -> Self {
+        Lister { opts, colors, raw, repositories: RefCell::new(HashMap::new()) }
+    }
+
+    /// The name of the file at `path` as it is written in the output.
+    fn shown(&self, name: &OsStr, path: &Path, metadata: Option<&Metadata>) -> OsString {
+        if self.raw {
+            name.to_os_string()
+        } else {
+            self.colors.paint(&escape_name(name), path, metadata).into()
+        }
+    }
+
+    fn shown_entry(&self, entry: &Entry) -> OsString {
+        self.shown(&entry.name, &entry.path, entry.metadata.as_ref())
+    }
+
+    /// A path in a header or in an error message.
+    fn shown_path(&self, path: &Path) -> OsString {
+        if self.raw {
+            path.as_os_str().to_os_string()
+        } else {
+            escape_name(path.as_os_str()).into()
+        }
+    }
+
+    /// The repository `dir` is in. Each repository is only read once.
+    fn repository(&self, dir: &Path) -> Option<Rc<Repository>> {
+        let mut repositories = self.repositories.borrow_mut();
+        if let Some(found) = repositories.get(dir) {
+            return found.clone();
+        }
+        // Looked up for each directory: a nested repository or a submodule has its
+        // own status, even under a repository that is already read
+        let root = Repository::root(dir);
+        let known = root.and_then(|root| {
+            repositories.values().flatten().find(|repository| repository.root == root).cloned()
+        });
+        let found = known.or_else(|| Repository::open(dir).map(Rc::new));
+        repositories.insert(dir.to_path_buf(), found.clone());
+        found
+    }
+
+    fn git_status(&self, entry: &Entry) -> Option<FileStatus> {
+        let name = entry.path.file_name()?;
+        let parent = entry.path.parent().filter(|parent| !parent.as_os_str().is_empty());
+        let dir = fs::canonicalize(parent.unwrap_or(Path::new("."))).ok()?;
+        let repository = self.repository(&dir)?;
+        let path = dir.join(name);
+        Some(repository.status(path.strip_prefix(&repository.root).ok()?))
+    }
+
+    /// The status of `--git` and a space, or nothing without `--git` or outside of
+    /// a repository.
+    fn git_column(&self, entry: &Entry) -> String {
+        let Some(status) = self.opts.git_opt.then(|| self.git_status(entry)).flatten() else {
+            return String::new();
+        };
+        let paint = |letter: char, staged: bool| match letter {
+            _ if self.raw => letter.to_string(),
+            '-' | 'I' => letter.dark_grey().to_string(),
+            'N' => letter.red().to_string(),
+            _ if staged => letter.green().to_string(),
+            _ => letter.red().to_string(),
+        };
+        format!("{}{} ", paint(status.staged, true), paint(status.unstaged, false))
+    }
+
+    fn print_long(&self, entries: &[Entry], io: &mut Io) {
+        let rows: Vec<[String; 5]> = entries
+            .iter()
+            .map(|entry| match &entry.metadata {
+                Some(md) => [
+                    mode_string(md),
+                    md.nlink().to_string(),
+                    user_name(md.uid()),
+                    group_name(md.gid()),
+                    if self.opts.human_opt { human_size(md.len()) } else { md.len().to_string() },
+                ],
+                None => [String::from("??????????"), String::from("?"), String::from("?"), String::from("?"), String::from("?")],
+            })
+            .collect();
+        let width = |column: usize| rows.iter().map(|row| row[column].len()).max().unwrap_or(0);
+        let (links_width, owner_width, group_width, size_width) = (width(1), width(2), width(3), width(4));
+
+        for (entry, row) in entries.iter().zip(rows.iter()) {
+            let mtime = entry
+                .metadata
+                .as_ref()
+                .and_then(|md| md.modified().ok())
+                .map_or(String::from("?"), format_mtime);
+            let mut line = OsString::from(format!(
+                "{} {:>links_width$} {:<owner_width$} {:<group_width$} {:>size_width$} {} {}",
+                row[0],
+                row[1],
+                row[2],
+                row[3],
+                row[4],
+                mtime,
+                self.git_column(entry),
+            ));
+            line.push(self.shown_entry(entry));
+            if entry.is_symlink() {
+                if let Ok(target) = fs::read_link(&entry.path) {
+                    // The target is relative to the directory of the link
+                    let target_path = entry.path.parent().map_or(target.clone(), |dir| dir.join(&target));
+                    let metadata = fs::symlink_metadata(&target_path).ok();
+                    line.push(" -> ");
+                    line.push(self.shown(target.as_os_str(), &target_path, metadata.as_ref()));
+                }
+            }
+            io.out_raw(&line);
+        }
+    }
+
+    fn print_entries(&self, entries: &[Entry], io: &mut Io) {
+        if self.opts.long_opt {
+            self.print_long(entries, io);
+        } else {
+            let names: Vec<OsString> = entries
+                .iter()
+                .map(|entry| {
+                    let mut name = OsString::from(self.git_column(entry));
+                    name.push(self.shown_entry(entry));
+                    name
+                })
+                .collect();
+            if self.raw {
+                names.iter().for_each(|name| io.out_raw(name));
+            } else {
+                // Without raw names everything was escaped, there is nothing lossy here
+                let names: Vec<String> = names.iter().map(|name| name.to_string_lossy().into_owned()).collect();
+                columnize_text(&names, io);
+            }
+        }
+    }
+
+    fn read_entries(&self, dir: &Path) -> io::Result<Vec<Entry>> {
+        let opts = &self.opts;
+        let mut entries = vec![];
+        if opts.all_opt {
+            entries.push(Entry::new(OsString::from("."), dir.join(".")));
+            entries.push(Entry::new(OsString::from(".."), dir.join("..")));
+        }
+        for entry in dir.read_dir()? {
+            let entry = entry?;
+            let name = entry.file_name();
+            if name.as_bytes().starts_with(b".") && !opts.all_opt && !opts.almost_all_opt {
+                continue;
+            }
+            entries.push(Entry::new(name, entry.path()));
+        }
+        sort_entries(&mut entries, opts);
+        Ok(entries)
+    }
+
+    /// Lists the contents of `dir`, and its subdirectories with `-R`. Returns false if
+    /// something couldn't be read.
+    fn list_dir(&self, dir: &Path, io: &mut Io, header: bool) -> bool {
+        info!("builtins::ls::Lister::list_dir(): Listing files in {}", dir.to_string_lossy());
+        let entries = match self.read_entries(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("builtins::ls::Lister::list_dir(): Cannot read the specified directory");
+                error!("builtins::ls::Lister::list_dir(): {err}");
+                io.err(format!("ls: cannot open directory '{}': {}", escape_name(dir.as_os_str()), describe(&err)));
+                return false;
+            }
+        };
+
+        if header {
+            let mut line = self.shown_path(dir);
+            line.push(":");
+            io.out_raw(&line);
+        }
+        if self.opts.long_opt {
+            let blocks: u64 = entries.iter().filter_map(|entry| entry.metadata.as_ref()).map(|md| md.blocks()).sum();
+            // st_blocks counts 512 bytes blocks, ls shows 1K blocks
+            let total = if self.opts.human_opt { human_size(blocks * 512) } else { (blocks / 2).to_string() };
+            io.out(format!("total {total}"));
+        }
+        self.print_entries(&entries, io);
+
+        let mut ok = true;
+        if self.opts.recursive_opt {
+            for entry in entries.iter().filter(|entry| entry.is_real_dir()) {
+                io.out("");
+                ok &= self.list_dir(&entry.path, io, true);
+            }
+        }
+        ok
+    }
+
+    /// Prints the entries of `dir` under `prefix`, the lines of the levels above.
+    fn print_tree(&self, dir: &Path, prefix: &str, depth: usize, count: &mut TreeCount, io: &mut Io) -> bool {
+        if self.opts.tree.flatten().is_some_and(|max_depth| depth >= max_depth) {
+            return true;
+        }
+        let mut entries = match self.read_entries(dir) {
+            Ok(entries) => entries,
+            Err(err) => {
+                error!("builtins::ls::Lister::print_tree(): Cannot read {}: {err}", dir.to_string_lossy());
+                io.err(format!("ls: cannot open directory '{}': {}", escape_name(dir.as_os_str()), describe(&err)));
+                return false;
+            }
+        };
+        entries.retain(|entry| {
+            entry.name != "." && entry.name != ".." && !self.git_status(entry).is_some_and(|status| status.is_ignored())
+        });
+
+        let mut ok = true;
+        for (index, entry) in entries.iter().enumerate() {
+            let last = index + 1 == entries.len();
+            let branch = if last { "└── " } else { "├── " };
+            let mut line = OsString::from(format!("{}{prefix}{branch}", self.git_column(entry)));
+            line.push(self.shown_entry(entry));
+            io.out_raw(&line);
+            if entry.is_real_dir() {
+                count.dirs += 1;
+                let prefix = format!("{prefix}{}", if last { "    " } else { "│   " });
+                ok &= self.print_tree(&entry.path, &prefix, depth + 1, count, io);
+            } else {
+                count.files += 1;
+            }
+        }
+        ok
+    }
+}
+
+pub struct ListCmd;
+
+impl Builtin for ListCmd {
+    fn name(&self) -> &'static str {
+        "ls"
+    }
+
+    fn command(&self) -> clap::Command {
+        LsArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Files
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        let opts: LsArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => {
+                error!("builtins::ls::ListCmd::run(): Cannot list files because the arguments are wrong");
+                return status;
+            }
+        };
+
+        let raw = !io.stdout.is_terminal();
+        let lister = Lister::new(opts, FileColors::new(&state.configs.theme), raw);
+        let opts = &lister.opts;
+
+        let mut status = ExitStatus::SUCCESS;
+        let mut files = vec![];
+        let mut dirs = vec![];
+        for path in opts.paths.iter() {
+            let name = path.as_os_str().to_os_string();
+            // The arguments follow symlinks, `ls link` lists the directory it points to
+            match fs::metadata(path) {
+                Ok(md) if md.is_dir() && !opts.directory_opt => dirs.push(Entry::new(name, path.clone())),
+                Ok(_) => files.push(Entry::new(name, path.clone())),
+                Err(_) if path.is_symlink() => files.push(Entry::new(name, path.clone())),
+                Err(err) => {
+                    io.err(format!("ls: cannot access '{}': {}", escape_name(&name), describe(&err)));
+                    status = ExitStatus::FAILURE;
+                }
+            }
+        }
+        sort_entries(&mut files, opts);
+        sort_entries(&mut dirs, opts);
+
+        if !files.is_empty() {
+            lister.print_entries(&files, io);
+        }
+        let headers = !files.is_empty() || dirs.len() > 1 || opts.recursive_opt;
+        let mut count = TreeCount::default();
+        for (index, dir) in dirs.iter().enumerate() {
+            if index > 0 || !files.is_empty() {
+                io.out("");
+            }
+            let ok = if opts.tree.is_some() {
+                let mut line = OsString::from(lister.git_column(dir));
+                line.push(lister.shown_entry(dir));
+                io.out_raw(&line);
+                lister.print_tree(&dir.path, "", 0, &mut count, io)
+            } else {
+                lister.list_dir(&dir.path, io, headers)
+            };
+            if !ok {
+                status = ExitStatus::FAILURE;
+            }
+        }
+        if opts.tree.is_some() && !dirs.is_empty() {
+            let dirs = if count.dirs == 1 { "directory" } else { "directories" };
+            let files = if count.files == 1 { "file" } else { "files" };
+            io.out(format!("\n{} {dirs}, {} {files}", count.dirs, count.files));
+        }
+        status
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::setup::ThemeConfig;
+    use crate::test_support::scratch_dir;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn human_sizes_round_up_like_gnu() {
+        let cases = [
+            (0, "0"),
+            (1023, "1023"),
+            (1024, "1.0K"),
+            (1025, "1.1K"),
+            (1536, "1.5K"),
+            (10 * 1024 - 1, "10K"),
+            (10 * 1024, "10K"),
+            (10 * 1024 + 1, "11K"),
+            (1024 * 1024 - 1, "1.0M"),
+            (1024 * 1024, "1.0M"),
+            (5 * 1024 * 1024 * 1024, "5.0G"),
+            (1 << 50, "1.0P"),
+            (u64::MAX, "16E"),
+        ];
+        for (size, expected) in cases {
+            assert_eq!(human_size(size), expected, "{size}");
+        }
+    }
+
+    #[test]
+    fn mode_strings_show_the_type_and_the_special_bits() {
+        let root = scratch_dir("ls-mode");
+        let file = root.join("file");
+        fs::write(&file, "").unwrap();
+        let fifo = root.join("fifo");
+        let fifo_path = std::ffi::CString::new(fifo.as_os_str().as_bytes()).unwrap();
+        assert_eq!(unsafe { libc::mkfifo(fifo_path.as_ptr(), 0o644) }, 0);
+        std::os::unix::fs::symlink("file", root.join("link")).unwrap();
+
+        let mode = |path: &Path, bits: Option<u32>| {
+            if let Some(bits) = bits {
+                fs::set_permissions(path, fs::Permissions::from_mode(bits)).unwrap();
+            }
+            mode_string(&fs::symlink_metadata(path).unwrap())
+        };
+        let cases = [
+            (0o644, "-rw-r--r--"),
+            (0o755, "-rwxr-xr-x"),
+            (0o000, "----------"),
+            (0o4755, "-rwsr-xr-x"),
+            (0o4644, "-rwSr--r--"),
+            (0o2755, "-rwxr-sr-x"),
+            (0o2745, "-rwxr-Sr-x"),
+        ];
+        for (bits, expected) in cases {
+            assert_eq!(mode(&file, Some(bits)), expected, "{bits:o}");
+        }
+        assert_eq!(mode(&root, Some(0o1777)), "drwxrwxrwt");
+        assert_eq!(mode(&root, Some(0o1776)), "drwxrwxrwT");
+        assert_eq!(mode(&root, Some(0o755)), "drwxr-xr-x");
+        assert_eq!(mode(&root.join("link"), None), "lrwxrwxrwx");
+        assert_eq!(&mode(&fifo, None)[..1], "p");
+        assert_eq!(&mode(Path::new("/dev/null"), None)[..1], "c");
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn nested_repositories_have_their_own_status() {
+        let outer = scratch_dir("ls-git");
+        let inner = outer.join("inner");
+        fs::create_dir_all(&inner).unwrap();
+        git2::Repository::init(&outer).unwrap();
+        git2::Repository::init(&inner).unwrap();
+        fs::write(outer.join("outer.txt"), "").unwrap();
+        fs::write(inner.join("inner.txt"), "").unwrap();
+        fs::write(outer.join(".gitignore"), "inner/\n").unwrap();
+
+        let lister = Lister::new(LsArgs::parse_from(["ls", "--git"]), FileColors::new(&ThemeConfig::default()), true);
+        let column = |path: PathBuf| lister.git_column(&Entry::new(path.file_name().unwrap().into(), path));
+        // The outer repository is read first, the inner one must not reuse it
+        assert_eq!(column(outer.join("outer.txt")), "-N ");
+        assert_eq!(column(inner.join("inner.txt")), "-N ");
+
+        let _ = fs::remove_dir_all(&outer);
+    }
+}
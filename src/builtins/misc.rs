@@ -0,0 +1,127 @@
+use clap::{CommandFactory, Parser};
+use crossterm::cursor::MoveTo;
+use crossterm::{execute, terminal};
+use log::info;
+use std::io::{self, Write};
+
+use super::{parse_args, Builtin, CompletionHint, ExitStatus, Io, ShellState};
+use crate::script_loader;
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Print the arguments separated by spaces", long_about = None)]
+struct EchoArgs {
+    #[arg(name = "STRING", trailing_var_arg = true, allow_hyphen_values = true)]
+    words: Vec<String>,
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Clear the terminal screen", long_about = None)]
+struct ClearArgs {}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Exit the shell", long_about = None)]
+struct ExitArgs {}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "1.0.0", about = "Run the lua scripts of the config again", long_about = None)]
+struct ReloadScriptsArgs {}
+
+pub struct EchoCmd;
+
+impl Builtin for EchoCmd {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
+
+    fn command(&self) -> clap::Command {
+        EchoArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Files
+    }
+
+    /// The arguments are not parsed, `echo --help` prints `--help`.
+    fn run(&self, args: Vec<String>, io: &mut Io, _state: &mut ShellState) -> ExitStatus {
+        io.out(args.iter().skip(1).cloned().collect::<Vec<String>>().join(" "));
+        ExitStatus::SUCCESS
+    }
+}
+
+fn clear_screen(io: &mut Io) -> io::Result<()> {
+    info!("builtins::misc::clear_screen(): Trying to clear terminal");
+    execute!(
+        io.stdout,
+        terminal::Clear(terminal::ClearType::All),
+        MoveTo(0, 0)
+    )?;
+    io.stdout.flush()?;
+    Ok(())
+}
+
+pub struct ClearCmd;
+
+impl Builtin for ClearCmd {
+    fn name(&self) -> &'static str {
+        "clear"
+    }
+
+    fn command(&self) -> clap::Command {
+        ClearArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, _state: &mut ShellState) -> ExitStatus {
+        if let Err(status) = parse_args::<ClearArgs>(args, io) {
+            return status;
+        }
+        if clear_screen(io).is_err() {
+            io.err("clear: Error while trying to clear the terminal");
+            return ExitStatus::FAILURE;
+        }
+        ExitStatus::SUCCESS
+    }
+}
+
+pub struct ExitCmd;
+
+impl Builtin for ExitCmd {
+    fn name(&self) -> &'static str {
+        "exit"
+    }
+
+    fn command(&self) -> clap::Command {
+        ExitArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        if let Err(status) = parse_args::<ExitArgs>(args, io) {
+            return status;
+        }
+        state.exit_requested = true;
+        ExitStatus::SUCCESS
+    }
+}
+
+pub struct ReloadScriptsCmd;
+
+impl Builtin for ReloadScriptsCmd {
+    fn name(&self) -> &'static str {
+        "reload_scripts"
+    }
+
+    fn command(&self) -> clap::Command {
+        ReloadScriptsArgs::command()
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus {
+        if let Err(status) = parse_args::<ReloadScriptsArgs>(args, io) {
+            return status;
+        }
+        if state.lua.is_none() {
+            io.err("reload_scripts: the scripts can't be reloaded from a script");
+            return ExitStatus::FAILURE;
+        }
+        state.lua = Some(script_loader::load(&state.configs, state.external.clone(), state.dir_stack.clone()));
+        ExitStatus::SUCCESS
+    }
+}
@@ -0,0 +1,468 @@
+use clap::error::ErrorKind;
+use clap::Parser;
+use log::{error, info};
+use rlua::Lua;
+use std::collections::BTreeMap;
+use std::ffi::OsStr;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, IsTerminal, PipeReader, PipeWriter, Read, Write};
+use std::os::fd::AsFd;
+use std::os::unix::ffi::OsStrExt;
+use std::process::Stdio;
+use std::sync::atomic::AtomicU32;
+use std::sync::{Arc, Mutex, OnceLock};
+
+use crate::commands::ExternalCommands;
+use crate::resolve::Resolver;
+use crate::script_loader;
+use crate::setup::YamlConfiguration;
+
+mod config;
+mod dirs;
+mod help;
+mod lookup;
+mod ls;
+mod misc;
+mod read;
+
+pub use dirs::{change_dir, init_pwd, logical_cwd, DirStack};
+
+/// Exit status of a builtin, with the same meaning as the one of a process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExitStatus(pub i32);
+
+impl ExitStatus {
+    pub const SUCCESS: ExitStatus = ExitStatus(0);
+    pub const FAILURE: ExitStatus = ExitStatus(1);
+    /// Wrong arguments, like bash does for its builtins.
+    pub const USAGE: ExitStatus = ExitStatus(2);
+}
+
+/// What the arguments of a builtin are, so the completion knows what to offer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionHint {
+    None,
+    Files,
+    Directories,
+    Commands,
+}
+
+/// Where a builtin or a command reads its input from.
+pub enum Input {
+    /// The stdin of the shell.
+    Stdin,
+    File(File),
+    Pipe(PipeReader),
+}
+
+impl Input {
+    /// True if the input comes from a terminal, and not from a file or a pipe.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Input::Stdin => io::stdin().is_terminal(),
+            Input::File(file) => file.is_terminal(),
+            Input::Pipe(_) => false,
+        }
+    }
+
+    /// A handle for a child process, the original one stays usable.
+    fn to_stdio(&self) -> io::Result<Stdio> {
+        Ok(match self {
+            Input::Stdin => Stdio::inherit(),
+            Input::File(file) => Stdio::from(file.try_clone()?),
+            Input::Pipe(pipe) => Stdio::from(pipe.try_clone()?),
+        })
+    }
+}
+
+impl Read for Input {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            Input::Stdin => io::stdin().read(buf),
+            Input::File(file) => file.read(buf),
+            Input::Pipe(pipe) => pipe.read(buf),
+        }
+    }
+}
+
+/// Where a builtin or a command writes its output or its errors.
+pub enum Output {
+    /// The stdout of the shell.
+    Stdout,
+    /// The stderr of the shell.
+    Stderr,
+    File(File),
+    Pipe(PipeWriter),
+}
+
+impl Output {
+    pub fn try_clone(&self) -> io::Result<Output> {
+        Ok(match self {
+            Output::Stdout => Output::Stdout,
+            Output::Stderr => Output::Stderr,
+            Output::File(file) => Output::File(file.try_clone()?),
+            Output::Pipe(pipe) => Output::Pipe(pipe.try_clone()?),
+        })
+    }
+
+    /// True if the output goes to a terminal, and not to a file or a pipe.
+    pub fn is_terminal(&self) -> bool {
+        match self {
+            Output::Stdout => io::stdout().is_terminal(),
+            Output::Stderr => io::stderr().is_terminal(),
+            Output::File(file) => file.is_terminal(),
+            Output::Pipe(_) => false,
+        }
+    }
+
+    /// A handle for a child process, the original one stays usable.
+    fn to_stdio(&self) -> io::Result<Stdio> {
+        Ok(match self {
+            Output::Stdout => Stdio::from(io::stdout().as_fd().try_clone_to_owned()?),
+            Output::Stderr => Stdio::from(io::stderr().as_fd().try_clone_to_owned()?),
+            Output::File(file) => Stdio::from(file.try_clone()?),
+            Output::Pipe(pipe) => Stdio::from(pipe.try_clone()?),
+        })
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout => io::stdout().write(buf),
+            Output::Stderr => io::stderr().write(buf),
+            Output::File(file) => file.write(buf),
+            Output::Pipe(pipe) => pipe.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout => io::stdout().flush(),
+            Output::Stderr => io::stderr().flush(),
+            Output::File(file) => file.flush(),
+            Output::Pipe(pipe) => pipe.flush(),
+        }
+    }
+}
+
+/// The handles a builtin reads from and writes to, so its output can go to the
+/// terminal, a file or a pipe like the output of any other command.
+pub struct Io {
+    pub stdin: Input,
+    pub stdout: Output,
+    pub stderr: Output,
+}
+
+impl Io {
+    /// Copies of the handles for a child process: stdin, stdout and stderr.
+    pub fn to_stdio(&self) -> io::Result<(Stdio, Stdio, Stdio)> {
+        Ok((self.stdin.to_stdio()?, self.stdout.to_stdio()?, self.stderr.to_stdio()?))
+    }
+
+    /// Writes a line to stdout. A failed write (like a closed pipe) is only logged,
+    /// there is nobody left to tell.
+    pub fn out(&mut self, line: impl fmt::Display) {
+        if let Err(err) = writeln!(self.stdout, "{line}") {
+            info!("builtins::Io::out(): Cannot write to stdout: {err}");
+        }
+    }
+
+    /// Writes a line to stdout as it is, it doesn't need to be UTF-8.
+    pub fn out_raw(&mut self, line: &OsStr) {
+        let written = self.stdout.write_all(line.as_bytes()).and_then(|_| self.stdout.write_all(b"\n"));
+        if let Err(err) = written {
+            info!("builtins::Io::out_raw(): Cannot write to stdout: {err}");
+        }
+    }
+
+    /// Writes a line to stderr.
+    pub fn err(&mut self, line: impl fmt::Display) {
+        if let Err(err) = writeln!(self.stderr, "{line}") {
+            info!("builtins::Io::err(): Cannot write to stderr: {err}");
+        }
+    }
+
+    /// Reads one line from stdin without the trailing newline. The line is read a
+    /// byte at a time, so nothing after it is taken away from the next reader.
+    pub fn read_line(&mut self) -> Option<String> {
+        let mut line = vec![];
+        let mut byte = [0u8; 1];
+        loop {
+            match self.stdin.read(&mut byte) {
+                Ok(0) if line.is_empty() => return None,
+                Ok(0) => break,
+                Ok(_) if byte[0] == b'\n' => break,
+                Ok(_) => line.push(byte[0]),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    error!("builtins::Io::read_line(): Cannot read the answer: {err}");
+                    return None;
+                }
+            }
+        }
+        Some(String::from_utf8_lossy(&line).trim().to_string())
+    }
+
+    /// Writes `question` to stderr and reads the answer from stdin.
+    pub fn ask(&mut self, question: &str) -> Option<String> {
+        if write!(self.stderr, "{question}").and_then(|_| self.stderr.flush()).is_err() {
+            return None;
+        }
+        self.read_line()
+    }
+
+    /// Asks a yes/no question, anything but "y" or "yes" is a no.
+    pub fn confirm(&mut self, question: &str) -> bool {
+        matches!(
+            self.ask(&format!("{question} [y/N] ")).map(|answer| answer.to_lowercase()).as_deref(),
+            Some("y") | Some("yes")
+        )
+    }
+
+    pub fn flush(&mut self) {
+        if let Err(err) = self.stdout.flush() {
+            info!("builtins::Io::flush(): Cannot flush stdout: {err}");
+        }
+    }
+}
+
+/// Everything of the shell a builtin may look at or change.
+pub struct ShellState {
+    pub configs: YamlConfiguration,
+    pub external: ExternalCommands,
+    pub dir_stack: Arc<Mutex<DirStack>>,
+    /// The lua state of the scripts. `None` when the builtin runs from a script.
+    pub lua: Option<Lua>,
+    /// Set by `exit`, the main loop stops after the command.
+    pub exit_requested: bool,
+    /// Set by `config reload`, the main loop reads the configs again before the
+    /// next prompt.
+    pub reload_requested: bool,
+    /// Pid of the command running in the foreground, 0 if there is none. The ctrl-c
+    /// handler kills it.
+    pub foreground_pid: Arc<AtomicU32>,
+    /// Set on the copies made by `subshell`. The builtins leave the working
+    /// directory and the environment of the shell alone then.
+    pub subshell: bool,
+    /// The functions of the scripts with their docs, copied in a subshell because it
+    /// can't reach `lua`. Empty anywhere else.
+    pub function_docs: BTreeMap<String, Option<String>>,
+}
+
+impl ShellState {
+    /// A copy of the state for a command that runs apart from the shell, like the
+    /// stages of a pipeline that are not the last one. The scripts are not reachable
+    /// from it, `exit` doesn't end the shell and `cd` doesn't move it.
+    pub fn subshell(&self) -> ShellState {
+        let dir_stack = self.dir_stack.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).clone();
+        ShellState {
+            configs: self.configs.clone(),
+            external: self.external.clone(),
+            dir_stack: Arc::new(Mutex::new(dir_stack)),
+            lua: None,
+            exit_requested: false,
+            reload_requested: false,
+            foreground_pid: Arc::new(AtomicU32::new(0)),
+            subshell: true,
+            function_docs: self.functions(),
+        }
+    }
+
+    /// The functions registered by the scripts, by name, with their docs.
+    pub fn functions(&self) -> BTreeMap<String, Option<String>> {
+        let Some(lua) = &self.lua else {
+            return self.function_docs.clone();
+        };
+        script_loader::function_names(lua)
+            .into_iter()
+            .map(|name| {
+                let doc = script_loader::function_doc(lua, &name);
+                (name, doc)
+            })
+            .collect()
+    }
+
+    pub fn resolver(&self) -> Resolver<'_> {
+        Resolver::new(&self.configs.terminal_config.alias, self.lua.as_ref(), &self.external)
+    }
+}
+
+/// A command run by the shell itself instead of being looked up in PATH.
+pub trait Builtin: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// The clap definition of the arguments, the help and the completion of the
+    /// flags are generated from it.
+    fn command(&self) -> clap::Command;
+
+    /// One line describing the builtin, the `about` of its clap definition.
+    fn summary(&self) -> String {
+        self.command()
+            .get_about()
+            .map(|about| about.to_string())
+            .unwrap_or_default()
+    }
+
+    /// The full help of the builtin, as shown by `help NAME` and `NAME --help`.
+    fn usage(&self) -> String {
+        self.command()
+            .name(self.name())
+            .render_help()
+            .to_string()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::None
+    }
+
+    /// Runs the builtin. `args` includes the name of the builtin, like `argv`.
+    fn run(&self, args: Vec<String>, io: &mut Io, state: &mut ShellState) -> ExitStatus;
+}
+
+/// Every builtin, by name.
+pub struct Registry {
+    builtins: BTreeMap<&'static str, Box<dyn Builtin>>,
+}
+
+impl Registry {
+    fn new() -> Self {
+        let mut registry = Registry { builtins: BTreeMap::new() };
+        registry.register(Box::new(config::ConfigCmd));
+        registry.register(Box::new(dirs::CdCmd));
+        registry.register(Box::new(dirs::DirsCmd));
+        registry.register(Box::new(dirs::PopdCmd));
+        registry.register(Box::new(dirs::PushdCmd));
+        registry.register(Box::new(dirs::PwdCmd));
+        registry.register(Box::new(dirs::ZCmd));
+        registry.register(Box::new(help::HelpCmd));
+        registry.register(Box::new(lookup::BuiltinCmd));
+        registry.register(Box::new(lookup::CommandCmd));
+        registry.register(Box::new(lookup::HashCmd));
+        registry.register(Box::new(lookup::RehashCmd));
+        registry.register(Box::new(lookup::TypeCmd));
+        registry.register(Box::new(lookup::WhichCmd));
+        registry.register(Box::new(ls::ListCmd));
+        registry.register(Box::new(misc::ClearCmd));
+        registry.register(Box::new(misc::EchoCmd));
+        registry.register(Box::new(misc::ExitCmd));
+        registry.register(Box::new(misc::ReloadScriptsCmd));
+        registry.register(Box::new(read::ReadCmd));
+        registry
+    }
+
+    fn register(&mut self, builtin: Box<dyn Builtin>) {
+        let name = builtin.name();
+        assert!(!self.builtins.contains_key(name), "Two builtins are named {name}");
+        self.builtins.insert(name, builtin);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn Builtin> {
+        self.builtins.get(name).map(|builtin| builtin.as_ref())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.builtins.contains_key(name)
+    }
+
+    /// Every builtin, sorted by name.
+    pub fn iter(&self) -> impl Iterator<Item = &dyn Builtin> {
+        self.builtins.values().map(|builtin| builtin.as_ref())
+    }
+
+    /// Names of every builtin, sorted.
+    pub fn names(&self) -> Vec<&'static str> {
+        self.builtins.keys().copied().collect()
+    }
+}
+
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::new)
+}
+
+/// Parses the arguments of a builtin. The errors and the output of `--help` are
+/// written to `io`, and the status the builtin should return is given back.
+pub fn parse_args<T: Parser>(arguments: Vec<String>, io: &mut Io) -> Result<T, ExitStatus> {
+    T::try_parse_from(arguments).map_err(|err| match err.kind() {
+        ErrorKind::DisplayHelp | ErrorKind::DisplayVersion => {
+            if let Err(err) = write!(io.stdout, "{err}") {
+                info!("builtins::parse_args(): Cannot write to stdout: {err}");
+            }
+            ExitStatus::SUCCESS
+        }
+        _ => {
+            if let Err(err) = write!(io.stderr, "{err}") {
+                info!("builtins::parse_args(): Cannot write to stderr: {err}");
+            }
+            ExitStatus::USAGE
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Parser, Debug)]
+    #[command(about = "A builtin of the tests")]
+    struct TestArgs {
+        #[arg(short = 'f')]
+        flag_opt: bool,
+    }
+
+    /// Parses `args` as `TestArgs`, with the stdout and stderr written to them.
+    fn parse(args: &[&str]) -> (Result<TestArgs, ExitStatus>, String, String) {
+        let (mut out_reader, out_writer) = io::pipe().unwrap();
+        let (mut err_reader, err_writer) = io::pipe().unwrap();
+        let mut io = Io { stdin: Input::Stdin, stdout: Output::Pipe(out_writer), stderr: Output::Pipe(err_writer) };
+        let parsed = parse_args(args.iter().map(|arg| arg.to_string()).collect(), &mut io);
+        drop(io);
+        let (mut out, mut err) = (String::new(), String::new());
+        out_reader.read_to_string(&mut out).unwrap();
+        err_reader.read_to_string(&mut err).unwrap();
+        (parsed, out, err)
+    }
+
+    #[test]
+    fn every_builtin_is_registered_under_its_name() {
+        let registry = Registry::new();
+        for (name, builtin) in &registry.builtins {
+            assert_eq!(*name, builtin.name());
+            assert!(registry.contains(name));
+            assert_eq!(registry.get(name).map(|builtin| builtin.name()), Some(*name));
+            // clap checks the definition of the arguments
+            builtin.command().name(builtin.name()).debug_assert();
+            assert!(!builtin.summary().is_empty(), "{name} has no summary");
+        }
+        for name in ["cd", "pwd", "pushd", "popd", "dirs", "type", "which", "command", "builtin", "hash", "help", "ls", "read", "config"] {
+            assert!(registry.contains(name), "{name} isn't registered");
+        }
+        assert!(registry.get("nonexistent").is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "Two builtins are named pwd")]
+    fn refuses_two_builtins_with_one_name() {
+        let mut registry = Registry::new();
+        registry.register(Box::new(dirs::PwdCmd));
+    }
+
+    #[test]
+    fn parse_args_gives_the_status_of_the_builtin() {
+        let (parsed, out, err) = parse(&["test", "-f"]);
+        assert!(parsed.is_ok_and(|args| args.flag_opt));
+        assert!(out.is_empty() && err.is_empty());
+
+        let (parsed, out, err) = parse(&["test", "--help"]);
+        assert_eq!(parsed.unwrap_err(), ExitStatus::SUCCESS);
+        assert!(out.contains("A builtin of the tests"), "{out}");
+        assert!(err.is_empty(), "{err}");
+
+        let (parsed, out, err) = parse(&["test", "--wrong"]);
+        assert_eq!(parsed.unwrap_err(), ExitStatus::USAGE);
+        assert!(out.is_empty(), "{out}");
+        assert!(err.contains("--wrong"), "{err}");
+    }
+}
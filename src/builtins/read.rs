@@ -0,0 +1,461 @@
+use clap::{CommandFactory, Parser};
+use crossterm::style::Stylize;
+use crossterm::terminal;
+use log::{error, info};
+use std::fs::{self, Metadata};
+use std::io::{self, Read};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::ls::{group_name, human_size, mode_string, user_name};
+use super::{parse_args, Builtin, CompletionHint, ExitStatus, Io, Output, ShellState};
+use crate::commands::format_system_time;
+use crate::pipeline::describe;
+use crate::elf;
+use crate::highlight::{self, Highlighter};
+use crate::pager::{self, Line};
+
+/// The lines to show, counted from 1. Without an end it goes to the end of the file.
+#[derive(Clone, Copy, Debug)]
+struct LineRange {
+    start: usize,
+    end: Option<usize>,
+}
+
+/// Parses `START:END`, `START:`, `:END` or a single line.
+fn parse_range(value: &str) -> Result<LineRange, String> {
+    let parse = |number: &str| match number.parse::<usize>() {
+        Ok(0) => Err(String::from("the lines are counted from 1")),
+        Ok(number) => Ok(number),
+        Err(_) => Err(format!("`{number}` is not a line number")),
+    };
+    let range = match value.split_once(':') {
+        Some((start, end)) => LineRange {
+            start: if start.is_empty() { 1 } else { parse(start)? },
+            end: if end.is_empty() { None } else { Some(parse(end)?) },
+        },
+        None => {
+            let line = parse(value)?;
+            LineRange { start: line, end: Some(line) }
+        }
+    };
+    match range.end {
+        Some(end) if end < range.start => Err(format!("the range ends before line {}", range.start)),
+        _ => Ok(range),
+    }
+}
+
+/// How much of the beginning of a file is read to tell binary files from text.
+const CHECKED_BYTES: usize = 8192;
+
+/// The most bytes of a hex dump shown in the pager, which keeps every line in memory.
+/// The rest can be seen with `--range` or through a pipe.
+const PAGED_HEX_BYTES: u64 = 1024 * 1024;
+
+/// Whether `content` looks like a binary file and not text: it has NUL bytes or
+/// isn't UTF-8. Only the beginning is checked.
+fn is_binary(content: &[u8]) -> bool {
+    let start = &content[..content.len().min(CHECKED_BYTES)];
+    // A character can be cut at the end of the part that is checked
+    start.contains(&0) || std::str::from_utf8(start).is_err_and(|err| err.error_len().is_some())
+}
+
+/// Replaces the tabs with spaces up to the next multiple of 8 columns, the pager cuts
+/// the lines by their width.
+fn expand_tabs(line: &str) -> String {
+    let mut expanded = String::with_capacity(line.len());
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = 8 - column % 8;
+            expanded.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            expanded.push(c);
+            column += 1;
+        }
+    }
+    expanded
+}
+
+#[derive(Parser, Debug)]
+#[command(author = "XtremeTHN", version = "0.8.0", about = "Read files with this command", long_about = None)]
+struct ReadArgs {
+    #[arg(
+        short = 'f',
+        long = "force",
+        help = "Read binary files as text instead of showing a hex dump"
+    )]
+    force_opt: bool,
+
+    #[arg(short = 'x', long = "hex", help = "Show a hex dump of the file, even if it is text")]
+    hex_opt: bool,
+
+    #[arg(
+        short = 'm',
+        long = "metadata",
+        conflicts_with_all = ["force_opt", "hex_opt"],
+        help = "Show the metadata of the file instead of its content"
+    )]
+    metadata_opt: bool,
+
+    #[arg(short = 'n', long = "number", help = "Number the lines")]
+    number_opt: bool,
+
+    #[arg(
+        long = "range",
+        value_name = "START:END",
+        value_parser = parse_range,
+        help = "Only show these lines, like 10:40, 10: or :40. The lines of a hex dump have 16 bytes"
+    )]
+    range: Option<LineRange>,
+
+    file: PathBuf,
+}
+
+pub struct ReadCmd;
+
+impl Builtin for ReadCmd {
+    fn name(&self) -> &'static str {
+        "read"
+    }
+
+    fn command(&self) -> clap::Command {
+        ReadArgs::command()
+    }
+
+    fn completion(&self) -> CompletionHint {
+        CompletionHint::Files
+    }
+
+    fn run(&self, args: Vec<String>, io: &mut Io, _state: &mut ShellState) -> ExitStatus {
+        let opts: ReadArgs = match parse_args(args, io) {
+            Ok(opts) => opts,
+            Err(status) => return status,
+        };
+        // Styled only on a terminal, so the errors can be redirected to a file
+        let name = if io.stderr.is_terminal() { "read".green().to_string() } else { String::from("read") };
+        if opts.metadata_opt {
+            info!("builtins::read::ReadCmd::run(): Reading metadata...");
+            return match show_metadata(&opts.file, io) {
+                Ok(()) => ExitStatus::SUCCESS,
+                Err(err) => {
+                    error!("builtins::read::ReadCmd::run(): Error while trying to get file metadata");
+                    error!("builtins::read::ReadCmd::run(): {}", err);
+                    io.err(format!("{name}: Couldnt read {} metadata: {err}", opts.file.to_string_lossy()));
+                    ExitStatus::FAILURE
+                }
+            };
+        }
+
+        info!("builtins::read::ReadCmd::run(): Reading file...");
+        match show_file(&opts, io) {
+            Ok(()) => ExitStatus::SUCCESS,
+            Err(err) => {
+                error!("builtins::read::ReadCmd::run(): Error while trying to read the file");
+                error!("builtins::read::ReadCmd::run(): {}", err);
+                io.err(format!("{name}: {}: {}", opts.file.to_string_lossy(), describe(&err)));
+                ExitStatus::FAILURE
+            }
+        }
+    }
+}
+
+/// Shows the file as text or as a hex dump. Only its beginning is read to choose, a
+/// hex dump doesn't need the whole file in memory.
+fn show_file(opts: &ReadArgs, io: &mut Io) -> io::Result<()> {
+    let mut file = fs::File::open(&opts.file)?;
+    let mut content = vec![];
+    file.by_ref().take(CHECKED_BYTES as u64).read_to_end(&mut content)?;
+    if opts.hex_opt || (!opts.force_opt && is_binary(&content)) {
+        info!("builtins::read::ReadCmd::run(): Showing a hex dump...");
+        return show_hexdump(opts, io::Cursor::new(content).chain(file), io);
+    }
+    file.read_to_end(&mut content)?;
+    show_text(opts, &String::from_utf8_lossy(&content), io);
+    Ok(())
+}
+
+/// True if the output is the terminal of the shell, where `show_lines` can page.
+fn can_page(io: &Io) -> bool {
+    matches!(io.stdout, Output::Stdout) && io.stdout.is_terminal()
+}
+
+/// Prints `lines`, in the pager if they don't fit in the terminal.
+fn show_lines(title: &str, lines: Vec<Line>, io: &mut Io) {
+    let color = io.stdout.is_terminal();
+    let rows = terminal::size().map_or(0, |(_, rows)| rows as usize);
+    if can_page(io) && lines.len() >= rows.saturating_sub(1) {
+        let lines: Vec<Line> = lines
+            .into_iter()
+            .map(|line| Line { plain: expand_tabs(&line.plain), shown: expand_tabs(&line.shown) })
+            .collect();
+        if let Err(err) = pager::page(title, &lines) {
+            error!("builtins::read::show_lines(): Cannot show the pager: {err}");
+            lines.iter().for_each(|line| io.out(&line.shown));
+        }
+        return;
+    }
+    for line in lines {
+        io.out(if color { line.shown } else { line.plain });
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap_or(path.as_os_str()).to_string_lossy().into_owned()
+}
+
+/// Prints the lines of `text` in the range, colored by the syntax of the file when
+/// they go to the terminal.
+fn show_text(opts: &ReadArgs, text: &str, io: &mut Io) {
+    let color = io.stdout.is_terminal();
+    let syntax = color.then(|| highlight::syntax_for(&opts.file)).flatten();
+    let mut highlighter = syntax.map(Highlighter::new);
+    let range = opts.range.unwrap_or(LineRange { start: 1, end: None });
+
+    let all_lines: Vec<&str> = text.lines().collect();
+    let end = range.end.unwrap_or(all_lines.len()).min(all_lines.len());
+    let number_width = end.to_string().len();
+    let mut lines = vec![];
+    for (index, line) in all_lines.iter().take(end).enumerate() {
+        // The lines before the range are highlighted too, a block comment can start there
+        let shown = match highlighter.as_mut() {
+            Some(highlighter) => highlighter.line(line),
+            None => line.to_string(),
+        };
+        if index + 1 < range.start {
+            continue;
+        }
+        let (plain, shown) = if opts.number_opt {
+            let number = format!("{:>number_width$} ", index + 1);
+            let shown = if color { format!("{}{shown}", number.clone().dim()) } else { format!("{number}{shown}") };
+            (format!("{number}{line}"), shown)
+        } else {
+            (line.to_string(), shown)
+        };
+        lines.push(Line { plain, shown });
+    }
+
+    let title = match syntax {
+        Some(syntax) => format!("{} ({})", file_name(&opts.file), syntax.name),
+        None => file_name(&opts.file),
+    };
+    show_lines(&title, lines, io);
+}
+
+/// Prints the hex dump of the lines of `content` in the range. On the terminal of
+/// the shell it goes through `show_lines`, so it is paged when it doesn't fit, and
+/// stops after `PAGED_HEX_BYTES`. Anywhere else it is written a block at a time.
+fn show_hexdump(opts: &ReadArgs, mut content: impl Read, io: &mut Io) -> io::Result<()> {
+    let range = opts.range.unwrap_or(LineRange { start: 1, end: None });
+    let mut offset = (range.start as u64 - 1) * 16;
+    io::copy(&mut content.by_ref().take(offset), &mut io::sink())?;
+    let mut left = range.end.map_or(u64::MAX, |end| (end + 1 - range.start) as u64 * 16);
+
+    if can_page(io) {
+        let mut block = vec![];
+        content.by_ref().take(left.min(PAGED_HEX_BYTES)).read_to_end(&mut block)?;
+        let mut lines = hexdump_lines(&block, offset);
+        let mut more = [0u8; 1];
+        if left > PAGED_HEX_BYTES && content.read(&mut more)? > 0 {
+            let note = format!("The dump stops after {}, use --range or a pipe to see the rest", human_size(PAGED_HEX_BYTES));
+            lines.push(Line { plain: note.clone(), shown: note.dim().to_string() });
+        }
+        show_lines(&format!("{} (hex)", file_name(&opts.file)), lines, io);
+        return Ok(());
+    }
+
+    let color = io.stdout.is_terminal();
+    let mut block = vec![];
+    while left > 0 {
+        block.clear();
+        content.by_ref().take(left.min(64 * 1024)).read_to_end(&mut block)?;
+        if block.is_empty() {
+            break;
+        }
+        for line in hexdump_lines(&block, offset) {
+            io.out(if color { line.shown } else { line.plain });
+        }
+        offset += block.len() as u64;
+        left -= block.len() as u64;
+    }
+    Ok(())
+}
+
+/// The lines of a hex dump like the one of `hexdump -C`: the offset (counted from
+/// `start`), 16 bytes in hex in two groups of 8, and the same bytes as ASCII between
+/// bars, with a dot for the ones that can't be printed. NUL bytes are dark grey and
+/// the bytes above ASCII yellow.
+fn hexdump_lines(content: &[u8], start: u64) -> Vec<Line> {
+    let paint = |byte: u8, text: String| match byte {
+        0 => text.dark_grey().to_string(),
+        0x80.. => text.yellow().to_string(),
+        _ => text,
+    };
+    let mut lines = vec![];
+    for (index, chunk) in content.chunks(16).enumerate() {
+        let offset = format!("{:08x}", start + index as u64 * 16);
+        let (mut plain, mut shown) = (format!("{offset}  "), format!("{}  ", offset.clone().dim()));
+        for column in 0..16 {
+            let hex = chunk.get(column).map_or(String::from("  "), |byte| format!("{byte:02x}"));
+            let separator = if column == 7 { "  " } else { " " };
+            shown.push_str(&chunk.get(column).map_or(hex.clone(), |byte| paint(*byte, hex.clone())));
+            shown.push_str(separator);
+            plain.push_str(&hex);
+            plain.push_str(separator);
+        }
+        let ascii = |byte: u8| if byte.is_ascii_graphic() || byte == b' ' { char::from(byte) } else { '.' };
+        plain.push_str(&format!(" |{}|", chunk.iter().map(|byte| ascii(*byte)).collect::<String>()));
+        shown.push_str(" |");
+        chunk.iter().for_each(|byte| shown.push_str(&paint(*byte, ascii(*byte).to_string())));
+        shown.push('|');
+        lines.push(Line { plain, shown });
+    }
+    lines
+}
+
+fn file_type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_char_device() {
+        "character device"
+    } else if file_type.is_block_device() {
+        "block device"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else {
+        "regular file"
+    }
+}
+
+/// The beginning of the file, enough for the headers of an ELF file and its
+/// interpreter.
+fn read_head(path: &Path) -> io::Result<Vec<u8>> {
+    let mut head = vec![];
+    fs::File::open(path)?.take(64 * 1024).read_to_end(&mut head)?;
+    Ok(head)
+}
+
+/// Prints the size, mode, owner, inode, links and times of the file, and what it
+/// runs on if it is an ELF file.
+fn show_metadata(path: &Path, io: &mut Io) -> io::Result<()> {
+    let metadata = fs::metadata(path)?;
+    let changed = UNIX_EPOCH + Duration::new(metadata.ctime().max(0) as u64, metadata.ctime_nsec() as u32);
+    let time = |time: io::Result<SystemTime>| time.map_or(String::from("unknown"), format_system_time);
+
+    let mut fields = vec![
+        ("File", path.to_string_lossy().into_owned()),
+        ("Type", file_type_name(&metadata).to_string()),
+        ("Size", match metadata.size() {
+            size @ ..1024 => format!("{size} bytes"),
+            size => format!("{size} bytes ({})", human_size(size)),
+        }),
+        ("Mode", format!("{:04o} ({})", metadata.mode() & 0o7777, mode_string(&metadata))),
+        ("Owner", format!("{} ({})", user_name(metadata.uid()), metadata.uid())),
+        ("Group", format!("{} ({})", group_name(metadata.gid()), metadata.gid())),
+        ("Inode", metadata.ino().to_string()),
+        ("Links", metadata.nlink().to_string()),
+        ("Accessed", time(metadata.accessed())),
+        ("Modified", time(metadata.modified())),
+        ("Changed", format_system_time(changed)),
+    ];
+    if metadata.is_file() {
+        match read_head(path) {
+            Ok(head) => match elf::parse(&head) {
+                Some(info) => {
+                    let endian = if info.little_endian { "little endian" } else { "big endian" };
+                    fields.push(("Format", format!("ELF {}-bit {endian} {}", info.bits, info.kind)));
+                    fields.push(("Architecture", info.architecture));
+                    fields.push(("Interpreter", info.interpreter.unwrap_or(String::from("none (statically linked)"))));
+                }
+                None => fields.push(("Content", String::from(if is_binary(&head) { "binary" } else { "text" }))),
+            },
+            Err(err) => error!("builtins::read::show_metadata(): Cannot read {}: {err}", path.to_string_lossy()),
+        }
+    }
+
+    let color = io.stdout.is_terminal();
+    let width = fields.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    for (name, value) in fields {
+        let name = format!("{name:>width$}");
+        let name = if color { name.blue().bold().to_string() } else { name };
+        io.out(format!("{name}: {value}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dumps_16_bytes_a_line() {
+        let lines = hexdump_lines(b"Hello, world!\n\0\x7f\xffmore", 0);
+        let plain: Vec<_> = lines.iter().map(|line| line.plain.as_str()).collect();
+        assert_eq!(
+            plain,
+            [
+                "00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 7f  |Hello, world!...|",
+                "00000010  ff 6d 6f 72 65                                    |.more|",
+            ]
+        );
+        assert!(lines[0].shown.contains(&"00".dark_grey().to_string()));
+        assert!(lines[1].shown.contains(&"ff".yellow().to_string()));
+        assert!(hexdump_lines(b"", 0).is_empty());
+        assert!(hexdump_lines(b"x", 0x20)[0].plain.starts_with("00000020  78 "));
+    }
+
+    #[test]
+    fn parses_the_line_ranges() {
+        let cases = [
+            ("10:40", 10, Some(40)),
+            ("10:", 10, None),
+            (":40", 1, Some(40)),
+            (":", 1, None),
+            ("7", 7, Some(7)),
+            ("3:3", 3, Some(3)),
+        ];
+        for (value, start, end) in cases {
+            let range = parse_range(value).unwrap();
+            assert_eq!((range.start, range.end), (start, end), "{value}");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_line_ranges() {
+        let cases = [
+            ("0", "the lines are counted from 1"),
+            (":0", "the lines are counted from 1"),
+            ("x:4", "`x` is not a line number"),
+            ("4:-1", "`-1` is not a line number"),
+            ("", "`` is not a line number"),
+            ("40:10", "the range ends before line 40"),
+        ];
+        for (value, message) in cases {
+            assert_eq!(parse_range(value).unwrap_err(), message, "{value}");
+        }
+    }
+
+    #[test]
+    fn tells_binary_files_from_text() {
+        assert!(!is_binary(b""));
+        assert!(!is_binary(b"fn main() {}\n"));
+        assert!(!is_binary("caf\u{e9} \u{1f980}\n".as_bytes()));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\0"));
+        assert!(is_binary(b"latin-1 caf\xe9 \n"));
+    }
+
+    #[test]
+    fn a_character_cut_at_the_end_of_the_check_is_still_text() {
+        let mut content = "a".repeat(8191).into_bytes();
+        content.extend_from_slice("\u{e9}".as_bytes());
+        assert!(!is_binary(&content));
+        // A NUL after the checked part isn't seen
+        content.push(0);
+        assert!(!is_binary(&content));
+    }
+}
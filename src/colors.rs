@@ -0,0 +1,306 @@
+use log::warn;
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, Metadata};
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+
+use crate::setup::ThemeConfig;
+
+/// The colors of `dircolors --print-database`, used when neither `LS_COLORS` nor
+/// the theme of the config file say anything else.
+const DEFAULT_COLORS: &str = "di=01;34:ln=01;36:pi=40;33:so=01;35:do=01;35:bd=40;33;01:cd=40;33;01:\
+or=40;31;01:su=37;41:sg=30;43:tw=30;42:ow=34;42:st=37;44:ex=01;32:\
+*.tar=01;31:*.tgz=01;31:*.zip=01;31:*.gz=01;31:*.bz2=01;31:*.xz=01;31:*.zst=01;31:\
+*.7z=01;31:*.rar=01;31:*.deb=01;31:*.rpm=01;31:*.jar=01;31:\
+*.jpg=01;35:*.jpeg=01;35:*.png=01;35:*.gif=01;35:*.bmp=01;35:*.svg=01;35:*.webp=01;35:\
+*.tif=01;35:*.tiff=01;35:*.mp4=01;35:*.mkv=01;35:*.webm=01;35:*.avi=01;35:*.mov=01;35:\
+*.mp3=00;36:*.flac=00;36:*.ogg=00;36:*.wav=00;36:*.m4a=00;36:*.opus=00;36";
+
+/// The names the theme of the config file can use instead of the `dircolors` codes.
+const KIND_NAMES: [(&str, &str); 16] = [
+    ("normal", "no"),
+    ("file", "fi"),
+    ("directory", "di"),
+    ("symlink", "ln"),
+    ("orphan", "or"),
+    ("missing", "mi"),
+    ("fifo", "pi"),
+    ("socket", "so"),
+    ("door", "do"),
+    ("block_device", "bd"),
+    ("char_device", "cd"),
+    ("executable", "ex"),
+    ("setuid", "su"),
+    ("setgid", "sg"),
+    ("sticky", "st"),
+    ("other_writable", "ow"),
+];
+
+/// Turns a color of the theme into SGR codes: either the codes themselves
+/// (`01;34`) or words like `bold blue`, `bright_red` or `on_yellow`.
+pub fn parse_style(style: &str) -> Option<String> {
+    if style.chars().all(|ch| ch.is_ascii_digit() || ch == ';') {
+        return Some(style.to_string());
+    }
+    const COLORS: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let mut codes = vec![];
+    for word in style.split_whitespace() {
+        let word = word.to_lowercase();
+        let code = match word.as_str() {
+            "bold" => 1,
+            "dim" => 2,
+            "italic" => 3,
+            "underline" => 4,
+            "blink" => 5,
+            "reverse" => 7,
+            _ => {
+                let (base, name) = if let Some(name) = word.strip_prefix("on_bright_") {
+                    (100, name)
+                } else if let Some(name) = word.strip_prefix("on_") {
+                    (40, name)
+                } else if let Some(name) = word.strip_prefix("bright_") {
+                    (90, name)
+                } else {
+                    (30, word.as_str())
+                };
+                base + COLORS.iter().position(|color| *color == name)?
+            }
+        };
+        codes.push(code.to_string());
+    }
+    if codes.is_empty() {
+        None
+    } else {
+        Some(codes.join(";"))
+    }
+}
+
+/// The colors of the file names, following the rules of `LS_COLORS`.
+#[derive(Clone, Debug, Default)]
+pub struct FileColors {
+    /// `di`, `ln`, `ex`... and their SGR codes.
+    kinds: HashMap<String, String>,
+    /// The endings of `*.tar` like rules, lowercase.
+    suffixes: Vec<(String, String)>,
+}
+
+impl FileColors {
+    /// The default colors, then `LS_COLORS` and then the theme of the config file.
+    pub fn new(theme: &ThemeConfig) -> Self {
+        let mut colors = FileColors::default();
+        colors.apply(DEFAULT_COLORS);
+        if theme.use_ls_colors {
+            if let Ok(ls_colors) = env::var("LS_COLORS") {
+                colors.apply(&ls_colors);
+            }
+        }
+        for (key, style) in theme.colors.iter() {
+            let Some(codes) = parse_style(style) else {
+                warn!("colors::FileColors::new(): Ignoring the color `{style}' of `{key}', it isn't valid");
+                continue;
+            };
+            let key = KIND_NAMES
+                .iter()
+                .find(|(name, _)| name == key)
+                .map_or(key.as_str(), |(_, code)| code);
+            colors.set(key, codes);
+        }
+        colors
+    }
+
+    /// Adds the rules of a `LS_COLORS` string, `di=01;34:*.tar=01;31:...`.
+    pub fn apply(&mut self, rules: &str) {
+        for rule in rules.split(':').filter(|rule| !rule.is_empty()) {
+            match rule.split_once('=') {
+                Some((key, codes)) => self.set(key, codes.to_string()),
+                None => warn!("colors::FileColors::apply(): Ignoring the rule `{rule}', it has no `='"),
+            }
+        }
+    }
+
+    fn set(&mut self, key: &str, codes: String) {
+        if let Some(suffix) = key.strip_prefix('*') {
+            let suffix = suffix.to_lowercase();
+            self.suffixes.retain(|(existing, _)| *existing != suffix);
+            self.suffixes.push((suffix, codes));
+        } else {
+            self.kinds.insert(key.to_string(), codes);
+        }
+    }
+
+    fn kind(&self, key: &str) -> Option<&str> {
+        self.kinds
+            .get(key)
+            .map(String::as_str)
+            .filter(|codes| !codes.is_empty() && *codes != "0" && *codes != "00")
+    }
+
+    fn suffix(&self, name: &str) -> Option<&str> {
+        let name = name.to_lowercase();
+        self.suffixes
+            .iter()
+            .filter(|(suffix, _)| name.ends_with(suffix.as_str()))
+            .max_by_key(|(suffix, _)| suffix.len())
+            .map(|(_, codes)| codes.as_str())
+    }
+
+    /// The SGR codes of the file at `path`. `metadata` is the one of the file itself,
+    /// not of the target of a symlink.
+    pub fn style(&self, name: &str, path: &Path, metadata: Option<&Metadata>) -> Option<&str> {
+        let Some(metadata) = metadata else {
+            return self.kind("mi").or_else(|| self.kind("or"));
+        };
+        let file_type = metadata.file_type();
+        if file_type.is_symlink() {
+            return match fs::metadata(path) {
+                Err(_) => self.kind("or").or_else(|| self.kind("ln")),
+                Ok(target) if self.kinds.get("ln").is_some_and(|codes| codes == "target") => {
+                    self.style(name, path, Some(&target))
+                }
+                Ok(_) => self.kind("ln"),
+            };
+        }
+
+        let mode = metadata.permissions().mode();
+        if file_type.is_dir() {
+            let sticky = mode & 0o1000 != 0;
+            let other_writable = mode & 0o002 != 0;
+            let key = match (sticky, other_writable) {
+                (true, true) => "tw",
+                (false, true) => "ow",
+                (true, false) => "st",
+                (false, false) => "di",
+            };
+            return self.kind(key).or_else(|| self.kind("di"));
+        }
+        if file_type.is_fifo() {
+            return self.kind("pi");
+        }
+        if file_type.is_socket() {
+            return self.kind("so");
+        }
+        if file_type.is_block_device() {
+            return self.kind("bd");
+        }
+        if file_type.is_char_device() {
+            return self.kind("cd");
+        }
+        if mode & 0o4000 != 0 && self.kind("su").is_some() {
+            return self.kind("su");
+        }
+        if mode & 0o2000 != 0 && self.kind("sg").is_some() {
+            return self.kind("sg");
+        }
+        if mode & 0o111 != 0 && self.kind("ex").is_some() {
+            return self.kind("ex");
+        }
+        self.suffix(name).or_else(|| self.kind("fi"))
+    }
+
+    /// `name` between the escape sequences of its color, or as it is if it has none.
+    pub fn paint(&self, name: &str, path: &Path, metadata: Option<&Metadata>) -> String {
+        match self.style(name, path, metadata) {
+            Some(codes) => format!("\x1b[{codes}m{name}\x1b[0m"),
+            None => name.to_string(),
+        }
+    }
+
+    /// Like `paint()`, reading the metadata of `path`.
+    pub fn paint_path(&self, name: &str, path: &Path) -> String {
+        self.paint(name, path, fs::symlink_metadata(path).ok().as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    /// The style of a regular file named `name`, with `rules` on top of the defaults.
+    fn file_style(rules: &str, name: &str) -> Option<String> {
+        let root = scratch_dir("colors");
+        let path = root.join("file");
+        fs::write(&path, "").unwrap();
+        let mut colors = FileColors::default();
+        colors.apply(DEFAULT_COLORS);
+        colors.apply(rules);
+        let style = colors.style(name, &path, fs::symlink_metadata(&path).ok().as_ref()).map(String::from);
+        let _ = fs::remove_dir_all(&root);
+        style
+    }
+
+    #[test]
+    fn parses_the_styles_of_the_theme() {
+        let cases = [
+            ("01;34", Some("01;34")),
+            ("", Some("")),
+            ("bold blue", Some("1;34")),
+            ("Bold  BLUE", Some("1;34")),
+            ("red", Some("31")),
+            ("bright_red", Some("91")),
+            ("on_yellow", Some("43")),
+            ("on_bright_white", Some("107")),
+            ("dim italic underline blink reverse black", Some("2;3;4;5;7;30")),
+            ("   ", None),
+            ("orange", None),
+            ("bold orange", None),
+            ("on_", None),
+            ("01;3x", None),
+        ];
+        for (style, expected) in cases {
+            assert_eq!(parse_style(style).as_deref(), expected, "{style:?}");
+        }
+    }
+
+    #[test]
+    fn reads_the_rules_of_ls_colors() {
+        let mut colors = FileColors::default();
+        colors.apply("di=01;34:ln=target::broken:*.TAR=01;31:*.gz=31:*.tar.gz=35:*.gz=32");
+        assert_eq!(colors.kind("di"), Some("01;34"));
+        assert_eq!(colors.kinds.get("ln").map(String::as_str), Some("target"));
+        assert!(!colors.kinds.contains_key("broken"));
+        // The patterns are lowercase, a later one replaces an earlier one
+        assert_eq!(colors.suffix("backup.tar"), Some("01;31"));
+        assert_eq!(colors.suffix("notes.GZ"), Some("32"));
+        assert_eq!(colors.suffixes.iter().filter(|(suffix, _)| suffix == ".gz").count(), 1);
+        // The longest pattern wins
+        assert_eq!(colors.suffix("src.tar.gz"), Some("35"));
+        assert_eq!(colors.suffix("README"), None);
+    }
+
+    #[test]
+    fn zero_turns_a_kind_off() {
+        let mut colors = FileColors::default();
+        colors.apply("di=0:ex=00:fi=");
+        assert_eq!(colors.kind("di"), None);
+        assert_eq!(colors.kind("ex"), None);
+        assert_eq!(colors.kind("fi"), None);
+    }
+
+    #[test]
+    fn styles_the_files_by_their_suffix() {
+        assert_eq!(file_style("", "photo.PNG").as_deref(), Some("01;35"));
+        assert_eq!(file_style("fi=33", "notes.txt").as_deref(), Some("33"));
+        assert_eq!(file_style("", "notes.txt"), None);
+    }
+
+    #[test]
+    fn the_theme_uses_names_or_codes() {
+        let theme = ThemeConfig {
+            use_ls_colors: false,
+            colors: HashMap::from([
+                (String::from("directory"), String::from("bold green")),
+                (String::from("ex"), String::from("01;33")),
+                (String::from("*.rs"), String::from("bright_red")),
+                (String::from("symlink"), String::from("not a color")),
+            ]),
+        };
+        let colors = FileColors::new(&theme);
+        assert_eq!(colors.kind("di"), Some("1;32"));
+        assert_eq!(colors.kind("ex"), Some("01;33"));
+        assert_eq!(colors.suffix("main.rs"), Some("91"));
+        // An invalid color keeps the default one
+        assert_eq!(colors.kind("ln"), Some("01;36"));
+    }
+}
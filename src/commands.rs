@@ -1,546 +1,499 @@
-use clap::Parser;
-use crossterm::cursor::MoveTo;
-use crossterm::style::Stylize;
-use crossterm::{execute, terminal};
 use is_executable::IsExecutable;
 use log::{error, info};
-use std::io::{self, Read, Write};
-use std::path::{Path, self};
+use std::ffi::OsStr;
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
 use std::process::Child;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
 use std::time::SystemTime;
-use std::{
-    env, fs,
-    path::PathBuf,
-    process::{Command, Stdio},
-};
+use std::{env, fs, path::PathBuf, process::Command};
 use term_size::dimensions;
+use unicode_width::UnicodeWidthChar;
+
+use crate::builtins::Io;
+use crate::path_index::{CommandIndex, Scan, Stamp};
+
+/// Set by the SIGWINCH handler, the width in `TERMINAL_WIDTH` must be read again.
+static WIDTH_CHANGED: AtomicBool = AtomicBool::new(true);
+static TERMINAL_WIDTH: AtomicUsize = AtomicUsize::new(0);
+/// The handler that was installed before ours, rustyline's one.
+static PREVIOUS_SIGWINCH_HANDLER: AtomicUsize = AtomicUsize::new(libc::SIG_DFL);
+/// The previous handler was installed with `SA_SIGINFO` and takes three arguments.
+static PREVIOUS_TAKES_SIGINFO: AtomicBool = AtomicBool::new(false);
+
+type SigInfoHandler = extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void);
+
+extern "C" fn sigwinch_handler(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    WIDTH_CHANGED.store(true, Ordering::SeqCst);
+    let previous = PREVIOUS_SIGWINCH_HANDLER.load(Ordering::SeqCst);
+    if previous == libc::SIG_DFL || previous == libc::SIG_IGN {
+        return;
+    }
+    if PREVIOUS_TAKES_SIGINFO.load(Ordering::SeqCst) {
+        let previous: SigInfoHandler = unsafe { std::mem::transmute(previous) };
+        previous(signal, info, context);
+    } else {
+        let previous: extern "C" fn(libc::c_int) = unsafe { std::mem::transmute(previous) };
+        previous(signal);
+    }
+}
 
-use crate::setup::{self, write_conf};
+/// Keeps track of the size of the terminal. It must be called after the line editor
+/// installed its own SIGWINCH handler, which is still called.
+pub fn watch_terminal_size() {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = sigwinch_handler as SigInfoHandler as libc::sighandler_t;
+        // Ours takes the siginfo too, so it can be given to a previous handler that wants it
+        action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        if libc::sigaction(libc::SIGWINCH, &action, &mut previous) != 0 {
+            error!("commands::watch_terminal_size(): Cannot install the SIGWINCH handler: {}", io::Error::last_os_error());
+            return;
+        }
+        PREVIOUS_TAKES_SIGINFO.store(previous.sa_flags & libc::SA_SIGINFO != 0, Ordering::SeqCst);
+        PREVIOUS_SIGWINCH_HANDLER.store(previous.sa_sigaction, Ordering::SeqCst);
+    }
+}
 
-pub fn columnize_text(items: &Vec<String>) {
-    info!("commands::columnize_text(): Columnizing text...");
-    if let Some((width, _)) = dimensions() {
-        let longest_item_len = items.iter().map(|item| item.len()).max().unwrap_or(0);
-        let num_columns = width / (longest_item_len + 2).max(1);
-        let num_rows = (items.len() as f64 / num_columns as f64).ceil() as usize;
-
-        for row in 0..num_rows {
-            for column in 0..num_columns {
-                let index = row + column * num_rows;
-                if let Some(item) = items.get(index) {
-                    print!("{:<width$}", item, width = longest_item_len + 2);
+/// The number of columns of the terminal, read again after every resize.
+fn terminal_width() -> Option<usize> {
+    if WIDTH_CHANGED.swap(false, Ordering::SeqCst) {
+        let width = dimensions().map_or(0, |(width, _)| width);
+        TERMINAL_WIDTH.store(width, Ordering::SeqCst);
+    }
+    Some(TERMINAL_WIDTH.load(Ordering::SeqCst)).filter(|width| *width > 0)
+}
+
+/// The number of columns `text` takes in the terminal: the escape sequences of the
+/// colors take none, and wide characters like CJK ones or emoji take two.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            width += c.width().unwrap_or(0);
+            continue;
+        }
+        match chars.next() {
+            // CSI: `ESC [`, parameters and a final byte between `@` and `~`
+            Some('[') => {
+                for c in chars.by_ref() {
+                    if ('@'..='~').contains(&c) {
+                        break;
+                    }
                 }
             }
-            println!();
+            // OSC: `ESC ]` until BEL or `ESC \`
+            Some(']') => {
+                while let Some(c) = chars.next() {
+                    if c == '\x07' || (c == '\x1b' && chars.next_if_eq(&'\\').is_some()) {
+                        break;
+                    }
+                }
+            }
+            _ => {}
         }
-    } else {
-        error!("commands::columnize_text(): Cannot retrieve terminal width. Columnizing without formating...");
+    }
+    width
+}
+
+/// The beginning of `text` that fits in `width` columns. The escape sequences are
+/// kept, and the colors are reset at the end if something was cut.
+pub fn truncate_to_width(text: &str, width: usize) -> String {
+    let mut truncated = String::with_capacity(text.len());
+    let mut used = 0;
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            truncated.push(c);
+            if let Some(next) = chars.next() {
+                truncated.push(next);
+                if next == '[' {
+                    for c in chars.by_ref() {
+                        truncated.push(c);
+                        if ('@'..='~').contains(&c) {
+                            break;
+                        }
+                    }
+                }
+            }
+            continue;
+        }
+        let char_width = c.width().unwrap_or(0);
+        if used + char_width > width {
+            truncated.push_str("\x1b[0m");
+            break;
+        }
+        used += char_width;
+        truncated.push(c);
+    }
+    truncated
+}
+
+/// The rows and the widths of the columns of the widest layout of `widths` that fits
+/// in `line_width`, filled column by column. Like GNU `ls`, every column is as wide
+/// as its longest item.
+fn column_layout(widths: &[usize], line_width: usize) -> (usize, Vec<usize>) {
+    const SEPARATOR: usize = 2;
+    // One character and the separator is the narrowest a column can be
+    let max_columns = (line_width / (SEPARATOR + 1)).clamp(1, widths.len().max(1));
+
+    for columns in (2..=max_columns).rev() {
+        let rows = widths.len().div_ceil(columns);
+        let column_widths: Vec<usize> = widths
+            .chunks(rows)
+            .map(|column| column.iter().max().copied().unwrap_or(0))
+            .collect();
+        let line: usize = column_widths.iter().sum::<usize>() + SEPARATOR * (column_widths.len() - 1);
+        if line <= line_width {
+            return (rows, column_widths);
+        }
+    }
+    (widths.len(), vec![widths.iter().max().copied().unwrap_or(0)])
+}
+
+/// Prints `items` in as many columns as fit in the terminal, one per line when the
+/// output is not a terminal.
+pub fn columnize_text(items: &[String], io: &mut Io) {
+    info!("commands::columnize_text(): Columnizing text...");
+    let line_width = if io.stdout.is_terminal() { terminal_width() } else { None };
+    let Some(line_width) = line_width else {
         for item in items {
-            println!("{}", item);
+            io.out(item);
+        }
+        return;
+    };
+
+    let widths: Vec<usize> = items.iter().map(|item| display_width(item)).collect();
+    let (rows, column_widths) = column_layout(&widths, line_width);
+    for row in 0..rows {
+        let mut line = String::new();
+        for (column, column_width) in column_widths.iter().enumerate() {
+            let index = row + column * rows;
+            let Some(item) = items.get(index) else {
+                break;
+            };
+            line.push_str(item);
+            // The last item of the line isn't padded
+            if items.get(index + rows).is_some() && column + 1 < column_widths.len() {
+                line.push_str(&" ".repeat(column_width - widths[index] + 2));
+            }
         }
+        io.out(line);
     }
 }
 
-fn format_system_time(time: SystemTime) -> String {
+pub fn format_system_time(time: SystemTime) -> String {
     let datetime: chrono::DateTime<chrono::Local> = time.into();
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
-#[derive(Clone)]
-pub struct ExternalCommands {
-    exec_files: Vec<PathBuf>
+#[derive(Debug)]
+pub enum ExecError {
+    /// A command name that is not in PATH.
+    NotFound(String),
+    /// A path (a word with a slash) that doesn't exist.
+    NoSuchFile(String),
+    PermissionDenied(String),
+    IsADirectory(String),
+    /// A script whose `#!` interpreter doesn't exist.
+    BadInterpreter { path: String, interpreter: String },
+    Failed(String),
 }
 
-pub fn get_exec_vector() -> Result<Vec<PathBuf>, String> {
-    let path_var = env::var("PATH").unwrap_or_else(|_| String::new());
-    let mut exec_vec: Vec<PathBuf> = vec![];
-
-    let mut dont_exists_count = 0;
-    for path in env::split_paths(&path_var) {
-        info!("commands::get_exec_vector(): Reading {}...", path.as_path().to_str().unwrap());
-        
-        if path.exists() {
-            let readed = path.read_dir();
-
-            match readed {
-                Ok(read_obj) => {
-                    for entry in read_obj {
-                        if let Ok(folder) = entry {
-                            if folder.path().is_executable() {
-                                exec_vec.push(folder.path().to_path_buf());
-                            }
-                        }
-                    }
-                }
-                Err(err) => {
-                    error!("commands::get_exec_vector(): Error while trying to read this dir '{}' located in the PATH enviroment variable", path.as_os_str().to_str().unwrap());
-                    return Err(err.to_string());
-                }
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::NotFound(name) => write!(f, "unknown command: {name}"),
+            ExecError::NoSuchFile(path) => write!(f, "{path}: No such file or directory"),
+            ExecError::PermissionDenied(path) => write!(f, "{path}: Permission denied"),
+            ExecError::IsADirectory(path) => write!(f, "{path}: Is a directory"),
+            ExecError::BadInterpreter { path, interpreter } => {
+                write!(f, "{path}: {interpreter}: bad interpreter: No such file or directory")
             }
-        } else {
-            dont_exists_count += 1;
+            ExecError::Failed(reason) => write!(f, "{reason}"),
         }
     }
-    if dont_exists_count > 0 {
-        info!("commands::get_exec_vector(): {dont_exists_count} folder/folders doesn't exists in the PATH environment variable");
-    }
-    Ok(exec_vec)
+}
+
+/// Reads the interpreter from the `#!` line of a script.
+fn read_interpreter(path: &Path) -> Option<String> {
+    let mut buffer = [0u8; 256];
+    let read = fs::File::open(path).and_then(|mut file| file.read(&mut buffer)).ok()?;
+    let first_line = buffer[..read].split(|byte| *byte == b'\n').next()?;
+    let shebang = first_line.strip_prefix(b"#!")?;
+    let shebang = String::from_utf8_lossy(shebang);
+    shebang.split_whitespace().next().map(String::from)
+}
+
+#[derive(Clone)]
+pub struct ExternalCommands {
+    index: Arc<Mutex<CommandIndex>>,
+    /// Held while PATH is scanned, so two threads don't scan it at the same time.
+    /// The index stays usable meanwhile.
+    scanning: Arc<Mutex<()>>,
 }
 
 impl ExternalCommands {
-    pub fn new() -> Result<Self, ()> {
-        let vec_of_exec = get_exec_vector();
-        match vec_of_exec {
-            Ok(vec_ex) => {
-                Ok(ExternalCommands { exec_files: vec_ex })
-            }
-            Err(err) => {
-                error!("commands::ExternalCommands::new(): Error while trying to get the executables vector");
-                error!("commands::ExternalCommands::new(): {err}");
-                println!("yarsh: Error while trying to find the executables in the PATH environment variable");
-                Err(())
+    /// Returns right away, PATH is scanned in the background and on demand.
+    pub fn new() -> Self {
+        let external = ExternalCommands {
+            index: Arc::new(Mutex::new(CommandIndex::new())),
+            scanning: Arc::new(Mutex::new(())),
+        };
+        let warm_up = external.clone();
+        thread::spawn(move || {
+            info!("commands::ExternalCommands::new(): Indexing the PATH environment variable in the background...");
+            warm_up.fresh_index(|_| ());
+        });
+        external
+    }
+
+    fn with_index<T>(&self, func: impl FnOnce(&mut CommandIndex) -> T) -> T {
+        let mut index = self.index.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        func(&mut index)
+    }
+
+    /// Like `with_index`, scanning PATH first if the index is out of date. The
+    /// directories are read without holding the lock of the index.
+    fn fresh_index<T>(&self, func: impl FnOnce(&mut CommandIndex) -> T) -> T {
+        let stamp = Stamp::now();
+        if !self.with_index(|index| index.is_current(&stamp)) {
+            let _scanning = self.scanning.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            // Another thread may have scanned it while this one waited
+            if !self.with_index(|index| index.is_current(&stamp)) {
+                let scan = Scan::new(stamp);
+                self.with_index(|index| index.update(scan));
             }
         }
+        self.with_index(func)
     }
 
-    pub fn find_executable_command(&self, executable_name: &str) -> Option<PathBuf> {
-        // Obtener la variable PATH
-        // let path_var = env::var("PATH").unwrap_or_else(|_| String::new());
-    
-        // Dividir la variable PATH en una lista de directorios
-        // let paths: Vec<_> = env::split_paths(&path_var).collect();
-    
-        // Buscar el ejecutable en cada directorio del PATH
-        // let mut found = None;
-        // for path in paths {
-        //     let executable_path = path.join(executable_name);
-        //     if fs::metadata(&executable_path).is_ok() {
-        //         info!(
-        //             "commands::run_external_command(): Founded an executable on '{}'",
-        //             executable_path.as_os_str().to_str().unwrap()
-        //         );
-        //         found = Some(executable_path);
-        //         break;
-        //     }
-        // }
-        // found
-        let binded = PathBuf::from(executable_name);
-        println!("{}", binded.starts_with("./") == true);
-        if binded.starts_with("./") {
-            error!("commands::ExternalCommands::find_executable_command(): Browsing in the current directory");
-
-            match Builtin::getcwd() {
-                Ok(mut path_exec_name) => {
-                    path_exec_name.push(executable_name);
-                    if path_exec_name.exists() {
-                        return Some(path_exec_name);
-                    } else {
-                        return None;
-                    }
-                }
-                Err(err) => {
-                    error!("commands::ExternalCommands::find_executable_command(): Error while trying to get the working directory");
-                    error!("commands::ExternalCommands::find_executable_command(): {err}");
-                    return None;
-                }
-            }
+    /// Looks `name` up in an up to date index, counting a hit with `count_hit`. A miss
+    /// scans PATH again once, the file may have become executable without changing
+    /// the mtime of its directory.
+    fn lookup(&self, name: &str, count_hit: bool) -> Option<PathBuf> {
+        let find = |index: &mut CommandIndex| if count_hit { index.lookup(name) } else { index.find(name) };
+        let found = self.fresh_index(find);
+        if found.is_some() || self.with_index(|index| index.was_missed(name)) {
+            return found;
         }
-        for x in self.exec_files.clone() {
-            if x.ends_with(executable_name) {
-                info!(
-                    "commands::run_external_command(): Founded an executable on '{}'",
-                    x.as_os_str().to_str().unwrap(),
-                );
-                return Some(x);
+        let _scanning = self.scanning.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let scan = Scan::new(Stamp::now());
+        self.with_index(|index| {
+            index.update(scan);
+            let found = find(index);
+            if found.is_none() {
+                index.note_miss(name);
             }
+            found
+        })
+    }
+
+    /// Scans every PATH directory again right now.
+    pub fn rehash(&self) {
+        let _scanning = self.scanning.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let scan = Scan::new(Stamp::now());
+        self.with_index(|index| {
+            index.forget_misses();
+            index.update(scan);
+        });
+    }
+
+    /// Forgets every remembered command, PATH is scanned again on the next lookup.
+    pub fn clear_index(&self) {
+        self.with_index(|index| index.clear());
+    }
+
+    /// Forgets the hits of `name`. Returns false if it was not remembered.
+    pub fn forget(&self, name: &str) -> bool {
+        self.with_index(|index| index.forget(name))
+    }
+
+    /// The commands that have been used, with their hits and paths.
+    pub fn remembered(&self) -> Vec<(String, usize, PathBuf)> {
+        self.with_index(|index| index.remembered())
+    }
+
+    pub fn find_executable_command(&self, executable_name: &str) -> Option<PathBuf> {
+        self.resolve_command(executable_name).ok()
+    }
+
+    /// Like `find_executable_command`, without counting it as a use in the `hash`
+    /// table. For the lookups that only report where a command is.
+    pub fn locate_command(&self, executable_name: &str) -> Option<PathBuf> {
+        if executable_name.contains('/') {
+            return ExternalCommands::resolve_path(Path::new(executable_name)).ok();
         }
-        None
+        self.lookup(executable_name, false)
     }
 
-    pub fn run_external_command(&self, command: &str) -> Result<Option<Child>, &str> {
-        // Dividir el comando en partes separadas por el carácter '|'
-        let commands: Vec<&str> = command.trim().split('|').collect();
-
-        let more_commands: Vec<&str> = command.trim().split(';').collect();
-        if more_commands.len() > 1 {
-            for x in more_commands {
-                match self.run_external_command(x) {
-                    Ok(out_opt) => {
-                        if let Some(mut out) = out_opt {
-                            out.wait();
-                        }
-                    }
-                    Err(err) => {
-                        error!("commands::run_external_command : loop (for) : match : Err(err): Cannot execute the extra command: {x}");
-                        error!("commands::run_external_command : loop (for) : match : Err(err): {err}");
-                        println!("yarp: Cannot run the executable {x}");
-                    }
-                }
+    /// Finds the file to run for `executable_name`. Names with a slash are paths
+    /// (absolute or relative to the working directory) and are used as they are,
+    /// any other name is looked up in PATH.
+    pub fn resolve_command(&self, executable_name: &str) -> Result<PathBuf, ExecError> {
+        if executable_name.contains('/') {
+            return ExternalCommands::resolve_path(Path::new(executable_name));
+        }
+
+        let found = self.lookup(executable_name, true);
+        if let Some(x) = &found {
+            info!(
+                "commands::run_external_command(): Founded an executable on '{}'",
+                x.to_string_lossy(),
+            );
+        }
+        found.ok_or_else(|| ExecError::NotFound(executable_name.to_string()))
+    }
+
+    /// Checks that the file at `path` can be run.
+    pub fn resolve_path(path: &Path) -> Result<PathBuf, ExecError> {
+        let shown = path.to_string_lossy().into_owned();
+        info!("commands::ExternalCommands::resolve_path(): Running {shown} by path");
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+                return Err(ExecError::PermissionDenied(shown));
             }
-            return Err("No");
+            Err(_) => return Err(ExecError::NoSuchFile(shown)),
+        };
+        if metadata.is_dir() {
+            return Err(ExecError::IsADirectory(shown));
         }
+        if !path.is_executable() {
+            return Err(ExecError::PermissionDenied(shown));
+        }
+        Ok(path.to_path_buf())
+    }
 
-        // Procesar cada comando en el pipeline
-        let mut previous_output = None;
-        for (index, cmd) in commands.iter().enumerate() {
-            // Dividir el comando en partes separadas por espacios en blanco
-            let parts: Vec<&str> = cmd.split_whitespace().collect();
-
-            // Verificar si hay un ejecutable en la primera parte del comando
-            if let Some(executable) = parts.first() {
-                let found = self.find_executable_command(executable);
-
-                // Si el ejecutable no se encuentra
-                if let Some(executable_path) = found {
-                    // Configurar las opciones de redirección de entrada/salida
-                    let stdout = if index < commands.len() - 1 {
-                        // Si no es el último comando, redirigir la salida al siguiente comando
-                        Stdio::piped()
-                    } else {
-                        // Si es el último comando, heredar la salida estándar del proceso padre
-                        Stdio::inherit()
-                    };
-                    let stdin = previous_output.map_or(Stdio::inherit(), |output: Child| {
-                        Stdio::from(output.stdout.unwrap())
-                    });
-
-                    // Ejecutar el comando
-                    info!("commands::run_external_command(): Executing command...");
-                    let child_process = Command::new(executable_path)
-                        .args(&parts[1..])
-                        .stdout(stdout)
-                        .stdin(stdin)
-                        .spawn();
-
-                    // Verificar si se pudo ejecutar el comando
-                    match child_process {
-                        Ok(child) => {
-                            // Obtener la salida estándar del proceso actual para usarla como entrada en el siguiente comando
-                            previous_output = Some(child);
-                            if index == commands.len() - 1 {
-                                return Ok(previous_output);
-                            }
-                        }
-                        Err(_) => {
-                            return Err("Failed to execute command");
-                        }
-                    }
-                } else {
-                    println!("yarp: unknown command: {}", executable);
-                    return Err("Executable not found");
-                }
+    /// Every file `executable_name` could run, in PATH order. Unlike the index, this
+    /// also lists the executables shadowed by an earlier PATH directory.
+    pub fn find_all(&self, executable_name: &str) -> Vec<PathBuf> {
+        if executable_name.contains('/') {
+            return self.resolve_command(executable_name).into_iter().collect();
+        }
+        let path_var = env::var_os("PATH").unwrap_or_default();
+        let mut found: Vec<PathBuf> = vec![];
+        for dir in env::split_paths(&path_var) {
+            let candidate = dir.join(executable_name);
+            if candidate.is_executable() && !candidate.is_dir() && !found.contains(&candidate) {
+                found.push(candidate);
             }
         }
-        Ok(None)
+        found
     }
-}
 
-#[derive(Parser, Debug)]
-#[command(author = "XtremeTHN", version = "1.0.1", about = "List files", long_about = None)]
-struct LsArgs {
-    #[structopt(name = "DIRECTORY", default_value = ".")]
-    dir: String,
-}
+    /// Turns the error of a failed spawn into the reason the user should see.
+    fn spawn_error(path: &Path, err: io::Error) -> ExecError {
+        let shown = path.to_string_lossy().to_string();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => ExecError::PermissionDenied(shown),
+            // The file exists, so what is missing is the interpreter of the script
+            io::ErrorKind::NotFound if path.exists() => match read_interpreter(path) {
+                Some(interpreter) => ExecError::BadInterpreter { path: shown, interpreter },
+                None => ExecError::NoSuchFile(shown),
+            },
+            io::ErrorKind::NotFound => ExecError::NoSuchFile(shown),
+            _ => ExecError::Failed(format!("{shown}: {err}")),
+        }
+    }
 
-#[derive(Parser, Debug)]
-#[command(author = "XtremeTHN", version = "1.0.10", about = "Edit the config file of Yarsh", long_about = None)]
-struct ConfigArgs {
-    #[arg(
-        short = 's',
-        long = "set", 
-        help = "Specifies that you want to set a value in the configs",
-        requires_all = &["section", "field", "value"],
-    )]
-    set_opt: bool,
-
-    #[arg(
-        short = 'g',
-        long = "get",
-        help = "Specifies that you want to get a value in the configs",
-        requires_all = &["section", "field"],
-    )]
-    get_opt: bool,
-
-    #[arg(short = 'l', long = "list", help = "List all values of the configs")]
-    list_opt: bool,
-
-    section: Option<String>,
-    field: Option<String>,
-    value: Option<String>,
-}
+    /// File names of every executable found in PATH.
+    pub fn command_names(&self) -> Vec<String> {
+        self.fresh_index(|index| index.names())
+    }
 
-#[derive(Parser, Debug)]
-#[command(author = "XtremeTHN", version = "0.8.0", about = "Read files with this command", long_about = None)]
-struct ReadArgs {
-    #[arg(
-        short = 'f',
-        long = "force",
-        help = "Specifies that you want to force the read of a file"
-    )]
-    force_opt: bool,
-
-    file: PathBuf,
+    /// Starts the file `path` with `args`, reading from and writing to the handles of `io`.
+    /// The arguments are passed as they are, they don't need to be UTF-8.
+    pub fn spawn(path: &Path, args: &[impl AsRef<OsStr>], io: &Io) -> Result<Child, ExecError> {
+        let (stdin, stdout, stderr) = io.to_stdio().map_err(|err| ExecError::Failed(err.to_string()))?;
+
+        info!("commands::ExternalCommands::spawn(): Executing {}...", path.to_string_lossy());
+        Command::new(path)
+            .args(args)
+            .stdin(stdin)
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()
+            .map_err(|err| ExternalCommands::spawn_error(path, err))
+    }
 }
-pub struct Builtin {}
-
-impl Builtin {
-    pub fn config_cmd(arguments: Vec<String>) {
-        info!("commands::Builtin::config_cmd(): Reading config file...");
-        let configs = setup::load_conf();
-        match ConfigArgs::try_parse_from(arguments) {
-            Ok(args) => {
-                if args.list_opt {
-                    println!("{}: Listing values...", "config".blue());
-                    println!(
-                        "{} ({}):",
-                        "Logs Configurations".bold(),
-                        "logs_configurations".green()
-                    );
-                    println!(
-                        "  write_to_file: {}",
-                        configs.logs_configurations.write_to_file
-                    );
-                    println!(
-                        "  write_to_stdout: {}",
-                        configs.logs_configurations.write_to_stdout
-                    );
-                }
-                if args.set_opt {
-                    let mut configs_set_opt_clone = configs.clone();
-                    match args.section.clone().unwrap().as_str() {
-                        "logs_configurations" => match args.field.clone().unwrap().as_str() {
-                            "write_to_file" => {
-                                configs_set_opt_clone.logs_configurations.write_to_file =
-                                    args.value.clone().unwrap().parse().unwrap();
-                                write_conf(configs_set_opt_clone);
-                            }
-                            "write_to_stdout" => {
-                                configs_set_opt_clone.logs_configurations.write_to_stdout =
-                                    args.value.clone().unwrap().parse().unwrap();
-                                write_conf(configs_set_opt_clone);
-                            }
-                            &_ => {
-                                println!("{}: No such field", "config".blue());
-                                ()
-                            }
-                        },
-                        &_ => {
-                            println!("{}: No such section", "config".blue());
-                        }
-                    }
-                }
-                if args.get_opt {
-                    let configs_get_opt_clone = configs;
-                    match args.section.clone().unwrap().as_str() {
-                        "logs_configurations" => match args.field.unwrap().as_str() {
-                            "write_to_file" => {
-                                println!(
-                                    "{}: {}",
-                                    "Value".cyan(),
-                                    configs_get_opt_clone.logs_configurations.write_to_file
-                                );
-                            }
-                            "write_to_stdout" => {
-                                println!(
-                                    "{}: {}",
-                                    "Value".cyan(),
-                                    configs_get_opt_clone.logs_configurations.write_to_stdout
-                                );
-                            }
-                            &_ => {
-                                println!("{}: No such field", "config".blue());
-                                ()
-                            }
-                        },
-                        &_ => {
-                            println!("{}: No such section", "config".blue());
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                println!("{err}");
-            }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display_width_counts_the_columns() {
+        let cases = [
+            ("", 0),
+            ("ls", 2),
+            ("caf\u{e9}", 4),
+            // A combining accent takes no column
+            ("cafe\u{301}", 4),
+            ("\u{65e5}\u{672c}\u{8a9e}.txt", 10),
+            ("\u{1f980}", 2),
+            ("\x1b[1;34mdir\x1b[0m", 3),
+            ("\x1b]8;;file:///tmp\x07tmp\x1b]8;;\x07", 3),
+            ("\x1b]8;;file:///tmp\x1b\\tmp\x1b]8;;\x1b\\", 3),
+        ];
+        for (text, width) in cases {
+            assert_eq!(display_width(text), width, "{text:?}");
         }
     }
-    pub fn clear_screen() -> io::Result<()> {
-        info!("commands::clear_screan(): Trying to clear terminal");
-        let mut stdout = io::stdout();
-        execute!(
-            stdout,
-            terminal::Clear(terminal::ClearType::All),
-            MoveTo(0, 0)
-        )?;
-        stdout.flush()?;
-        Ok(())
+
+    #[test]
+    fn truncate_to_width_keeps_the_colors() {
+        assert_eq!(truncate_to_width("abcdef", 3), "abc\x1b[0m");
+        assert_eq!(truncate_to_width("abc", 3), "abc");
+        assert_eq!(truncate_to_width("\x1b[31mabcdef", 2), "\x1b[31mab\x1b[0m");
+        // A wide character that doesn't fit is left out whole
+        assert_eq!(truncate_to_width("a\u{65e5}", 2), "a\x1b[0m");
     }
 
-    pub fn read_file(arguments: Vec<String>) {
-        match ReadArgs::try_parse_from(arguments) {
-            Ok(opts) => {
-                let mut is_exec = opts.file.is_executable();
-                if opts.force_opt {
-                    is_exec = false;
-                }
-                match is_exec {
-                    false => {
-                        let file = fs::File::open(opts.file.clone());
-                        info!("commands::Builtin::read_file(): Reading file...");
-                        match file {
-                            Ok(mut file_obj) => {
-                                info!("commands::Builtin::read_file(): Creating buffer...");
-                                let mut buffer = String::new();
-                                match file_obj.read_to_string(&mut buffer) {
-                                    Ok(_) => println!("{}", buffer),
-                                    Err(err) => {
-                                        error!("commands::Builtin::read_file(): Error while trying to save file content to the buffer");
-                                        error!("commands::Builtin::read_file(): {}", err);
-                                        println!(
-                                            "{}: Error while trying to read {}",
-                                            "read".green(),
-                                            opts.file.as_path().to_str().unwrap()
-                                        );
-                                        println!("{}: More information in the logs (You can use the 'logs last_log' command)", "read".green());
-                                    }
-                                }
-                            }
-                            Err(err) => {
-                                error!("commands::Builtin::read_file(): Error while trying to open the file");
-                                error!("commands::Builtin::read_file(): {}", err);
-                                println!("{}: Error while trying to read {}", "read".green(), err);
-                                println!("{}: More information in the logs (You can use the 'logs last_log' command)", "read".green());
-                            }
-                        }
-                    }
-                    true => {
-                        info!("commands::Builtin::read_file(): Executable file detected");
-                        info!("commands::Builtin::read_file(): Reading metadata...");
-                        let metadata = fs::metadata(opts.file.clone());
-                        match metadata {
-                            Ok(md_obj) => {
-                                println!("{}: Executable file detected! Reading metadata instead of the file content...", "read".green());
-                                let mut metadata_formated: Vec<String> = vec![];
-
-                                let lm = md_obj.accessed().unwrap_or(SystemTime::now());
-
-                                let mut read_write_perms = String::new();
-                                if md_obj.permissions().readonly() {
-                                    read_write_perms.push_str(
-                                        format!("{}: Read only", "Permissions".blue().bold())
-                                            .as_str(),
-                                    );
-                                } else {
-                                    read_write_perms.push_str(
-                                        format!("{}: Read and write", "Permissions".blue().bold())
-                                            .as_str(),
-                                    );
-                                }
-
-                                metadata_formated.push(format!(
-                                    "{}: {}",
-                                    "Last Modified".blue().bold(),
-                                    format_system_time(lm)
-                                ));
-                                metadata_formated.push(read_write_perms);
-                                info!("commands::Builtin::read_file(): Showing metadata...");
-                                columnize_text(&metadata_formated);
-                            }
-                            Err(err) => {
-                                error!("commands::Builtin::read_file(): Error while trying to get file metadata");
-                                error!("commands::Builtin::read_file(): {}", err);
-                                println!(
-                                    "read: Couldnt read {} metadata",
-                                    opts.file.as_path().to_str().unwrap()
-                                );
-                                ()
-                            }
-                        }
-                    }
-                }
-            }
-            Err(err) => {
-                println!("{err}");
-            }
+    #[test]
+    fn column_layout_uses_the_widest_layout_that_fits() {
+        let cases: &[(&[usize], usize, usize, &[usize])] = &[
+            (&[], 80, 0, &[0]),
+            (&[3], 80, 1, &[3]),
+            // 3 + 2 + 3 + 2 + 3 fills a line of 13 exactly
+            (&[3, 3, 3], 13, 1, &[3, 3, 3]),
+            (&[3, 3, 3], 12, 2, &[3, 3]),
+            (&[3, 3, 3], 7, 3, &[3]),
+            (&[10, 1, 1, 1], 14, 2, &[10, 1]),
+            (&[20], 10, 1, &[20]),
+        ];
+        for (widths, line_width, rows, column_widths) in cases {
+            assert_eq!(column_layout(widths, *line_width), (*rows, column_widths.to_vec()), "{widths:?} in {line_width}");
         }
     }
 
-    pub fn getcwd() -> Result<PathBuf, String> {
-        info!("commands::Builtin::getcwd(): Retrieving the working directory...");
-        match env::current_dir() {
-            Ok(wkd) => {
-                println!("{}", wkd.as_os_str().to_string_lossy().to_string());
-                Ok(wkd)
-            }
-            Err(err) => {
-                error!("commands::Builtin::getcwd(): Cannot get the current work directory because this error:");
-                error!("commands::Builtin::getcwd(): {err}");
-                Err(err.to_string())
-            }
-        }
+    static PREVIOUS_CALLED_WITH_INFO: AtomicBool = AtomicBool::new(false);
+
+    extern "C" fn previous_handler(_: libc::c_int, info: *mut libc::siginfo_t, _: *mut libc::c_void) {
+        PREVIOUS_CALLED_WITH_INFO.store(!info.is_null(), Ordering::SeqCst);
     }
 
-    pub fn list_cmd(arguments: Vec<String>) {
-        let args_obj = LsArgs::try_parse_from(arguments);
-        match args_obj {
-            Ok(opt) => {
-                info!(
-                    "commands::Builtin::list_cmd(): Listing files in {}",
-                    opt.dir
-                );
-                let work_dir_convertion = PathBuf::from(&opt.dir);
-                let mut colored_vector: Vec<String> = vec![];
-                info!("commands::Builtin::list_cmd(): Formating text...");
-                match work_dir_convertion.read_dir() {
-                    Ok(iterator) => {
-                        for x in iterator {
-                            if x.as_ref().unwrap().path().is_dir() {
-                                let file = x.unwrap().path().clone();
-                                colored_vector.push(
-                                    file.file_name()
-                                        .unwrap()
-                                        .to_str()
-                                        .unwrap()
-                                        .blue()
-                                        .to_string(),
-                                );
-                            } else if x.as_ref().unwrap().path().is_symlink() {
-                                let file = x.unwrap().path().clone();
-                                colored_vector.push(
-                                    file.file_name()
-                                        .unwrap()
-                                        .to_str()
-                                        .unwrap()
-                                        .green()
-                                        .to_string(),
-                                );
-                            } else {
-                                let file = x.unwrap().path().clone();
-                                colored_vector.push(
-                                    file.file_name()
-                                        .unwrap()
-                                        .to_str()
-                                        .unwrap()
-                                        .yellow()
-                                        .to_string(),
-                                );
-                            }
-                        }
-                        columnize_text(&colored_vector);
-                    }
-                    Err(err) => {
-                        error!(
-                            "commands::Builtin::list_cmd(): Cannot read the specified directory"
-                        );
-                        error!("commands::Builtin::list_cmd(): {err}");
-                        println!("ls: Cannot read this directory");
-                    }
-                }
-            }
-            Err(err) => {
-                error!("commands::Builtin::list_cmd(): Cannot list files because this error:");
-                error!("commands::Builtin::list_cmd(): {err}");
-                println!("ls: Cannot list files");
-            }
-        };
+    #[test]
+    fn the_sigwinch_handler_calls_a_siginfo_handler_installed_before() {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = previous_handler as SigInfoHandler as libc::sighandler_t;
+            action.sa_flags = libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            assert_eq!(libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut()), 0);
+        }
+        watch_terminal_size();
+        WIDTH_CHANGED.store(false, Ordering::SeqCst);
+        unsafe {
+            libc::raise(libc::SIGWINCH);
+        }
+        assert!(WIDTH_CHANGED.load(Ordering::SeqCst));
+        assert!(PREVIOUS_CALLED_WITH_INFO.load(Ordering::SeqCst));
     }
 }
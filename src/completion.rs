@@ -0,0 +1,171 @@
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{CompletionType, Context, Helper};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::builtins::{registry, Builtin, CompletionHint};
+use crate::colors::FileColors;
+use crate::commands::ExternalCommands;
+use crate::expand;
+
+/// Tab completion of the prompt. The first word completes to commands, the rest of
+/// the words to what the builtin says its arguments are (files for external commands).
+pub struct ShellHelper {
+    external: ExternalCommands,
+    files: FilenameCompleter,
+    /// Aliases and functions, they can change while the shell runs.
+    pub extra_commands: Vec<String>,
+    /// Colors of the files in the completion list and the command line.
+    pub colors: FileColors,
+    /// The directory of the files being completed, the list only shows their names.
+    completion_dir: RefCell<Option<PathBuf>>,
+}
+
+impl ShellHelper {
+    pub fn new(external: ExternalCommands, colors: FileColors) -> Self {
+        ShellHelper {
+            external,
+            files: FilenameCompleter::new(),
+            extra_commands: vec![],
+            colors,
+            completion_dir: RefCell::new(None),
+        }
+    }
+
+    fn complete_command(&self, word: &str, start: usize) -> (usize, Vec<Pair>) {
+        let mut names: Vec<String> = registry().names().into_iter().map(String::from).collect();
+        names.extend(self.external.command_names());
+        names.extend(self.extra_commands.iter().cloned());
+        names.retain(|name| name.starts_with(word));
+        names.sort();
+        names.dedup();
+        let pairs = names
+            .into_iter()
+            .map(|name| Pair { display: name.clone(), replacement: format!("{name} ") })
+            .collect();
+        (start, pairs)
+    }
+
+    /// The flags of `builtin` starting with `word`, taken from its clap definition.
+    fn complete_flag(builtin: &dyn Builtin, word: &str, start: usize) -> (usize, Vec<Pair>) {
+        let command = builtin.command();
+        let mut flags = vec![];
+        for arg in command.get_arguments() {
+            if let Some(long) = arg.get_long() {
+                flags.push(format!("--{long}"));
+            }
+            if let Some(short) = arg.get_short() {
+                flags.push(format!("-{short}"));
+            }
+        }
+        flags.retain(|flag| flag.starts_with(word));
+        flags.sort();
+        let pairs = flags
+            .into_iter()
+            .map(|flag| Pair { display: flag.clone(), replacement: format!("{flag} ") })
+            .collect();
+        (start, pairs)
+    }
+}
+
+impl Completer for ShellHelper {
+    type Candidate = Pair;
+
+    fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(char::is_whitespace).map_or(0, |index| index + 1);
+        let word = &before[start..];
+        let builtin = before.split_whitespace().next().and_then(|name| registry().get(name));
+
+        let hint = if before[..start].trim().is_empty() {
+            if word.contains('/') {
+                CompletionHint::Files
+            } else {
+                CompletionHint::Commands
+            }
+        } else if let (Some(builtin), true) = (builtin, word.starts_with('-')) {
+            return Ok(ShellHelper::complete_flag(builtin, word, start));
+        } else {
+            builtin.map_or(CompletionHint::Files, |builtin| builtin.completion())
+        };
+
+        *self.completion_dir.borrow_mut() = match hint {
+            CompletionHint::Files | CompletionHint::Directories => {
+                let dir = word.rfind('/').map_or("", |index| &word[..=index]);
+                Some(PathBuf::from(expand::expand_tilde(dir)))
+            }
+            _ => None,
+        };
+        match hint {
+            CompletionHint::None => Ok((pos, vec![])),
+            CompletionHint::Commands => Ok(self.complete_command(word, start)),
+            CompletionHint::Files => self.files.complete_path(line, pos),
+            CompletionHint::Directories => {
+                let (start, mut pairs) = self.files.complete_path(line, pos)?;
+                pairs.retain(|pair| pair.replacement.ends_with('/'));
+                Ok((start, pairs))
+            }
+        }
+    }
+}
+
+impl Hinter for ShellHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ShellHelper {
+    /// Paints the words that are the paths of existing files.
+    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
+        let mut highlighted = String::with_capacity(line.len());
+        let mut painted = false;
+        let mut rest = line;
+        let mut first = true;
+        while !rest.is_empty() {
+            let spaces = rest.len() - rest.trim_start().len();
+            highlighted.push_str(&rest[..spaces]);
+            rest = &rest[spaces..];
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            let word = &rest[..end];
+            rest = &rest[end..];
+            if word.is_empty() {
+                break;
+            }
+
+            let path = expand::expand_tilde(word);
+            let metadata = fs::symlink_metadata(&path).ok();
+            // The command is left alone, `ls` is not the file ./ls
+            if metadata.is_some() && (!first || word.contains('/')) {
+                highlighted.push_str(&self.colors.paint(word, Path::new(&path), metadata.as_ref()));
+                painted = true;
+            } else {
+                highlighted.push_str(word);
+            }
+            first = false;
+        }
+        if painted {
+            Cow::Owned(highlighted)
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    fn highlight_candidate<'c>(&self, candidate: &'c str, _completion: CompletionType) -> Cow<'c, str> {
+        match &*self.completion_dir.borrow() {
+            Some(dir) => Cow::Owned(self.colors.paint_path(candidate, &dir.join(candidate))),
+            None => Cow::Borrowed(candidate),
+        }
+    }
+
+    fn highlight_char(&self, _line: &str, _pos: usize) -> bool {
+        true
+    }
+}
+
+impl Validator for ShellHelper {}
+
+impl Helper for ShellHelper {}
@@ -0,0 +1,771 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Print, Stylize};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use log::info;
+use serde_yaml::{Mapping, Value};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use unicode_width::UnicodeWidthStr;
+
+use crate::colors;
+use crate::commands::truncate_to_width;
+use crate::setup::{self, YamlConfiguration};
+
+/// The fields whose values are one of a few names.
+const CHOICES: &[(&str, &[&str])] = &[
+    ("terminal_config.edit_mode", &["emacs", "vi"]),
+    ("logs_configurations.level", &["OFF", "ERROR", "WARN", "INFO", "DEBUG", "TRACE"]),
+];
+
+/// Given back by the change of the config file when it was changed by someone else
+/// since the editor read it.
+const CHANGED: &str = "The config file changed since it was opened";
+
+/// How a field is edited, from the type of its default value.
+#[derive(Clone, Copy)]
+enum Kind {
+    Toggle,
+    Choice(&'static [&'static str]),
+    Text,
+    /// Text that can be empty, which is written as `null`.
+    OptionalText,
+    List,
+    Map,
+}
+
+struct Field {
+    section: String,
+    key: String,
+    kind: Kind,
+}
+
+impl Field {
+    fn path(&self) -> String {
+        format!("{}.{}", self.section, self.key)
+    }
+}
+
+/// The fields of every section of the configs, in the order they are written.
+fn fields() -> Vec<Field> {
+    let Ok(Value::Mapping(defaults)) = serde_yaml::to_value(YamlConfiguration::default()) else {
+        return vec![];
+    };
+    let mut fields = vec![];
+    for (section, values) in &defaults {
+        let (Some(section), Value::Mapping(values)) = (section.as_str(), values) else {
+            continue;
+        };
+        for (key, default) in values {
+            let Some(key) = key.as_str() else { continue };
+            let path = format!("{section}.{key}");
+            let kind = match (CHOICES.iter().find(|(field, _)| *field == path), default) {
+                (Some((_, choices)), _) => Kind::Choice(choices),
+                (None, Value::Bool(_)) => Kind::Toggle,
+                (None, Value::Null) => Kind::OptionalText,
+                (None, Value::Sequence(_)) => Kind::List,
+                (None, Value::Mapping(_)) => Kind::Map,
+                (None, _) => Kind::Text,
+            };
+            fields.push(Field { section: section.to_string(), key: key.to_string(), kind });
+        }
+    }
+    fields
+}
+
+fn text_of(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        _ => serde_yaml::to_string(value).map_or(String::new(), |text| text.trim_end().to_string()),
+    }
+}
+
+/// The keys of a map, sorted so they don't move around while they are edited.
+fn sorted_keys(map: &Mapping) -> Vec<String> {
+    let mut keys: Vec<String> = map.keys().map(text_of).collect();
+    keys.sort();
+    keys
+}
+
+/// Checks the edited configs before they are saved: the types of the fields, and
+/// the aliases, scripts and colors that couldn't work.
+fn validate(tree: &Value) -> Result<YamlConfiguration, String> {
+    let configs: YamlConfiguration = serde_yaml::from_value(tree.clone()).map_err(|err| err.to_string())?;
+    for (name, command) in &configs.terminal_config.alias {
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(format!("terminal_config.alias: `{name}` can't be the name of an alias"));
+        }
+        if command.trim().is_empty() {
+            return Err(format!("terminal_config.alias.{name}: the command is empty"));
+        }
+    }
+    if configs.scripts_config.files.iter().any(|file| file.as_os_str().is_empty()) {
+        return Err(String::from("scripts_config.files: a file is empty"));
+    }
+    for (key, style) in &configs.theme.colors {
+        if colors::parse_style(style).is_none() {
+            return Err(format!("theme.colors.{key}: `{style}` is not a color"));
+        }
+    }
+    Ok(configs)
+}
+
+/// Edits the config file of the user in the alternate screen until the user quits.
+pub fn edit() -> Result<(), String> {
+    let mut issues = vec![];
+    let (path, text) = setup::read_user_conf(&mut issues).ok_or_else(|| issues.join(", "))?;
+    let configs = setup::parse_conf(&text, &path.to_string_lossy()).0;
+    let tree = serde_yaml::to_value(configs).map_err(|err| err.to_string())?;
+    info!("config_editor::edit(): Editing {}", path.to_string_lossy());
+
+    let mut editor = ConfigEditor {
+        path,
+        original: tree.clone(),
+        tree,
+        fields: fields(),
+        selected: 0,
+        top: 0,
+        message: None,
+        armed: None,
+    };
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode().map_err(|err| err.to_string())?;
+    let result = execute!(stdout, EnterAlternateScreen, Hide).and_then(|_| editor.run(&mut stdout));
+    let restored = execute!(stdout, Show, LeaveAlternateScreen);
+    let cooked = terminal::disable_raw_mode();
+    result.and(restored).and(cooked).map_err(|err| err.to_string())
+}
+
+/// The rows for the lines, without the title and the status line.
+fn height() -> usize {
+    terminal::size().map_or(24, |(_, rows)| rows as usize).saturating_sub(2).max(1)
+}
+
+/// The first line on the screen, so `selected` is on it.
+fn scrolled(top: usize, selected: usize) -> usize {
+    let height = height();
+    if selected < top {
+        selected
+    } else if selected >= top + height {
+        selected + 1 - height
+    } else {
+        top
+    }
+}
+
+/// Draws the title, the lines from `top` with `selected` in reverse video, and the
+/// status line.
+fn draw(out: &mut impl Write, title: &str, lines: &[String], top: usize, selected: Option<usize>, status: &str) -> io::Result<()> {
+    let (columns, _) = terminal::size()?;
+    let columns = columns as usize;
+    queue!(out, MoveTo(0, 0), Clear(ClearType::CurrentLine), Print(truncate_to_width(title, columns).bold()))?;
+    for row in 0..height() {
+        queue!(out, MoveTo(0, row as u16 + 1), Clear(ClearType::CurrentLine))?;
+        match lines.get(top + row) {
+            Some(line) if Some(top + row) == selected => {
+                let line = format!("{line:<columns$}");
+                queue!(out, Print(truncate_to_width(&line, columns).reverse()))?
+            }
+            Some(line) => queue!(out, Print(truncate_to_width(line, columns)))?,
+            None => queue!(out, Print("~".dark_grey()))?,
+        }
+    }
+    let status = truncate_to_width(status, columns);
+    queue!(out, MoveTo(0, height() as u16 + 1), Clear(ClearType::CurrentLine), Print(status.reverse()))?;
+    out.flush()
+}
+
+fn read_key() -> io::Result<KeyEvent> {
+    loop {
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Release {
+                return Ok(key);
+            }
+        }
+    }
+}
+
+fn is_ctrl(key: &KeyEvent, c: char) -> bool {
+    key.code == KeyCode::Char(c) && key.modifiers.contains(KeyModifiers::CONTROL)
+}
+
+/// Reads a line of text in the status line, starting with `initial`. `None` if it
+/// was cancelled.
+fn read_text(out: &mut impl Write, label: &str, initial: &str) -> io::Result<Option<String>> {
+    let mut text: Vec<char> = initial.chars().collect();
+    let mut cursor = text.len();
+    let row = height() as u16 + 1;
+    let result = loop {
+        let before: String = text[..cursor].iter().collect();
+        let line: String = text.iter().collect();
+        let column = label.width() + before.width();
+        queue!(out, MoveTo(0, row), Clear(ClearType::CurrentLine), Print(format!("{label}{line}")))?;
+        queue!(out, MoveTo(column as u16, row), Show)?;
+        out.flush()?;
+        let key = read_key()?;
+        if is_ctrl(&key, 'c') {
+            break None;
+        }
+        match key.code {
+            KeyCode::Enter => break Some(text.iter().collect()),
+            KeyCode::Esc => break None,
+            KeyCode::Backspace if cursor > 0 => {
+                cursor -= 1;
+                text.remove(cursor);
+            }
+            KeyCode::Delete if cursor < text.len() => {
+                text.remove(cursor);
+            }
+            KeyCode::Left => cursor = cursor.saturating_sub(1),
+            KeyCode::Right => cursor = (cursor + 1).min(text.len()),
+            KeyCode::Home => cursor = 0,
+            KeyCode::End => cursor = text.len(),
+            KeyCode::Char(c) => {
+                text.insert(cursor, c);
+                cursor += 1;
+            }
+            _ => {}
+        }
+    };
+    queue!(out, Hide)?;
+    Ok(result)
+}
+
+/// What the next press of a key does when it is pressed again, after a warning.
+#[derive(Clone, Copy, PartialEq)]
+enum Armed {
+    /// Quit without saving the changes.
+    Quit,
+    /// Save over the changes made to the file by someone else.
+    Overwrite,
+}
+
+struct ConfigEditor {
+    path: PathBuf,
+    /// The configs as they were read or last saved.
+    original: Value,
+    tree: Value,
+    fields: Vec<Field>,
+    /// The selected field.
+    selected: usize,
+    /// The first line on the screen.
+    top: usize,
+    /// Shown instead of the help in the status line until the next key.
+    message: Option<String>,
+    armed: Option<Armed>,
+}
+
+impl ConfigEditor {
+    fn value(&self, field: &Field) -> &Value {
+        self.tree.get(&field.section).and_then(|section| section.get(&field.key)).unwrap_or(&Value::Null)
+    }
+
+    fn value_mut(&mut self, index: usize) -> Option<&mut Value> {
+        let field = &self.fields[index];
+        let section = self.tree.as_mapping_mut()?.entry(Value::from(field.section.as_str())).or_insert(Value::Mapping(Mapping::new()));
+        Some(section.as_mapping_mut()?.entry(Value::from(field.key.as_str())).or_insert(Value::Null))
+    }
+
+    fn set(&mut self, index: usize, value: Value) {
+        if let Some(slot) = self.value_mut(index) {
+            *slot = value;
+        }
+    }
+
+    fn changed(&self) -> bool {
+        self.tree != self.original
+    }
+
+    fn shown_value(&self, field: &Field) -> String {
+        let value = self.value(field);
+        match (&field.kind, value) {
+            (Kind::Toggle, value) => String::from(if value.as_bool() == Some(true) { "[x]" } else { "[ ]" }),
+            (Kind::Choice(_), value) => format!("< {} >", text_of(value)),
+            (Kind::OptionalText, Value::Null) => String::from("(none)"),
+            (Kind::List, Value::Sequence(list)) if list.is_empty() => String::from("(empty)"),
+            (Kind::Map, Value::Mapping(map)) if map.is_empty() => String::from("(empty)"),
+            (Kind::List, Value::Sequence(list)) => list.iter().map(text_of).collect::<Vec<_>>().join(", "),
+            (Kind::Map, Value::Mapping(map)) => {
+                let items: Vec<String> = sorted_keys(map).into_iter().map(|key| format!("{key}={}", text_of(&map[key.as_str()]))).collect();
+                items.join(", ")
+            }
+            (_, value) => text_of(value),
+        }
+    }
+
+    /// The lines of the sections and their fields, and the line of each field.
+    fn lines(&self) -> (Vec<String>, Vec<usize>) {
+        let width = self.fields.iter().map(|field| field.key.len()).max().unwrap_or(0);
+        let mut lines = vec![];
+        let mut rows = vec![];
+        let mut section = "";
+        for field in &self.fields {
+            if field.section != section {
+                section = &field.section;
+                lines.push(section.blue().bold().to_string());
+            }
+            rows.push(lines.len());
+            lines.push(format!("  {:<width$}  {}", field.key, self.shown_value(field)));
+        }
+        (lines, rows)
+    }
+
+    fn title(&self) -> String {
+        let changed = if self.changed() { " (changed)" } else { "" };
+        format!(" Config: {}{changed}", self.path.to_string_lossy())
+    }
+
+    /// Saves the configs if they are valid. Unless `force` is set, it fails when the
+    /// file changed since it was read, so the changes of others aren't lost.
+    fn save(&mut self, force: bool) -> Result<(), String> {
+        let configs = validate(&self.tree)?;
+        let original = &self.original;
+        setup::update_conf_at(&self.path, |current| {
+            let current_tree = serde_yaml::to_value(&*current).map_err(|err| err.to_string())?;
+            if !force && current_tree != *original {
+                return Err(String::from(CHANGED));
+            }
+            *current = configs;
+            Ok(())
+        })?;
+        info!("config_editor::ConfigEditor::save(): Saved {}", self.path.to_string_lossy());
+        self.original = self.tree.clone();
+        Ok(())
+    }
+
+    /// Changes the selected field: toggles a bool, moves to the next choice, reads
+    /// a text or opens the editor of a list or a map.
+    fn change(&mut self, out: &mut impl Write, step: isize) -> io::Result<()> {
+        let index = self.selected;
+        let field = &self.fields[index];
+        let (kind, path, current) = (field.kind, field.path(), self.value(field).clone());
+        match kind {
+            Kind::Toggle => self.set(index, Value::Bool(current.as_bool() != Some(true))),
+            Kind::Choice(choices) => {
+                let current = text_of(&current);
+                let position = choices.iter().position(|choice| choice.eq_ignore_ascii_case(&current)).unwrap_or(0);
+                let next = (position as isize + step).rem_euclid(choices.len() as isize) as usize;
+                self.set(index, Value::from(choices[next]));
+            }
+            Kind::Text | Kind::OptionalText => {
+                if let Some(text) = read_text(out, &format!(" {path}: "), &text_of(&current))? {
+                    let value =
+                        if matches!(kind, Kind::OptionalText) && text.is_empty() { Value::Null } else { Value::from(text) };
+                    self.set(index, value);
+                }
+            }
+            Kind::List | Kind::Map => {
+                let title = format!(" {path}");
+                let value = match (kind, current) {
+                    (_, value @ (Value::Sequence(_) | Value::Mapping(_))) => value,
+                    (Kind::Map, _) => Value::Mapping(Mapping::new()),
+                    _ => Value::Sequence(vec![]),
+                };
+                let mut collection = Collection { title, value, selected: 0, top: 0, message: None };
+                collection.run(out)?;
+                self.set(index, collection.value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Puts the default value back in the selected field.
+    fn reset(&mut self) {
+        let field = &self.fields[self.selected];
+        let default = serde_yaml::to_value(YamlConfiguration::default()).ok();
+        let value = default
+            .as_ref()
+            .and_then(|tree| tree.get(&field.section))
+            .and_then(|section| section.get(&field.key))
+            .cloned()
+            .unwrap_or(Value::Null);
+        self.set(self.selected, value);
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            let (lines, rows) = self.lines();
+            let row = rows.get(self.selected).copied().unwrap_or(0);
+            // The header of the first section stays on the screen
+            self.top = scrolled(self.top, if self.selected == 0 { 0 } else { row });
+            let status = self.message.take().unwrap_or_else(|| {
+                String::from(" enter/space: change  ←/→: choices  d: default  s: save  q: quit")
+            });
+            draw(out, &self.title(), &lines, self.top, Some(row), &status)?;
+
+            let key = read_key()?;
+            let armed = self.armed.take();
+            if is_ctrl(&key, 'c') {
+                if self.quit_key(armed) {
+                    return Ok(());
+                }
+                continue;
+            }
+            if is_ctrl(&key, 's') {
+                self.save_key(armed);
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc if self.quit_key(armed) => return Ok(()),
+                KeyCode::Char('s') => self.save_key(armed),
+                KeyCode::Char('j') | KeyCode::Down | KeyCode::Tab => {
+                    self.selected = (self.selected + 1).min(self.fields.len().saturating_sub(1))
+                }
+                KeyCode::Char('k') | KeyCode::Up | KeyCode::BackTab => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Char('g') | KeyCode::Home => self.selected = 0,
+                KeyCode::Char('G') | KeyCode::End => self.selected = self.fields.len().saturating_sub(1),
+                KeyCode::Enter | KeyCode::Char(' ') | KeyCode::Right | KeyCode::Char('l') => self.change(out, 1)?,
+                KeyCode::Left | KeyCode::Char('h') => {
+                    if matches!(self.fields[self.selected].kind, Kind::Choice(_)) {
+                        self.change(out, -1)?;
+                    }
+                }
+                KeyCode::Char('d') => self.reset(),
+                _ => {}
+            }
+        }
+    }
+
+    /// Whether the editor can be closed. The first press with unsaved changes only
+    /// warns about them, ctrl-c too.
+    fn quit_key(&mut self, armed: Option<Armed>) -> bool {
+        if !self.changed() || armed == Some(Armed::Quit) {
+            return true;
+        }
+        self.message = Some(String::from(" The changes aren't saved, press q or ctrl-c again to quit without saving them"));
+        self.armed = Some(Armed::Quit);
+        false
+    }
+
+    fn save_key(&mut self, armed: Option<Armed>) {
+        self.message = Some(match self.save(armed == Some(Armed::Overwrite)) {
+            Ok(()) => String::from(" Saved"),
+            Err(err) if err == CHANGED => {
+                self.armed = Some(Armed::Overwrite);
+                format!(" {CHANGED}, press s again to save over it")
+            }
+            Err(err) => format!(" Not saved: {err}"),
+        });
+    }
+}
+
+/// The editor of a list or a map: its items can be added, changed and removed, and
+/// the items of a list can be moved.
+struct Collection {
+    title: String,
+    value: Value,
+    selected: usize,
+    top: usize,
+    message: Option<String>,
+}
+
+impl Collection {
+    fn len(&self) -> usize {
+        match &self.value {
+            Value::Sequence(list) => list.len(),
+            Value::Mapping(map) => map.len(),
+            _ => 0,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        match &self.value {
+            Value::Sequence(list) => list.iter().map(|item| format!("  - {}", text_of(item))).collect(),
+            Value::Mapping(map) => {
+                let keys = sorted_keys(map);
+                let width = keys.iter().map(|key| key.width()).max().unwrap_or(0);
+                keys.iter().map(|key| format!("  {key:<width$} = {}", text_of(&map[key.as_str()]))).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// The key of the selected item of a map.
+    fn selected_key(&self) -> Option<String> {
+        match &self.value {
+            Value::Mapping(map) => sorted_keys(map).into_iter().nth(self.selected),
+            _ => None,
+        }
+    }
+
+    fn select_key(&mut self, key: &str) {
+        if let Value::Mapping(map) = &self.value {
+            self.selected = sorted_keys(map).iter().position(|found| found == key).unwrap_or(0);
+        }
+    }
+
+    fn add(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if self.value.is_sequence() {
+            if let Some(item) = read_text(out, " New item: ", "")? {
+                if let Value::Sequence(list) = &mut self.value {
+                    list.push(Value::from(item));
+                    self.selected = list.len() - 1;
+                }
+            }
+            return Ok(());
+        }
+        let Some(key) = read_text(out, " New key: ", "")? else {
+            return Ok(());
+        };
+        let existing = self.value.get(key.as_str()).map(text_of).unwrap_or_default();
+        let Some(value) = read_text(out, &format!(" {key} = "), &existing)? else {
+            return Ok(());
+        };
+        if let Value::Mapping(map) = &mut self.value {
+            map.insert(Value::from(key.as_str()), Value::from(value));
+        }
+        self.select_key(&key);
+        Ok(())
+    }
+
+    fn change(&mut self, out: &mut impl Write) -> io::Result<()> {
+        if let Some(key) = self.selected_key() {
+            let current = self.value.get(key.as_str()).map(text_of).unwrap_or_default();
+            if let Some(value) = read_text(out, &format!(" {key} = "), &current)? {
+                if let Value::Mapping(map) = &mut self.value {
+                    map.insert(Value::from(key.as_str()), Value::from(value));
+                }
+            }
+            return Ok(());
+        }
+        let selected = self.selected;
+        let Some(current) = self.value.get(selected).map(text_of) else {
+            return Ok(());
+        };
+        if let Some(item) = read_text(out, " Item: ", &current)? {
+            if let Value::Sequence(list) = &mut self.value {
+                list[selected] = Value::from(item);
+            }
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let Some(key) = self.selected_key() else {
+            return Ok(());
+        };
+        let Some(renamed) = read_text(out, " Rename to: ", &key)? else {
+            return Ok(());
+        };
+        if let Value::Mapping(map) = &mut self.value {
+            if renamed != key && map.contains_key(renamed.as_str()) {
+                self.message = Some(format!(" There is already a `{renamed}`"));
+                return Ok(());
+            }
+            if let Some(value) = map.remove(key.as_str()) {
+                map.insert(Value::from(renamed.as_str()), value);
+            }
+        }
+        self.select_key(&renamed);
+        Ok(())
+    }
+
+    fn remove(&mut self) {
+        let key = self.selected_key();
+        match (&mut self.value, key) {
+            (Value::Mapping(map), Some(key)) => {
+                map.remove(key.as_str());
+            }
+            (Value::Sequence(list), _) if self.selected < list.len() => {
+                list.remove(self.selected);
+            }
+            _ => {}
+        }
+        self.selected = self.selected.min(self.len().saturating_sub(1));
+    }
+
+    /// Moves the selected item of a list up or down.
+    fn shift(&mut self, step: isize) {
+        if let Value::Sequence(list) = &mut self.value {
+            let Some(target) = self.selected.checked_add_signed(step).filter(|target| *target < list.len()) else {
+                return;
+            };
+            list.swap(self.selected, target);
+            self.selected = target;
+        }
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        let is_map = self.value.is_mapping();
+        loop {
+            let lines = self.lines();
+            self.top = scrolled(self.top, self.selected);
+            let help = if is_map {
+                " a: add  enter: change  r: rename  d: remove  q: back"
+            } else {
+                " a: add  enter: change  J/K: move  d: remove  q: back"
+            };
+            let status = self.message.take().unwrap_or_else(|| String::from(help));
+            let selected = Some(self.selected).filter(|_| !lines.is_empty());
+            draw(out, &self.title, &lines, self.top, selected, &status)?;
+
+            let key = read_key()?;
+            if is_ctrl(&key, 'c') {
+                return Ok(());
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc | KeyCode::Left | KeyCode::Char('h') => return Ok(()),
+                KeyCode::Char('j') | KeyCode::Down => self.selected = (self.selected + 1).min(self.len().saturating_sub(1)),
+                KeyCode::Char('k') | KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                KeyCode::Char('a') => self.add(out)?,
+                KeyCode::Enter | KeyCode::Char('e') if self.len() > 0 => self.change(out)?,
+                KeyCode::Char('r') if is_map => self.rename(out)?,
+                KeyCode::Char('d') | KeyCode::Delete => self.remove(),
+                KeyCode::Char('J') => self.shift(1),
+                KeyCode::Char('K') => self.shift(-1),
+                _ => {}
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    fn editor() -> ConfigEditor {
+        let tree = serde_yaml::to_value(YamlConfiguration::default()).unwrap();
+        ConfigEditor {
+            path: PathBuf::from("preferences.yml"),
+            original: tree.clone(),
+            tree,
+            fields: fields(),
+            selected: 0,
+            top: 0,
+            message: None,
+            armed: None,
+        }
+    }
+
+    fn tree_of(change: impl FnOnce(&mut YamlConfiguration)) -> Value {
+        let mut configs = YamlConfiguration::default();
+        change(&mut configs);
+        serde_yaml::to_value(configs).unwrap()
+    }
+
+    /// An editor of the config file `text`, written in a directory of its own.
+    fn editor_of(name: &str, text: &str) -> (PathBuf, ConfigEditor) {
+        let dir = scratch_dir(&format!("config-editor-{name}"));
+        let path = dir.join("preferences.yml");
+        std::fs::write(&path, text).unwrap();
+        let tree = serde_yaml::to_value(setup::parse_conf(text, "preferences.yml").0).unwrap();
+        let editor = ConfigEditor { path: path.clone(), original: tree.clone(), tree, ..editor() };
+        (dir, editor)
+    }
+
+    fn field(editor: &ConfigEditor, key: &str) -> usize {
+        editor.fields.iter().position(|field| field.key == key).unwrap()
+    }
+
+    #[test]
+    fn validates_the_edited_configs() {
+        assert!(validate(&tree_of(|_| ())).is_ok());
+        let valid = tree_of(|configs| {
+            configs.terminal_config.alias.insert(String::from("ll"), String::from("ls -l"));
+            configs.theme.colors.insert(String::from("*.log"), String::from("bold bright_red"));
+        });
+        assert!(validate(&valid).is_ok());
+
+        let alias = |name: &str, command: &str| {
+            tree_of(|configs| drop(configs.terminal_config.alias.insert(name.to_string(), command.to_string())))
+        };
+        let cases = [
+            (alias("", "ls"), "terminal_config.alias: `` can't be the name of an alias"),
+            (alias("l l", "ls"), "terminal_config.alias: `l l` can't be the name of an alias"),
+            (alias("ll", "  "), "terminal_config.alias.ll: the command is empty"),
+            (tree_of(|configs| configs.scripts_config.files.push(PathBuf::new())), "scripts_config.files: a file is empty"),
+            (
+                tree_of(|configs| drop(configs.theme.colors.insert(String::from("*.log"), String::from("bold pink")))),
+                "theme.colors.*.log: `bold pink` is not a color",
+            ),
+        ];
+        for (tree, message) in cases {
+            assert_eq!(validate(&tree).unwrap_err(), message);
+        }
+    }
+
+    #[test]
+    fn refuses_values_of_the_wrong_type() {
+        let mut tree = tree_of(|_| ());
+        tree["terminal_config"]["auto_cd"] = Value::from("maybe");
+        assert!(validate(&tree).is_err());
+    }
+
+    #[test]
+    fn saves_the_changes_and_keeps_the_comments() {
+        let text = "version: 1\n# The prompt\nterminal_config:\n  prompt: '$ '\n";
+        let (dir, mut editor) = editor_of("save", text);
+        let auto_cd = field(&editor, "auto_cd");
+        editor.set(auto_cd, Value::Bool(true));
+
+        editor.save(false).unwrap();
+        assert!(!editor.changed());
+        let saved = std::fs::read_to_string(dir.join("preferences.yml")).unwrap();
+        assert!(saved.contains("# The prompt\n"), "{saved}");
+        assert!(setup::parse_conf(&saved, "preferences.yml").0.terminal_config.auto_cd, "{saved}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn doesnt_save_invalid_configs() {
+        let text = "version: 1\n";
+        let (dir, mut editor) = editor_of("invalid", text);
+        let alias = field(&editor, "alias");
+        let mut aliases = Mapping::new();
+        aliases.insert(Value::from("ll"), Value::from(""));
+        editor.set(alias, Value::Mapping(aliases));
+
+        assert_eq!(editor.save(false).unwrap_err(), "terminal_config.alias.ll: the command is empty");
+        assert!(editor.changed());
+        assert_eq!(std::fs::read_to_string(dir.join("preferences.yml")).unwrap(), text);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn doesnt_overwrite_a_file_changed_by_someone_else() {
+        let (dir, mut editor) = editor_of("changed", "version: 1\n");
+        let path = dir.join("preferences.yml");
+        let auto_cd = field(&editor, "auto_cd");
+        editor.set(auto_cd, Value::Bool(true));
+        let theirs = "version: 1\nterminal_config:\n  prompt: '% '\n";
+        std::fs::write(&path, theirs).unwrap();
+
+        assert_eq!(editor.save(false).unwrap_err(), CHANGED);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), theirs);
+
+        // Saving over it is a choice of the user
+        editor.save(true).unwrap();
+        let configs = setup::parse_conf(&std::fs::read_to_string(&path).unwrap(), "preferences.yml").0;
+        assert!(configs.terminal_config.auto_cd);
+        assert_eq!(configs.terminal_config.prompt, "{}");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn quits_at_once_without_changes() {
+        let mut editor = editor();
+        assert!(editor.quit_key(None));
+        assert!(editor.message.is_none());
+    }
+
+    #[test]
+    fn warns_about_the_unsaved_changes_before_quitting() {
+        let mut editor = editor();
+        let index = editor.fields.iter().position(|field| field.key == "auto_cd").unwrap();
+        editor.set(index, Value::Bool(true));
+
+        assert!(!editor.quit_key(None));
+        assert!(editor.message.as_deref().is_some_and(|message| message.contains("aren't saved")));
+        let armed = editor.armed.take();
+        assert!(armed == Some(Armed::Quit));
+        assert!(editor.quit_key(armed));
+        // Another key in between disarms it
+        assert!(!editor.quit_key(None));
+    }
+}
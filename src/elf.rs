@@ -0,0 +1,228 @@
+/// What `read` shows about an ELF file: the headers that say what it runs on and what
+/// loads it.
+#[derive(Debug)]
+pub struct ElfInfo {
+    pub bits: u8,
+    pub little_endian: bool,
+    pub kind: &'static str,
+    pub architecture: String,
+    /// The program interpreter, the dynamic loader of the dynamically linked files.
+    pub interpreter: Option<String>,
+}
+
+const PT_INTERP: u32 = 3;
+
+fn machine_name(machine: u16) -> String {
+    match machine {
+        0x02 => "SPARC",
+        0x03 => "x86",
+        0x08 => "MIPS",
+        0x14 => "PowerPC",
+        0x15 => "PowerPC64",
+        0x16 => "S390",
+        0x28 => "ARM",
+        0x2b => "SPARC V9",
+        0x32 => "IA-64",
+        0x3e => "x86-64",
+        0xb7 => "AArch64",
+        0xf3 => "RISC-V",
+        0xf7 => "BPF",
+        0x102 => "LoongArch",
+        _ => return format!("unknown (machine {machine:#x})"),
+    }
+    .to_string()
+}
+
+/// Reads the integers of the file with its byte order.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    little_endian: bool,
+}
+
+impl Reader<'_> {
+    fn bytes<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.bytes.get(offset..offset.checked_add(N)?)?.try_into().ok()
+    }
+
+    fn u16(&self, offset: usize) -> Option<u16> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    }
+
+    fn u32(&self, offset: usize) -> Option<u32> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    }
+
+    fn u64(&self, offset: usize) -> Option<u64> {
+        let bytes = self.bytes(offset)?;
+        Some(if self.little_endian { u64::from_le_bytes(bytes) } else { u64::from_be_bytes(bytes) })
+    }
+
+    /// A field that is 32 bits long in 32 bit files and 64 in 64 bit ones.
+    fn word(&self, offset: usize, bits: u8) -> Option<usize> {
+        match bits {
+            32 => self.u32(offset).map(|word| word as usize),
+            _ => self.u64(offset).and_then(|word| usize::try_from(word).ok()),
+        }
+    }
+}
+
+/// Parses the ELF header of `bytes`, the beginning of a file. `None` if it isn't an
+/// ELF file. The interpreter is only found if its program header and its path are
+/// in `bytes`.
+pub fn parse(bytes: &[u8]) -> Option<ElfInfo> {
+    if !bytes.starts_with(b"\x7fELF") {
+        return None;
+    }
+    let bits = match bytes.get(4)? {
+        1 => 32,
+        2 => 64,
+        _ => return None,
+    };
+    let little_endian = match bytes.get(5)? {
+        1 => true,
+        2 => false,
+        _ => return None,
+    };
+    let reader = Reader { bytes, little_endian };
+
+    // The offsets of the fields after e_entry depend on the size of the addresses
+    let (phoff, phentsize, phnum) = match bits {
+        32 => (reader.word(28, bits)?, reader.u16(42)?, reader.u16(44)?),
+        _ => (reader.word(32, bits)?, reader.u16(54)?, reader.u16(56)?),
+    };
+    let interpreter = (0..phnum as usize).find_map(|index| {
+        let header = phoff.checked_add(index.checked_mul(phentsize as usize)?)?;
+        if reader.u32(header)? != PT_INTERP {
+            return None;
+        }
+        let (offset, size) = match bits {
+            32 => (reader.word(header + 4, bits)?, reader.word(header + 16, bits)?),
+            _ => (reader.word(header + 8, bits)?, reader.word(header + 32, bits)?),
+        };
+        let path = bytes.get(offset..offset.checked_add(size)?)?;
+        let path = path.split(|byte| *byte == 0).next().unwrap_or(path);
+        Some(String::from_utf8_lossy(path).into_owned())
+    });
+
+    let kind = match reader.u16(16)? {
+        1 => "relocatable",
+        2 => "executable",
+        // Position independent executables are shared objects with an interpreter
+        3 if interpreter.is_some() => "position independent executable",
+        3 => "shared object",
+        4 => "core dump",
+        _ => "unknown type",
+    };
+    Some(ElfInfo { bits, little_endian, kind, architecture: machine_name(reader.u16(18)?), interpreter })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTERPRETER: &[u8] = b"/lib/ld.so\0";
+
+    /// Writes the integers of a test file with its byte order.
+    struct Writer {
+        bytes: Vec<u8>,
+        little_endian: bool,
+    }
+
+    impl Writer {
+        fn put(&mut self, offset: usize, value: u64, size: usize) {
+            let bytes = if self.little_endian { value.to_le_bytes() } else { value.to_be_bytes() };
+            let bytes = if self.little_endian { &bytes[..size] } else { &bytes[8 - size..] };
+            self.bytes[offset..offset + size].copy_from_slice(bytes);
+        }
+    }
+
+    /// An ELF file with one program header, the interpreter when `interpreter` is set.
+    fn file(bits: u8, little_endian: bool, kind: u16, machine: u16, interpreter: bool) -> Vec<u8> {
+        let (header_size, entry_size, word) = if bits == 32 { (52, 32, 4) } else { (64, 56, 8) };
+        let mut writer = Writer { bytes: vec![0; header_size + entry_size], little_endian };
+        writer.bytes[..4].copy_from_slice(b"\x7fELF");
+        writer.bytes[4] = if bits == 32 { 1 } else { 2 };
+        writer.bytes[5] = if little_endian { 1 } else { 2 };
+        writer.put(16, kind as u64, 2);
+        writer.put(18, machine as u64, 2);
+        let (phoff, phentsize, phnum) = if bits == 32 { (28, 42, 44) } else { (32, 54, 56) };
+        writer.put(phoff, header_size as u64, word);
+        writer.put(phentsize, entry_size as u64, 2);
+        writer.put(phnum, 1, 2);
+        if interpreter {
+            let (offset, size) = if bits == 32 { (4, 16) } else { (8, 32) };
+            writer.put(header_size, PT_INTERP as u64, 4);
+            writer.put(header_size + offset, writer.bytes.len() as u64, word);
+            writer.put(header_size + size, INTERPRETER.len() as u64, word);
+            writer.bytes.extend_from_slice(INTERPRETER);
+        }
+        writer.bytes
+    }
+
+    #[test]
+    fn reads_a_64_bit_little_endian_file() {
+        let info = parse(&file(64, true, 3, 0x3e, true)).unwrap();
+        assert_eq!((info.bits, info.little_endian), (64, true));
+        assert_eq!(info.kind, "position independent executable");
+        assert_eq!(info.architecture, "x86-64");
+        assert_eq!(info.interpreter.as_deref(), Some("/lib/ld.so"));
+    }
+
+    #[test]
+    fn reads_a_32_bit_big_endian_file() {
+        let info = parse(&file(32, false, 2, 0x08, true)).unwrap();
+        assert_eq!((info.bits, info.little_endian), (32, false));
+        assert_eq!(info.kind, "executable");
+        assert_eq!(info.architecture, "MIPS");
+        assert_eq!(info.interpreter.as_deref(), Some("/lib/ld.so"));
+    }
+
+    #[test]
+    fn a_shared_object_has_no_interpreter() {
+        let info = parse(&file(64, true, 3, 0xb7, false)).unwrap();
+        assert_eq!(info.kind, "shared object");
+        assert_eq!(info.architecture, "AArch64");
+        assert_eq!(info.interpreter, None);
+
+        let info = parse(&file(32, true, 1, 0x1234, false)).unwrap();
+        assert_eq!(info.kind, "relocatable");
+        assert_eq!(info.architecture, "unknown (machine 0x1234)");
+    }
+
+    #[test]
+    fn rejects_other_files() {
+        assert!(parse(b"").is_none());
+        assert!(parse(b"#!/bin/sh\necho hi\n").is_none());
+        let mut bad_magic = file(64, true, 2, 0x3e, false);
+        bad_magic[1] = b'X';
+        assert!(parse(&bad_magic).is_none());
+        let mut bad_class = file(64, true, 2, 0x3e, false);
+        bad_class[4] = 3;
+        assert!(parse(&bad_class).is_none());
+        let mut bad_order = file(64, true, 2, 0x3e, false);
+        bad_order[5] = 0;
+        assert!(parse(&bad_order).is_none());
+    }
+
+    #[test]
+    fn a_truncated_header_is_not_read() {
+        let whole = file(64, true, 2, 0x3e, true);
+        for length in [4, 5, 6, 20, 40, 57] {
+            assert!(parse(&whole[..length]).is_none(), "{length} bytes");
+        }
+        // A 32 bit header is shorter, its fields must not be read at the 64 bit offsets
+        let whole = file(32, true, 2, 0x03, false);
+        assert!(parse(&whole[..52]).is_some());
+        assert!(parse(&whole[..45]).is_none());
+    }
+
+    #[test]
+    fn the_interpreter_is_only_found_when_it_was_read() {
+        let whole = file(64, true, 3, 0x3e, true);
+        let info = parse(&whole[..whole.len() - 3]).unwrap();
+        assert_eq!(info.kind, "shared object");
+        assert_eq!(info.interpreter, None);
+    }
+}
@@ -0,0 +1,223 @@
+use log::{error, info};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::{CStr, CString, OsStr, OsString},
+    os::unix::ffi::OsStrExt,
+    path::PathBuf,
+};
+
+use crate::parser::{self, Word};
+
+/// Runs every word of a command line through the expansion stage before the
+/// command is dispatched. For now this only does tilde expansion. The words are
+/// not text anymore once a home directory that isn't UTF-8 is put in them.
+pub fn expand_words(words: Vec<Word>) -> Vec<OsString> {
+    let dirs = TildeDirs::from_env();
+    words.iter().map(|word| expand_word_in(word, &dirs)).collect()
+}
+
+/// The text of `word`, with the `~` at its start expanded unless it was quoted.
+pub fn expand_word(word: &Word) -> OsString {
+    expand_word_in(word, &TildeDirs::from_env())
+}
+
+fn expand_word_in(word: &Word, dirs: &TildeDirs) -> OsString {
+    if word.quoted_prefix {
+        OsString::from(&word.text)
+    } else {
+        expand_tilde_in(&word.text, dirs)
+    }
+}
+
+/// Replaces the first word with its alias from the config. The replacement is expanded
+/// again, unless it starts with an alias that was already used (so `ls: ls -a` works).
+pub fn expand_alias(words: Vec<Word>, aliases: &HashMap<String, String>) -> Vec<Word> {
+    let mut words = words;
+    let mut used = HashSet::new();
+
+    while let Some(first) = words.first() {
+        let Some(replacement) = aliases.get(&first.text) else {
+            break;
+        };
+        if !used.insert(first.text.clone()) {
+            break;
+        }
+        // The words keep their quoting, so a quoted `~` of the alias isn't expanded
+        match parser::split_words(replacement) {
+            Ok(replacement_words) => {
+                info!("expand::expand_alias(): Expanding alias '{}'", first.text);
+                words.splice(0..1, replacement_words);
+            }
+            Err(err) => {
+                error!("expand::expand_alias(): Cannot parse the alias '{}': {err}", first.text);
+                break;
+            }
+        }
+    }
+    words
+}
+
+/// The directories `~`, `~+` and `~-` stand for.
+struct TildeDirs {
+    home: Option<PathBuf>,
+    pwd: Option<PathBuf>,
+    oldpwd: Option<PathBuf>,
+}
+
+impl TildeDirs {
+    /// HOME (or the home of the user if it isn't set), PWD and OLDPWD.
+    fn from_env() -> Self {
+        TildeDirs {
+            home: env::var_os("HOME").map(PathBuf::from).or_else(|| home_of_user(None)),
+            pwd: env::var_os("PWD").map(PathBuf::from),
+            oldpwd: env::var_os("OLDPWD").map(PathBuf::from),
+        }
+    }
+}
+
+/// Expands a leading `~`, `~user`, `~+` (PWD) or `~-` (OLDPWD). Words that can't be
+/// expanded (unknown user, unset variable) are returned unchanged. The directory is
+/// put in as it is, even if it isn't UTF-8.
+pub fn expand_tilde(word: &str) -> OsString {
+    expand_tilde_in(word, &TildeDirs::from_env())
+}
+
+fn expand_tilde_in(word: &str, dirs: &TildeDirs) -> OsString {
+    let Some(rest) = word.strip_prefix('~') else {
+        return OsString::from(word);
+    };
+    let (prefix, suffix) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, ""),
+    };
+
+    let expanded = match prefix {
+        "" => dirs.home.clone(),
+        "+" => dirs.pwd.clone(),
+        "-" => dirs.oldpwd.clone(),
+        user => home_of_user(Some(user)),
+    };
+
+    match expanded {
+        Some(path) => {
+            info!("expand::expand_tilde(): Expanded '~{prefix}' to {}", path.to_string_lossy());
+            let mut expanded = path.into_os_string();
+            expanded.push(suffix);
+            expanded
+        }
+        None => OsString::from(word),
+    }
+}
+
+/// Looks up the home directory of `user` in the password database. `None` means the
+/// user running the shell.
+fn home_of_user(user: Option<&str>) -> Option<PathBuf> {
+    let passwd = match user {
+        Some(name) => {
+            let name = CString::new(name).ok()?;
+            unsafe { libc::getpwnam(name.as_ptr()) }
+        }
+        None => unsafe { libc::getpwuid(libc::getuid()) },
+    };
+    if passwd.is_null() {
+        return None;
+    }
+    let dir = unsafe { CStr::from_ptr((*passwd).pw_dir) };
+    Some(PathBuf::from(OsStr::from_bytes(dir.to_bytes())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(texts: &[&str]) -> Vec<Word> {
+        texts.iter().map(|text| Word { text: text.to_string(), quoted_prefix: false }).collect()
+    }
+
+    fn texts(words: Vec<Word>) -> Vec<String> {
+        words.into_iter().map(|word| word.text).collect()
+    }
+
+    #[test]
+    fn expand_tilde_uses_the_variables() {
+        let mut dirs = TildeDirs {
+            home: Some(PathBuf::from(OsStr::from_bytes(b"/home/caf\xe9"))),
+            pwd: Some(PathBuf::from("/work")),
+            oldpwd: Some(PathBuf::from("/old")),
+        };
+        let cases: &[(&str, &[u8])] = &[
+            ("~", b"/home/caf\xe9"),
+            ("~/src/a.rs", b"/home/caf\xe9/src/a.rs"),
+            ("~+", b"/work"),
+            ("~+/x", b"/work/x"),
+            ("~-", b"/old"),
+            ("a~", b"a~"),
+            ("/~", b"/~"),
+            ("~no-such-user-here/x", b"~no-such-user-here/x"),
+        ];
+        for (word, expanded) in cases {
+            assert_eq!(expand_tilde_in(word, &dirs).as_bytes(), *expanded, "{word}");
+        }
+
+        let quoted = Word { text: String::from("~/x"), quoted_prefix: true };
+        assert_eq!(expand_word_in(&quoted, &dirs), OsString::from("~/x"));
+        let unquoted = Word { text: String::from("~/x"), quoted_prefix: false };
+        assert_eq!(expand_word_in(&unquoted, &dirs).as_bytes(), b"/home/caf\xe9/x");
+
+        dirs.oldpwd = None;
+        assert_eq!(expand_tilde_in("~-", &dirs), OsString::from("~-"));
+    }
+
+    #[test]
+    fn expand_tilde_finds_the_home_of_a_user() {
+        let Some(home) = home_of_user(None) else {
+            return;
+        };
+        let user = unsafe { CStr::from_ptr((*libc::getpwuid(libc::getuid())).pw_name) };
+        let user = user.to_str().unwrap();
+        let mut expected = home.into_os_string();
+        expected.push("/x");
+        assert_eq!(expand_tilde(&format!("~{user}/x")), expected);
+    }
+
+    #[test]
+    fn expand_alias_replaces_the_first_word() {
+        let aliases: HashMap<String, String> = [
+            ("ll", "ls -l"),
+            ("ls", "ls --color"),
+            ("la", "ll -a"),
+            ("say", "echo 'hello world'"),
+            ("loop", "again"),
+            ("again", "loop x"),
+            ("broken", "echo 'unclosed"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+
+        let cases: &[(&[&str], &[&str])] = &[
+            (&["ll", "/tmp"], &["ls", "--color", "-l", "/tmp"]),
+            (&["la"], &["ls", "--color", "-l", "-a"]),
+            (&["say", "ll"], &["echo", "hello world", "ll"]),
+            (&["loop"], &["loop", "x"]),
+            (&["broken", "x"], &["broken", "x"]),
+            (&["cat", "ll"], &["cat", "ll"]),
+            (&[], &[]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(texts(expand_alias(words(line), &aliases)), *expected, "{line:?}");
+        }
+    }
+
+    #[test]
+    fn a_quoted_tilde_of_an_alias_isnt_expanded() {
+        let aliases = HashMap::from([(String::from("t"), String::from("echo \"~\" ~/x"))]);
+        let dirs = TildeDirs { home: Some(PathBuf::from("/home/me")), pwd: None, oldpwd: None };
+        let expanded: Vec<OsString> = expand_alias(words(&["t", "~"]), &aliases)
+            .iter()
+            .map(|word| expand_word_in(word, &dirs))
+            .collect();
+        assert_eq!(expanded, ["echo", "~", "/home/me/x", "/home/me"]);
+    }
+}
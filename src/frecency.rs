@@ -0,0 +1,351 @@
+use chrono::Utc;
+use directories::ProjectDirs;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, create_dir_all, File, OpenOptions};
+use std::io::{self, Read};
+use std::os::fd::AsRawFd;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+/// Once the ranks add up to more than this, every rank is aged so old entries fade out.
+const MAX_TOTAL_RANK: f64 = 9000.0;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FrecencyEntry {
+    pub path: PathBuf,
+    pub rank: f64,
+    pub last_access: i64,
+}
+
+impl FrecencyEntry {
+    /// Combines how often (`rank`) and how recently (`last_access`) the directory was visited.
+    pub fn score(&self, now: i64) -> f64 {
+        let age = now - self.last_access;
+        if age < 3600 {
+            self.rank * 4.0
+        } else if age < 86400 {
+            self.rank * 2.0
+        } else if age < 604800 {
+            self.rank / 2.0
+        } else {
+            self.rank / 4.0
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct FrecencyDatabase {
+    pub entries: Vec<FrecencyEntry>,
+}
+
+fn database_path() -> Option<PathBuf> {
+    let base_dir = ProjectDirs::from("", "", "yarp")?;
+    let mut path = base_dir.data_dir().to_path_buf();
+    path.push("frecency.yml");
+    Some(path)
+}
+
+impl FrecencyDatabase {
+    pub fn load() -> Self {
+        let Some(path) = database_path() else {
+            error!("frecency::FrecencyDatabase::load(): Base dir object is null");
+            return Default::default();
+        };
+        let mut buffer = String::new();
+        match File::open(&path) {
+            Ok(mut file) => {
+                if let Err(err) = file.read_to_string(&mut buffer) {
+                    error!("frecency::FrecencyDatabase::load(): Cannot read the database: {err}");
+                    return Default::default();
+                }
+            }
+            // Nothing has been visited yet
+            Err(_) => return Default::default(),
+        }
+        serde_yaml::from_str(&buffer).unwrap_or_else(|err| {
+            error!("frecency::FrecencyDatabase::load(): Cannot parse the database, starting a new one");
+            error!("frecency::FrecencyDatabase::load(): {err}");
+            Default::default()
+        })
+    }
+
+    /// Writes the database to a temporary file that is renamed over the old one, so
+    /// other shells never read it half written. The caller holds the lock.
+    ///
+    /// The file isn't synced: losing the last visits in a crash is cheaper than a sync
+    /// on every `cd`.
+    pub fn save(&self) -> Result<(), String> {
+        let path = database_path().ok_or_else(|| String::from("Base dir object is null"))?;
+        let buffer = serde_yaml::to_string(self).map_err(|err| format!("Cannot serialize the database: {err}"))?;
+        let temp = path.with_file_name(format!(".frecency.yml.{}.tmp", std::process::id()));
+        let written = fs::write(&temp, buffer).and_then(|_| fs::rename(&temp, &path));
+        if let Err(err) = written {
+            let _ = fs::remove_file(&temp);
+            return Err(format!("Cannot write the database: {err}"));
+        }
+        Ok(())
+    }
+
+    pub fn visit(&mut self, path: &Path) {
+        let now = Utc::now().timestamp();
+        match self.entries.iter_mut().find(|entry| entry.path == path) {
+            Some(entry) => {
+                entry.rank += 1.0;
+                entry.last_access = now;
+            }
+            None => self.entries.push(FrecencyEntry {
+                path: path.to_path_buf(),
+                rank: 1.0,
+                last_access: now,
+            }),
+        }
+
+        let total: f64 = self.entries.iter().map(|entry| entry.rank).sum();
+        if total > MAX_TOTAL_RANK {
+            info!("frecency::FrecencyDatabase::visit(): Aging the database");
+            for entry in self.entries.iter_mut() {
+                entry.rank *= 0.99;
+            }
+            self.entries.retain(|entry| entry.rank >= 1.0);
+        }
+    }
+
+    /// Directories whose path contains every fragment in order, best score first.
+    /// Fragments are matched case-insensitively unless one of them has an uppercase letter.
+    pub fn matches(&self, fragments: &[String]) -> Vec<(f64, PathBuf)> {
+        let now = Utc::now().timestamp();
+        let case_sensitive = fragments
+            .iter()
+            .any(|fragment| fragment.chars().any(char::is_uppercase));
+
+        let mut found: Vec<(f64, PathBuf)> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.path.is_dir())
+            .filter(|entry| {
+                let mut haystack = entry.path.to_string_lossy().to_string();
+                if !case_sensitive {
+                    haystack = haystack.to_lowercase();
+                }
+                let mut position = 0;
+                for fragment in fragments {
+                    let fragment = if case_sensitive {
+                        fragment.clone()
+                    } else {
+                        fragment.to_lowercase()
+                    };
+                    match haystack[position..].find(&fragment) {
+                        Some(index) => position += index + fragment.len(),
+                        None => return false,
+                    }
+                }
+                true
+            })
+            .map(|entry| {
+                let mut score = entry.score(now);
+                // Prefer directories whose own name matches the last fragment
+                if let (Some(last), Some(name)) = (fragments.last(), entry.path.file_name()) {
+                    let name = name.to_string_lossy();
+                    let matched = if case_sensitive {
+                        name.contains(last.as_str())
+                    } else {
+                        name.to_lowercase().contains(&last.to_lowercase())
+                    };
+                    if matched {
+                        score *= 2.0;
+                    }
+                }
+                (score, entry.path.clone())
+            })
+            .collect();
+
+        found.sort_by(|a, b| b.0.total_cmp(&a.0));
+        found
+    }
+}
+
+/// Locks the database until the returned file is dropped, so the visits recorded by
+/// other shells at the same time are not lost.
+fn lock_database() -> Result<File, String> {
+    let path = database_path().ok_or_else(|| String::from("Base dir object is null"))?;
+    if let Some(parent) = path.parent() {
+        create_dir_all(parent).map_err(|err| format!("Cannot create the data directory: {err}"))?;
+    }
+    let lock_path = path.with_file_name("frecency.yml.lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|err| format!("Cannot open {}: {err}", lock_path.to_string_lossy()))?;
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(format!("Cannot lock {}: {}", lock_path.to_string_lossy(), io::Error::last_os_error()));
+    }
+    Ok(lock)
+}
+
+/// Tells the versions of the database file apart. Every save renames a new file over
+/// the old one, so the inode changes along with the time.
+#[derive(PartialEq, Debug)]
+struct FileStamp {
+    ino: u64,
+    size: u64,
+    modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    fn of(path: &Path) -> Option<FileStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        Some(FileStamp {
+            ino: metadata.ino(),
+            size: metadata.size(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// The database as this shell last wrote it. It is only read again from the file when
+/// another shell replaced it since then.
+static LAST_SAVED: Mutex<Option<(FileStamp, FrecencyDatabase)>> = Mutex::new(None);
+
+/// Adds a visit to `path` in the database stored in the data directory.
+pub fn record_visit(path: &Path) {
+    let _lock = match lock_database() {
+        Ok(lock) => lock,
+        Err(err) => {
+            error!("frecency::record_visit(): {err}");
+            return;
+        }
+    };
+    let Some(database_path) = database_path() else {
+        return;
+    };
+    let mut last_saved = LAST_SAVED.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    let current = FileStamp::of(&database_path);
+    let mut database = match last_saved.take() {
+        Some((stamp, database)) if current.as_ref() == Some(&stamp) => database,
+        _ => {
+            info!("frecency::record_visit(): Reading the database");
+            FrecencyDatabase::load()
+        }
+    };
+    database.visit(path);
+    if let Err(err) = database.save() {
+        error!("frecency::record_visit(): {err}");
+        return;
+    }
+    *last_saved = FileStamp::of(&database_path).map(|stamp| (stamp, database));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+
+    fn entry(path: &Path, rank: f64, age: i64) -> FrecencyEntry {
+        FrecencyEntry {
+            path: path.to_path_buf(),
+            rank,
+            last_access: Utc::now().timestamp() - age,
+        }
+    }
+
+    fn fragments(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    /// Creates the directories under a scratch directory of the test.
+    fn dirs(name: &str, paths: &[&str]) -> PathBuf {
+        let root = scratch_dir(&format!("frecency-{name}"));
+        for path in paths {
+            create_dir_all(root.join(path)).unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn a_visit_adds_an_entry_or_ranks_it_up() {
+        let mut database = FrecencyDatabase::default();
+        database.visit(Path::new("/a"));
+        database.visit(Path::new("/b"));
+        database.visit(Path::new("/a"));
+        let ranks: Vec<(&Path, f64)> = database.entries.iter().map(|entry| (entry.path.as_path(), entry.rank)).collect();
+        assert_eq!(ranks, [(Path::new("/a"), 2.0), (Path::new("/b"), 1.0)]);
+    }
+
+    #[test]
+    fn the_ranks_age_past_the_maximum_total() {
+        let mut database = FrecencyDatabase {
+            entries: vec![entry(Path::new("/big"), MAX_TOTAL_RANK, 0), entry(Path::new("/small"), 1.0, 0)],
+        };
+        database.visit(Path::new("/small"));
+        let ranks: Vec<(&Path, f64)> = database.entries.iter().map(|entry| (entry.path.as_path(), entry.rank)).collect();
+        assert_eq!(ranks, [(Path::new("/big"), MAX_TOTAL_RANK * 0.99), (Path::new("/small"), 2.0 * 0.99)]);
+
+        // The entries that fall under a rank of 1 are forgotten
+        let mut database = FrecencyDatabase {
+            entries: vec![entry(Path::new("/big"), MAX_TOTAL_RANK, 0)],
+        };
+        database.visit(Path::new("/new"));
+        let paths: Vec<&Path> = database.entries.iter().map(|entry| entry.path.as_path()).collect();
+        assert_eq!(paths, [Path::new("/big")]);
+    }
+
+    #[test]
+    fn recent_visits_score_higher() {
+        let path = Path::new("/a");
+        let now = Utc::now().timestamp();
+        let scores: Vec<f64> = [0, 7200, 172800, 1209600].iter().map(|age| entry(path, 8.0, *age).score(now)).collect();
+        assert_eq!(scores, [32.0, 16.0, 4.0, 2.0]);
+    }
+
+    #[test]
+    fn matches_need_every_fragment_in_order() {
+        let root = dirs("order", &["work/project/api", "work/api/project", "gone"]);
+        let database = FrecencyDatabase {
+            entries: vec![
+                entry(&root.join("work/project/api"), 1.0, 0),
+                entry(&root.join("work/api/project"), 1.0, 0),
+                entry(&root.join("missing/project/api"), 1.0, 0),
+            ],
+        };
+        let found: Vec<PathBuf> = database.matches(&fragments("proj api")).into_iter().map(|(_, path)| path).collect();
+        assert_eq!(found, [root.join("work/project/api")]);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn matches_rank_by_score_and_the_name_of_the_directory() {
+        let root = dirs("rank", &["api/docs", "src/api", "other/api"]);
+        let database = FrecencyDatabase {
+            entries: vec![
+                entry(&root.join("api/docs"), 3.0, 0),
+                entry(&root.join("src/api"), 2.0, 0),
+                entry(&root.join("other/api"), 1.0, 0),
+            ],
+        };
+        let found: Vec<(f64, PathBuf)> = database.matches(&fragments("api"));
+        let expected = [
+            (16.0, root.join("src/api")),
+            (12.0, root.join("api/docs")),
+            (8.0, root.join("other/api")),
+        ];
+        assert_eq!(found, expected);
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn matches_ignore_the_case_unless_a_fragment_has_an_uppercase_letter() {
+        let root = dirs("case", &["Music", "music"]);
+        let database = FrecencyDatabase {
+            entries: vec![entry(&root.join("Music"), 1.0, 0), entry(&root.join("music"), 1.0, 0)],
+        };
+        assert_eq!(database.matches(&fragments("music")).len(), 2);
+        let found: Vec<PathBuf> = database.matches(&fragments("Music")).into_iter().map(|(_, path)| path).collect();
+        assert_eq!(found, [root.join("Music")]);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
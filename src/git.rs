@@ -0,0 +1,220 @@
+use git2::{Status, StatusOptions};
+use log::{error, info};
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+
+/// The status of a file in the two columns of `git status --short`: the change
+/// staged in the index and the change in the working tree. `-` means unchanged,
+/// `N` untracked and `I` ignored.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FileStatus {
+    pub staged: char,
+    pub unstaged: char,
+}
+
+impl FileStatus {
+    pub const CLEAN: FileStatus = FileStatus { staged: '-', unstaged: '-' };
+    pub const UNTRACKED: FileStatus = FileStatus { staged: '-', unstaged: 'N' };
+    pub const IGNORED: FileStatus = FileStatus { staged: '-', unstaged: 'I' };
+
+    /// The letters `git status --short` shows for the flags of a file.
+    fn from_flags(flags: Status) -> Self {
+        if flags.is_ignored() {
+            return FileStatus::IGNORED;
+        }
+        if flags.is_wt_new() && !flags.intersects(Status::INDEX_NEW) {
+            return FileStatus::UNTRACKED;
+        }
+        if flags.is_conflicted() {
+            return FileStatus { staged: 'U', unstaged: 'U' };
+        }
+        let letter = |changes: &[(bool, char)]| changes.iter().find(|(set, _)| *set).map_or('-', |(_, letter)| *letter);
+        FileStatus {
+            staged: letter(&[
+                (flags.is_index_new(), 'A'),
+                (flags.is_index_modified(), 'M'),
+                (flags.is_index_deleted(), 'D'),
+                (flags.is_index_renamed(), 'R'),
+                (flags.is_index_typechange(), 'T'),
+            ]),
+            unstaged: letter(&[
+                (flags.is_wt_modified(), 'M'),
+                (flags.is_wt_deleted(), 'D'),
+                (flags.is_wt_renamed(), 'R'),
+                (flags.is_wt_typechange(), 'T'),
+            ]),
+        }
+    }
+
+    pub fn is_ignored(&self) -> bool {
+        *self == FileStatus::IGNORED
+    }
+
+    /// The most interesting of two statuses, for the directories: a change of any
+    /// file inside is shown on the directory.
+    fn merge(self, other: FileStatus) -> FileStatus {
+        fn rank(status: char) -> u8 {
+            match status {
+                '-' => 0,
+                'I' => 1,
+                'N' => 2,
+                _ => 3,
+            }
+        }
+        let pick = |a: char, b: char| if rank(b) > rank(a) { b } else { a };
+        FileStatus {
+            staged: pick(self.staged, other.staged),
+            unstaged: pick(self.unstaged, other.unstaged),
+        }
+    }
+}
+
+/// The status of the files of a repository, read once from its index and its
+/// `.gitignore` files.
+#[derive(Debug)]
+pub struct Repository {
+    pub root: PathBuf,
+    /// The files that aren't clean, relative to `root`. Untracked and ignored
+    /// directories are listed without their files.
+    files: HashMap<PathBuf, FileStatus>,
+    /// The status of each directory with changes inside, merged from its files.
+    dirs: HashMap<PathBuf, FileStatus>,
+}
+
+impl Repository {
+    /// The root of the working tree `dir` is in. A nested repository or a
+    /// submodule is found before the repository around it.
+    pub fn root(dir: &Path) -> Option<PathBuf> {
+        discover(dir).map(|(_, root)| root)
+    }
+
+    /// The repository `dir` belongs to, if it is inside one with a working tree.
+    pub fn open(dir: &Path) -> Option<Self> {
+        let (repository, root) = discover(dir)?;
+        info!("git::Repository::open(): Reading the status of {}", root.to_string_lossy());
+
+        let mut options = StatusOptions::new();
+        options
+            .include_untracked(true)
+            .include_ignored(true)
+            .recurse_untracked_dirs(false)
+            .recurse_ignored_dirs(false)
+            .renames_head_to_index(true);
+        let statuses = match repository.statuses(Some(&mut options)) {
+            Ok(statuses) => statuses,
+            Err(err) => {
+                error!("git::Repository::open(): Cannot read the status of {}: {err}", root.to_string_lossy());
+                return None;
+            }
+        };
+
+        let mut files = HashMap::new();
+        let mut dirs: HashMap<PathBuf, FileStatus> = HashMap::new();
+        for entry in statuses.iter() {
+            // A renamed file is shown with its new name, like `git status` does
+            let path = entry
+                .head_to_index()
+                .or_else(|| entry.index_to_workdir())
+                .and_then(|delta| delta.new_file().path_bytes())
+                .unwrap_or_else(|| entry.path_bytes());
+            let path = PathBuf::from(OsStr::from_bytes(path.strip_suffix(b"/").unwrap_or(path)));
+            let status = FileStatus::from_flags(entry.status());
+            if !status.is_ignored() {
+                for dir in path.ancestors().skip(1) {
+                    let merged = dirs.get(dir).copied().unwrap_or(FileStatus::CLEAN).merge(status);
+                    dirs.insert(dir.to_path_buf(), merged);
+                }
+            }
+            files.insert(path, status);
+        }
+        Some(Repository { root, files, dirs })
+    }
+
+    /// The status of `path`, relative to the root of the repository. The status of a
+    /// directory sums up the files inside it.
+    pub fn status(&self, path: &Path) -> FileStatus {
+        if let Some(status) = self.files.get(path) {
+            return *status;
+        }
+        // Inside an untracked or ignored directory
+        for ancestor in path.ancestors().skip(1) {
+            if let Some(status) = self.files.get(ancestor) {
+                if *status == FileStatus::UNTRACKED || status.is_ignored() {
+                    return *status;
+                }
+            }
+        }
+        self.dirs.get(path).copied().unwrap_or(FileStatus::CLEAN)
+    }
+}
+
+/// The innermost repository `dir` is in, with the root of its working tree.
+fn discover(dir: &Path) -> Option<(git2::Repository, PathBuf)> {
+    let repository = git2::Repository::discover(dir).ok()?;
+    // Without the `/` at the end
+    let root = repository.workdir()?.components().collect();
+    Some((repository, root))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+    use std::fs;
+
+    fn status(staged: char, unstaged: char) -> FileStatus {
+        FileStatus { staged, unstaged }
+    }
+
+    #[test]
+    fn maps_the_flags_to_the_letters_of_git_status() {
+        let cases = [
+            (Status::CURRENT, status('-', '-')),
+            (Status::WT_NEW, FileStatus::UNTRACKED),
+            (Status::IGNORED, FileStatus::IGNORED),
+            (Status::INDEX_NEW, status('A', '-')),
+            (Status::INDEX_NEW | Status::WT_MODIFIED, status('A', 'M')),
+            (Status::INDEX_MODIFIED, status('M', '-')),
+            (Status::INDEX_DELETED, status('D', '-')),
+            (Status::INDEX_RENAMED | Status::WT_DELETED, status('R', 'D')),
+            (Status::INDEX_TYPECHANGE, status('T', '-')),
+            (Status::WT_MODIFIED, status('-', 'M')),
+            (Status::WT_RENAMED, status('-', 'R')),
+            (Status::WT_TYPECHANGE, status('-', 'T')),
+            (Status::CONFLICTED | Status::INDEX_MODIFIED, status('U', 'U')),
+        ];
+        for (flags, expected) in cases {
+            assert_eq!(FileStatus::from_flags(flags), expected, "{flags:?}");
+        }
+    }
+
+    #[test]
+    fn directories_show_the_most_interesting_change() {
+        assert_eq!(FileStatus::CLEAN.merge(FileStatus::IGNORED), FileStatus::IGNORED);
+        assert_eq!(FileStatus::IGNORED.merge(FileStatus::UNTRACKED), FileStatus::UNTRACKED);
+        assert_eq!(FileStatus::UNTRACKED.merge(status('A', '-')), status('A', 'N'));
+        assert_eq!(status('M', '-').merge(status('A', 'D')), status('M', 'D'));
+    }
+
+    #[test]
+    fn finds_the_innermost_repository() {
+        let outer = scratch_dir("git");
+        let inner = outer.join("vendor/inner");
+        fs::create_dir_all(inner.join("src")).unwrap();
+        git2::Repository::init(&outer).unwrap();
+        git2::Repository::init(&inner).unwrap();
+        fs::write(inner.join("src/new.rs"), "").unwrap();
+        let outer = fs::canonicalize(&outer).unwrap();
+        let inner = outer.join("vendor/inner");
+
+        assert_eq!(Repository::root(&outer.join("vendor")), Some(outer.clone()));
+        assert_eq!(Repository::root(&inner.join("src")), Some(inner.clone()));
+        let repository = Repository::open(&inner.join("src")).unwrap();
+        assert_eq!(repository.root, inner);
+        assert_eq!(repository.status(Path::new("src/new.rs")), FileStatus::UNTRACKED);
+
+        let _ = fs::remove_dir_all(&outer);
+    }
+}
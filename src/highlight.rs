@@ -0,0 +1,261 @@
+use crossterm::style::Stylize;
+use std::path::Path;
+
+/// What the highlighter needs to know about a language: its words and how its
+/// comments and strings start and end.
+pub struct Syntax {
+    pub name: &'static str,
+    extensions: &'static [&'static str],
+    keywords: &'static [&'static str],
+    /// Types and constants, shown in another color than the keywords.
+    types: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    quotes: &'static [char],
+}
+
+const SYNTAXES: &[Syntax] = &[
+    Syntax {
+        name: "Rust",
+        extensions: &["rs"],
+        keywords: &[
+            "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for",
+            "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+            "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while",
+        ],
+        types: &[
+            "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64", "i128", "isize",
+            "f32", "f64", "String", "Vec", "Option", "Result", "Box", "Some", "None", "Ok", "Err", "true", "false",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"'],
+    },
+    Syntax {
+        name: "Python",
+        extensions: &["py", "pyw"],
+        keywords: &[
+            "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except",
+            "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "nonlocal", "not", "or", "pass",
+            "raise", "return", "try", "while", "with", "yield",
+        ],
+        types: &["None", "True", "False", "self", "int", "str", "float", "bool", "list", "dict", "tuple", "set"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "C",
+        extensions: &["c", "h", "cc", "cpp", "cxx", "hpp", "hh"],
+        keywords: &[
+            "break", "case", "class", "const", "continue", "default", "delete", "do", "else", "enum", "extern", "for",
+            "goto", "if", "inline", "namespace", "new", "private", "protected", "public", "return", "sizeof", "static",
+            "struct", "switch", "template", "typedef", "union", "using", "virtual", "volatile", "while", "#include",
+            "#define", "#ifdef", "#ifndef", "#endif", "#if", "#else", "#pragma",
+        ],
+        types: &[
+            "void", "char", "short", "int", "long", "float", "double", "signed", "unsigned", "bool", "size_t", "true",
+            "false", "NULL", "nullptr", "auto",
+        ],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "JavaScript",
+        extensions: &["js", "mjs", "cjs", "jsx", "ts", "tsx"],
+        keywords: &[
+            "async", "await", "break", "case", "catch", "class", "const", "continue", "default", "delete", "do", "else",
+            "export", "extends", "finally", "for", "from", "function", "if", "import", "in", "instanceof", "interface",
+            "let", "new", "of", "return", "switch", "this", "throw", "try", "type", "typeof", "var", "while", "yield",
+        ],
+        types: &["true", "false", "null", "undefined", "string", "number", "boolean", "any", "void"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\'', '`'],
+    },
+    Syntax {
+        name: "Go",
+        extensions: &["go"],
+        keywords: &[
+            "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "for", "func", "go",
+            "goto", "if", "import", "interface", "map", "package", "range", "return", "select", "struct", "switch",
+            "type", "var",
+        ],
+        types: &["bool", "byte", "error", "int", "int64", "float64", "rune", "string", "true", "false", "nil"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '`'],
+    },
+    Syntax {
+        name: "Java",
+        extensions: &["java", "kt", "cs"],
+        keywords: &[
+            "abstract", "break", "case", "catch", "class", "continue", "default", "do", "else", "extends", "final",
+            "finally", "for", "fun", "if", "implements", "import", "interface", "new", "package", "private",
+            "protected", "public", "return", "static", "switch", "this", "throw", "throws", "try", "val", "var", "void",
+            "while",
+        ],
+        types: &["boolean", "byte", "char", "double", "float", "int", "long", "short", "String", "true", "false", "null"],
+        line_comments: &["//"],
+        block_comment: Some(("/*", "*/")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "Lua",
+        extensions: &["lua"],
+        keywords: &[
+            "and", "break", "do", "else", "elseif", "end", "for", "function", "goto", "if", "in", "local", "not", "or",
+            "repeat", "return", "then", "until", "while",
+        ],
+        types: &["nil", "true", "false", "self"],
+        line_comments: &["--"],
+        block_comment: Some(("--[[", "]]")),
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "Shell",
+        extensions: &["sh", "bash", "zsh"],
+        keywords: &[
+            "case", "do", "done", "elif", "else", "esac", "export", "fi", "for", "function", "if", "in", "local",
+            "return", "then", "until", "while",
+        ],
+        types: &["echo", "cd", "exit", "set", "unset", "source", "true", "false"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "YAML",
+        extensions: &["yml", "yaml"],
+        keywords: &[],
+        types: &["true", "false", "null", "yes", "no"],
+        line_comments: &["#"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "TOML",
+        extensions: &["toml", "ini", "cfg"],
+        keywords: &[],
+        types: &["true", "false"],
+        line_comments: &["#", ";"],
+        block_comment: None,
+        quotes: &['"', '\''],
+    },
+    Syntax {
+        name: "JSON",
+        extensions: &["json"],
+        keywords: &[],
+        types: &["true", "false", "null"],
+        line_comments: &[],
+        block_comment: None,
+        quotes: &['"'],
+    },
+];
+
+/// The syntax of the file at `path`, chosen by its extension.
+pub fn syntax_for(path: &Path) -> Option<&'static Syntax> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    SYNTAXES.iter().find(|syntax| syntax.extensions.contains(&extension.as_str()))
+}
+
+/// Colors the lines of a file one after the other, a block comment can go on for
+/// several lines.
+pub struct Highlighter {
+    syntax: &'static Syntax,
+    in_block_comment: bool,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '#'
+}
+
+impl Highlighter {
+    pub fn new(syntax: &'static Syntax) -> Self {
+        Highlighter { syntax, in_block_comment: false }
+    }
+
+    /// The end of the string that starts at `start` with `quote`, after the closing
+    /// quote or at the end of the line.
+    fn string_end(line: &str, start: usize, quote: char) -> usize {
+        let mut escaped = false;
+        for (offset, c) in line[start + quote.len_utf8()..].char_indices() {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == quote {
+                return start + quote.len_utf8() + offset + c.len_utf8();
+            }
+        }
+        line.len()
+    }
+
+    pub fn line(&mut self, line: &str) -> String {
+        let syntax = self.syntax;
+        let mut highlighted = String::with_capacity(line.len());
+        let mut position = 0;
+
+        if self.in_block_comment {
+            let (_, close) = syntax.block_comment.unwrap_or_default();
+            let end = match line.find(close) {
+                Some(index) => {
+                    self.in_block_comment = false;
+                    index + close.len()
+                }
+                None => line.len(),
+            };
+            highlighted.push_str(&line[..end].dark_grey().to_string());
+            position = end;
+        }
+
+        while position < line.len() {
+            let rest = &line[position..];
+            let c = rest.chars().next().unwrap_or_default();
+
+            if syntax.line_comments.iter().any(|comment| rest.starts_with(comment))
+                && !syntax.block_comment.is_some_and(|(open, _)| rest.starts_with(open))
+            {
+                highlighted.push_str(&rest.dark_grey().to_string());
+                break;
+            }
+            if let Some((open, close)) = syntax.block_comment.filter(|(open, _)| rest.starts_with(open)) {
+                let end = match rest[open.len()..].find(close) {
+                    Some(index) => position + open.len() + index + close.len(),
+                    None => {
+                        self.in_block_comment = true;
+                        line.len()
+                    }
+                };
+                highlighted.push_str(&line[position..end].dark_grey().to_string());
+                position = end;
+                continue;
+            }
+            if syntax.quotes.contains(&c) {
+                let end = Highlighter::string_end(line, position, c);
+                highlighted.push_str(&line[position..end].green().to_string());
+                position = end;
+                continue;
+            }
+            if is_word_char(c) {
+                let end = rest.find(|c: char| !is_word_char(c)).map_or(line.len(), |index| position + index);
+                let word = &line[position..end];
+                if c.is_ascii_digit() {
+                    highlighted.push_str(&word.yellow().to_string());
+                } else if syntax.keywords.contains(&word) {
+                    highlighted.push_str(&word.magenta().to_string());
+                } else if syntax.types.contains(&word) {
+                    highlighted.push_str(&word.cyan().to_string());
+                } else {
+                    highlighted.push_str(word);
+                }
+                position = end;
+                continue;
+            }
+            highlighted.push(c);
+            position += c.len_utf8();
+        }
+        highlighted
+    }
+}
@@ -0,0 +1,318 @@
+use log::{error, info};
+use serde_yaml::{Mapping, Value};
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::migrations;
+use crate::setup::{self, YamlConfiguration};
+
+/// The config file shared by all the users, read before theirs.
+pub const SYSTEM_CONFIG: &str = "/etc/yarsh/preferences.yml";
+/// The variable that moves the system config somewhere else. It isn't a layer.
+const SYSTEM_CONFIG_VAR: &str = "YARSH_SYSTEM_CONFIG";
+/// The config of a project, in the directory where the shell starts or one of its
+/// parents.
+pub const PROJECT_CONFIG: &str = ".yarsh.yml";
+/// The environment variables that set a field, like `YARSH_TERMINAL_CONFIG__AUTO_CD`.
+const ENV_PREFIX: &str = "YARSH_";
+
+/// Where the values of a layer come from.
+enum Source {
+    File { path: PathBuf, text: String },
+    Env(String),
+}
+
+/// The values set by one source of configs. The layers that come later override
+/// the values of the earlier ones.
+pub struct Layer {
+    source: Source,
+    tree: Value,
+}
+
+impl Layer {
+    /// Where the value at `path` is set in this layer, `None` if it isn't.
+    fn origin(&self, path: &[String]) -> Option<String> {
+        get(&self.tree, path)?;
+        Some(match &self.source {
+            Source::File { path: file, text } => setup::place_of(text, &file.to_string_lossy(), path),
+            Source::Env(name) => format!("${name}"),
+        })
+    }
+
+    fn is_user(&self) -> bool {
+        matches!(&self.source, Source::File { path, .. } if Some(path) == setup::config_path().as_ref())
+    }
+}
+
+fn get<'a>(tree: &'a Value, path: &[String]) -> Option<&'a Value> {
+    path.iter().try_fold(tree, |node, step| match node {
+        Value::Mapping(map) => map.get(step.as_str()),
+        Value::Sequence(list) => list.get(step.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+/// The keys of `path` nested in maps, with `value` at the end.
+fn nest(path: &[String], value: Value) -> Value {
+    path.iter().rev().fold(value, |value, key| {
+        let mut map = Mapping::new();
+        map.insert(Value::from(key.as_str()), value);
+        Value::Mapping(map)
+    })
+}
+
+/// Puts the values of `layer` over the ones of `base`. The maps are merged key by
+/// key, the other values are replaced as a whole.
+fn merge_tree(base: &mut Value, layer: &Value) {
+    match (base, layer) {
+        (Value::Mapping(base), Value::Mapping(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(key) {
+                    Some(old) => merge_tree(old, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer.clone(),
+    }
+}
+
+/// The configs of all the layers together.
+pub fn merge(layers: &[Layer]) -> YamlConfiguration {
+    let mut tree = Value::Mapping(Mapping::new());
+    for layer in layers {
+        merge_tree(&mut tree, &layer.tree);
+    }
+    serde_yaml::from_value(tree).unwrap_or_else(|err| {
+        error!("layers::merge(): Cannot read the merged configs, using the default ones: {err}");
+        YamlConfiguration::default()
+    })
+}
+
+/// Where the value at `path` comes from: a file with its line and column, an
+/// environment variable, or `default`.
+pub fn origin(layers: &[Layer], path: &[String]) -> String {
+    layers.iter().rev().find_map(|layer| layer.origin(path)).unwrap_or_else(|| String::from("default"))
+}
+
+/// Where the value at `path` is set by a layer that comes after the config file of
+/// the user, so changing the file doesn't change it. A list or a value set over
+/// one of the parents of `path` overrides it too.
+pub fn overridden(layers: &[Layer], path: &[String]) -> Option<String> {
+    let user = layers.iter().position(Layer::is_user)?;
+    layers[user + 1..].iter().rev().find_map(|layer| {
+        (1..=path.len()).find_map(|depth| match get(&layer.tree, &path[..depth])? {
+            Value::Mapping(_) if depth < path.len() => None,
+            _ => layer.origin(&path[..depth]),
+        })
+    })
+}
+
+/// A config file read as a layer. The files of an older layout are upgraded in
+/// memory, only the one of the user is written again, and their version is left
+/// out so the one of the user is shown. The other layers came after the versions,
+/// so one without a version has the current layout.
+fn file_layer(path: PathBuf, text: String, upgrade: bool, issues: &mut Vec<String>) -> Layer {
+    let file = path.to_string_lossy().into_owned();
+    let (mut tree, found) = setup::parse_layer(&text, &file);
+    issues.extend(found);
+    if upgrade && tree.get("version").is_some() {
+        if let Err(err) = migrations::migrate(&mut tree) {
+            issues.push(format!("{file}: {err}, it is read as it is"));
+        }
+        if let Value::Mapping(map) = &mut tree {
+            map.remove("version");
+        }
+    }
+    Layer { source: Source::File { path, text }, tree }
+}
+
+/// The layers of the `YARSH_*` variables, one for each. The name is the path of the
+/// field with `__` between the steps, like `YARSH_TERMINAL_CONFIG__ALIAS__LL`. The
+/// value is read as YAML, and as text if the field is a string.
+fn env_layers(issues: &mut Vec<String>) -> Vec<Layer> {
+    let mut layers = vec![];
+    for (name, value) in env_vars().into_iter().filter(|(name, _)| name != SYSTEM_CONFIG_VAR) {
+        let path: Vec<String> = name[ENV_PREFIX.len()..].split("__").map(str::to_lowercase).collect();
+        if path.iter().any(String::is_empty) {
+            issues.push(format!("${name}: not a path of the configs, it is ignored"));
+            continue;
+        }
+        let attempt = |value: Value| {
+            let tree = nest(&path, value);
+            let mut ignored = vec![];
+            setup::deserialize_conf(tree.clone(), &mut ignored).map_err(|err| err.into_inner().to_string())?;
+            match ignored.first() {
+                Some(key) => Err(format!("unknown key `{key}`")),
+                None => Ok(tree),
+            }
+        };
+        let result = match serde_yaml::from_str::<Value>(&value) {
+            Ok(parsed) if !parsed.is_string() => {
+                attempt(parsed).or_else(|err| attempt(Value::from(value.as_str())).map_err(|_| err))
+            }
+            _ => attempt(Value::from(value.as_str())),
+        };
+        match result {
+            Ok(tree) => layers.push(Layer { source: Source::Env(name), tree }),
+            Err(err) => issues.push(format!("${name}: {err}, it is ignored")),
+        }
+    }
+    layers
+}
+
+/// The path of the system config, `$YARSH_SYSTEM_CONFIG` if it is set.
+fn system_config() -> PathBuf {
+    env::var_os(SYSTEM_CONFIG_VAR).map_or_else(|| PathBuf::from(SYSTEM_CONFIG), PathBuf::from)
+}
+
+/// The `YARSH_*` variables, sorted by name.
+fn env_vars() -> Vec<(String, String)> {
+    let mut vars: Vec<(String, String)> = env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+        .collect();
+    vars.sort();
+    vars
+}
+
+/// The `.yarsh.yml` of `dir` or of the closest of its parents that has one.
+pub fn find_project(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors().map(|dir| dir.join(PROJECT_CONFIG)).find(|path| path.is_file())
+}
+
+/// Whether a project config can be used.
+enum Trust {
+    Trusted,
+    /// It was trusted, but it changed since then.
+    Changed,
+    Unknown,
+}
+
+/// The file with the trusted project configs. Each line has the SHA-256 of a file
+/// and its path, so a file has to be trusted again when it changes.
+fn trust_file() -> Option<PathBuf> {
+    Some(setup::config_path()?.with_file_name("trusted_projects"))
+}
+
+fn digest(text: &str) -> String {
+    Sha256::digest(text.as_bytes()).iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn trusted() -> Vec<(String, PathBuf)> {
+    let Some(text) = trust_file().and_then(|file| fs::read_to_string(file).ok()) else {
+        return vec![];
+    };
+    text.lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(digest, path)| (digest.to_string(), PathBuf::from(path)))
+        .collect()
+}
+
+fn trust_of(path: &Path, text: &str) -> Trust {
+    let digest = digest(text);
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let digests: Vec<String> = trusted().into_iter().filter(|(_, trusted)| *trusted == path).map(|(digest, _)| digest).collect();
+    if digests.is_empty() {
+        Trust::Unknown
+    } else if digests.contains(&digest) {
+        Trust::Trusted
+    } else {
+        Trust::Changed
+    }
+}
+
+/// Trusts the project config at `path` as it is now, or stops trusting it when
+/// `trust` is false. Returns the path that was written in the list.
+pub fn set_trust(path: &Path, trust: bool) -> Result<PathBuf, String> {
+    let path = fs::canonicalize(path).map_err(|err| format!("{}: {err}", path.to_string_lossy()))?;
+    let file = trust_file().ok_or_else(|| String::from("Cannot find the config directory"))?;
+    let mut entries: Vec<(String, PathBuf)> = trusted().into_iter().filter(|(_, trusted)| *trusted != path).collect();
+    if trust {
+        let text = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.to_string_lossy()))?;
+        entries.push((digest(&text), path.clone()));
+    }
+    let text: String = entries.iter().map(|(digest, path)| format!("{digest}  {}\n", path.to_string_lossy())).collect();
+    fs::write(&file, text).map_err(|err| format!("Cannot write {}: {err}", file.to_string_lossy()))?;
+    info!("layers::set_trust(): {} is {}trusted", path.to_string_lossy(), if trust { "" } else { "not " });
+    Ok(path)
+}
+
+/// The config of the project of the current directory. It is only used after it is
+/// trusted with `config trust`, because it can set the scripts and the aliases the
+/// shell runs.
+fn project_layer(issues: &mut Vec<String>) -> Option<Layer> {
+    let path = find_project(&env::current_dir().ok()?)?;
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) => {
+            issues.push(format!("{}: {err}, it is ignored", path.to_string_lossy()));
+            return None;
+        }
+    };
+    match trust_of(&path, &text) {
+        Trust::Trusted => Some(file_layer(path, text, true, issues)),
+        Trust::Changed => {
+            issues.push(format!(
+                "{}: changed since it was trusted, it is ignored until you run `config trust` again",
+                path.to_string_lossy()
+            ));
+            None
+        }
+        Trust::Unknown => {
+            issues.push(format!("{}: not trusted, it is ignored. Run `config trust` to use it", path.to_string_lossy()));
+            None
+        }
+    }
+}
+
+/// Reads the layers of configs, from the first to the last: the system config,
+/// the config of the user, the trusted config of the project and the `YARSH_*`
+/// environment variables. Returns them with the problems found.
+pub fn read_layers() -> (Vec<Layer>, Vec<String>) {
+    let mut issues = vec![];
+    let mut layers = vec![];
+    let system = system_config();
+    match fs::read_to_string(&system) {
+        Ok(text) => layers.push(file_layer(system, text, true, &mut issues)),
+        Err(err) if err.kind() == ErrorKind::NotFound => {}
+        Err(err) => issues.push(format!("{}: {err}, it is ignored", system.to_string_lossy())),
+    }
+    if let Some((path, text)) = setup::read_user_conf(&mut issues) {
+        layers.push(file_layer(path, text, false, &mut issues));
+    }
+    layers.extend(project_layer(&mut issues));
+    layers.extend(env_layers(&mut issues));
+    (layers, issues)
+}
+
+/// The files the layers are read from, with their modification times, and the
+/// `YARSH_*` variables. When it changes the configs have to be read again.
+#[derive(PartialEq)]
+pub struct Stamp(Vec<(PathBuf, Option<SystemTime>)>, Vec<(String, String)>);
+
+/// The stamp of the layers as they are now. The project config is the one of the
+/// current directory, so going to another project changes it too, and so does a
+/// variable changed while the shell runs.
+pub fn stamp() -> Stamp {
+    let mut files = vec![system_config()];
+    files.extend(setup::config_path());
+    files.extend(trust_file());
+    files.extend(env::current_dir().ok().and_then(|cwd| find_project(&cwd)));
+    Stamp(
+        files
+            .into_iter()
+            .map(|file| {
+                let modified = fs::metadata(&file).and_then(|metadata| metadata.modified()).ok();
+                (file, modified)
+            })
+            .collect(),
+        env_vars(),
+    )
+}
@@ -1,47 +1,122 @@
 use crossterm::style::Stylize;
 use libc::{kill, pid_t, SIGTERM};
 use log::{error, info};
+use rustyline::config::Configurer;
 use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
-use shellwords::split;
+use rustyline::history::DefaultHistory;
+use rustyline::{EditMode, Editor};
 use std::{
-    env::{current_dir, set_current_dir},
-    path::Path,
-    sync::{mpsc, Arc},
+    collections::BTreeMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        mpsc, Arc, Mutex,
+    },
     thread,
 };
 
 mod script_loader;
+mod builtins;
+mod colors;
 mod commands;
+mod completion;
+mod config_editor;
+mod elf;
+mod expand;
+mod frecency;
+mod git;
+mod highlight;
+mod layers;
+mod migrations;
+mod pager;
+mod parser;
+mod path_index;
+mod pipeline;
+mod resolve;
 mod setup;
+mod suggest;
+#[cfg(test)]
+mod test_support;
+mod yaml_edit;
 
-use commands::{ExternalCommands, Builtin};
+use builtins::{DirStack, ShellState};
+use colors::FileColors;
+use commands::ExternalCommands;
+use completion::ShellHelper;
+use setup::YamlConfiguration;
 
 enum SendTypes {
-    ShouldExit(u32),
+    ShouldExit,
     Pid(u32),
 }
 
-fn main() {
-    setup::setup();
-    info!("main: Loading configuration. This will not be the last time...");
-    let configs = setup::load_conf();
+/// The prompt of the config with `{}` replaced by the working directory.
+fn prompt_text(configs: &YamlConfiguration) -> String {
+    match builtins::logical_cwd() {
+        Ok(wkd) => format!("{} >> ", configs.terminal_config.prompt.replace("{}", &wkd.to_string_lossy())),
+        Err(_) => String::from(">> "),
+    }
+}
 
-    info!("main: Browsing executables in the PATH environment variable...");
-    let external_obj = ExternalCommands::new().unwrap_or_else(|_| {
-        std::process::exit(1);
+/// Uses the configs read again while the shell runs. The aliases, the prompt and
+/// the rest of the state are read from `state` each time, the colors and the key
+/// bindings go to the editor and the loggers are switched. The scripts are only
+/// loaded again with `reload_scripts`.
+fn apply_configs(rl: &mut Editor<ShellHelper, DefaultHistory>, state: &mut ShellState, configs: YamlConfiguration) {
+    setup::apply_log_config(&configs.logs_configurations);
+    rl.set_edit_mode(match configs.terminal_config.edit_mode {
+        setup::EditMode::Emacs => EditMode::Emacs,
+        setup::EditMode::Vi => EditMode::Vi,
     });
+    if let Some(helper) = rl.helper_mut() {
+        helper.colors = FileColors::new(&configs.theme);
+    }
+    if configs.scripts_config.files != state.configs.scripts_config.files {
+        eprintln!("yarp: The scripts of the configs changed, run `reload_scripts` to load them");
+    }
+    if let Some(lua) = &state.lua {
+        script_loader::set_configs(lua, &configs);
+    }
+    state.configs = configs;
+}
+
+fn main() {
+    let (configs, issues) = setup::setup();
+    for issue in &issues {
+        eprintln!("yarp: {issue}");
+    }
+    let mut shown_issues = issues;
+    let mut stamp = layers::stamp();
+    builtins::init_pwd();
+
+    info!("main: Creating the index of the PATH environment variable...");
+    let external_obj = ExternalCommands::new();
+    let dir_stack = Arc::new(Mutex::new(DirStack::new()));
 
     info!("main: Loading scripts...");
-    script_loader::load(configs.scripts_config.files.clone(), external_obj.clone());
+    let lua = script_loader::load(&configs, external_obj.clone(), dir_stack.clone());
 
-    let mut rl = DefaultEditor::new().unwrap();
+    let mut rl: Editor<ShellHelper, DefaultHistory> = Editor::new().unwrap();
+    commands::watch_terminal_size();
+    rl.set_helper(Some(ShellHelper::new(external_obj.clone(), FileColors::new(&configs.theme))));
     #[cfg(feature = "with-file-history")]
     if rl.load_history("history.txt").is_err() {
         println!("No previous history.");
     }
 
-    let mut current_command_pid: Arc<u32> = Arc::new(0);
+    let current_command_pid = Arc::new(AtomicU32::new(0));
+    let mut state = ShellState {
+        configs: configs.clone(),
+        external: external_obj.clone(),
+        dir_stack,
+        lua: Some(lua),
+        exit_requested: false,
+        reload_requested: false,
+        foreground_pid: Arc::clone(&current_command_pid),
+        subshell: false,
+        function_docs: BTreeMap::new(),
+    };
+
+    apply_configs(&mut rl, &mut state, configs);
 
     let (sv, rv) = mpsc::channel::<SendTypes>();
 
@@ -49,8 +124,9 @@ fn main() {
     let ctrlc_ccppid = Arc::clone(&current_command_pid);
 
     let ctrlc_err = ctrlc::set_handler(move || {
-        info!("ctrlc_handler: Current pid to kill '{}'", *ctrlc_ccppid);
-        if let Err(err) = ctrlc_sender.send(SendTypes::Pid(*ctrlc_ccppid)) {
+        let pid = ctrlc_ccppid.load(Ordering::SeqCst);
+        info!("ctrlc_handler: Current pid to kill '{}'", pid);
+        if let Err(err) = ctrlc_sender.send(SendTypes::Pid(pid)) {
             error!("main : ctrlc : set_handler(): Cannot send information between threads");
             error!("main : ctrlc : set_handler(): {err}");
             println!("yarp: Cannot send the pid of the current program");
@@ -80,7 +156,7 @@ fn main() {
                     continue;
                 }
             }
-            Ok(SendTypes::ShouldExit(_)) => {
+            Ok(SendTypes::ShouldExit) => {
                 info!("thread : killer: Thread closed...");
                 break;
             }
@@ -94,8 +170,35 @@ fn main() {
         }
     });
 
-    let mut prompt = format!("{} >> ", current_dir().unwrap().to_string_lossy());
     loop {
+        // The configs are read again when a file of their layers changed, or when
+        // `config reload` asks for it, even from a script. The problems are shown once,
+        // or every time with `config reload`
+        if state.lua.as_ref().is_some_and(script_loader::take_reload_request) {
+            state.reload_requested = true;
+        }
+        let current = layers::stamp();
+        if state.reload_requested || current != stamp {
+            info!("main: Reading the configs again...");
+            stamp = current;
+            let (configs, issues) = setup::read_conf();
+            for issue in &issues {
+                if state.reload_requested || !shown_issues.contains(issue) {
+                    eprintln!("yarp: {issue}");
+                }
+            }
+            shown_issues = issues;
+            state.reload_requested = false;
+            apply_configs(&mut rl, &mut state, configs);
+        }
+        let prompt = prompt_text(&state.configs);
+        if let Some(helper) = rl.helper_mut() {
+            let mut extra_commands: Vec<String> = state.configs.terminal_config.alias.keys().cloned().collect();
+            if let Some(lua) = &state.lua {
+                extra_commands.extend(script_loader::function_names(lua));
+            }
+            helper.extra_commands = extra_commands;
+        }
         let readline = rl.readline(&prompt);
 
         match readline {
@@ -104,89 +207,9 @@ fn main() {
                     println!("{}: History cannot be saved", "Error".red());
                     println!("{}", err);
                 }
-                let shell_cmd = split(&line);
-                if let Err(err) = shell_cmd {
-                    println!("Debug: Cannot parse command: {}", err);
-                    continue;
-                }
-                let shell_cmd = shell_cmd.unwrap();
-
-                if let Some(unknown_cmd) = shell_cmd.get(0) {
-                    match unknown_cmd.as_str() {
-                        "ls" => {
-                            Builtin::list_cmd(shell_cmd);
-                        }
-                        "cd" => {
-                            if shell_cmd.get(1).is_some() {
-                                let binded = &shell_cmd[1].clone();
-                                let root = Path::new(binded);
-                                if let Err(err) = set_current_dir(root) {
-                                    println!("cd: {}", err);
-                                } else {
-                                    let binded = current_dir().unwrap();
-                                    prompt = format!("{} >> ", binded.to_string_lossy());
-                                }
-                            }
-                        }
-
-                        "echo" => {
-                            println!(
-                                "{}",
-                                shell_cmd
-                                    .iter()
-                                    .skip(1)
-                                    .cloned()
-                                    .collect::<Vec<String>>()
-                                    .join(" ")
-                            );
-                        }
-
-                        "clear" => {
-                            if let Err(_) = Builtin::clear_screen() {
-                                println!("clear: Error while trying to clear the terminal")
-                            };
-                        }
-
-                        "reload_scripts" => {
-                            script_loader::load(configs.scripts_config.files.clone(), external_obj.clone());
-                        }
-
-                        "read" => {
-                            // if let Some(file_path) = shell_cmd.get(1) {
-                            //     if file_path == "-f" {
-                            //         if let Some(file_path2) = shell_cmd.get(2) {
-                            //             Builtin::read_file(PathBuf::from(file_path2), true);
-                            //         } else {
-                            //             println!("{}: you need to specify the file path to be readed", "read".green());
-                            //             continue;
-                            //         }
-                            //     } else {
-                            //         Builtin::read_file(PathBuf::from(file_path), false);
-                            //     }
-                            // } else {
-                            //     println!("{}: you need to specify the file path to be readed", "read".green());
-                            //     continue;
-                            // }
-                            Builtin::read_file(shell_cmd);
-                        }
-
-                        "config" => {
-                            Builtin::config_cmd(shell_cmd);
-                        }
-
-                        "exit" => break,
-                        &_ => {
-                            if let Ok(output_obj) = external_obj.run_external_command(&shell_cmd.join(" ")) {
-                                let mut unwraped_output = output_obj.unwrap();
-                                current_command_pid = Arc::new(unwraped_output.id());
-                                if let Err(err) = unwraped_output.wait() {
-                                    error!("main : loop : match : &_: Cannot wait for the command");
-                                    error!("main : loop : match : &_: {err}");
-                                    println!("yarp: Cannot wait for the command...");
-                                }
-                            }
-                        }
-                    }
+                pipeline::run_line(&line, &mut state);
+                if state.exit_requested {
+                    break;
                 }
             }
             Err(ReadlineError::Interrupted) => {
@@ -208,9 +231,12 @@ fn main() {
         }
     }
     #[cfg(feature = "with-file-history")]
-    rl.save_history("history.txt");
+    if let Err(err) = rl.save_history("history.txt") {
+        error!("main: Cannot save the history");
+        error!("main: {err}");
+    }
     info!("main: Waiting for the killer thread to exit");
-    if let Err(err) = sv.send(SendTypes::ShouldExit(1)) {
+    if let Err(err) = sv.send(SendTypes::ShouldExit) {
         error!("main: Cannot send the stop flag to the killer thread.");
         error!("main: {err}. Forcibly closing...");
         std::process::exit(0);
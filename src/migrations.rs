@@ -0,0 +1,48 @@
+use serde_yaml::{Mapping, Value};
+
+/// The version of the layout of `preferences.yml` this shell writes.
+pub const CURRENT_VERSION: u64 = MIGRATIONS.len() as u64;
+
+/// Upgrades the config file from the version of its position to the next one.
+type Migration = fn(&mut Mapping) -> Result<(), String>;
+
+const MIGRATIONS: &[Migration] = &[unversioned_to_1];
+
+/// The files written before there was a version: the first layout and the ones that
+/// added `terminal_config.auto_cd`, `terminal_config.command_not_found_handler` and
+/// the `theme` section. The missing fields take their default values when the file
+/// is read, so they only need the version. Their `terminal_config.prompt` wasn't
+/// shown by those shells, but it is kept as the user wrote it.
+fn unversioned_to_1(_config: &mut Mapping) -> Result<(), String> {
+    Ok(())
+}
+
+/// The version of the layout of `tree`, 0 for the files written before there was one.
+pub fn version_of(tree: &Value) -> Result<u64, String> {
+    match tree.get("version") {
+        None => Ok(0),
+        Some(Value::Number(number)) => number.as_u64().ok_or_else(|| format!("`{number}` is not a version")),
+        Some(other) => Err(format!("`{}` is not a version", serde_yaml::to_string(other).unwrap_or_default().trim_end())),
+    }
+}
+
+/// Upgrades `tree` to the current layout, one version after the other. Files of a
+/// newer version than this shell knows are refused.
+pub fn migrate(tree: &mut Value) -> Result<(), String> {
+    let version = version_of(tree)?;
+    if version > CURRENT_VERSION {
+        return Err(format!("the version {version} is newer than the ones this shell knows ({CURRENT_VERSION})"));
+    }
+    let Value::Mapping(config) = tree else {
+        return Err(String::from("the config is not a map"));
+    };
+    for (from, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        migration(config).map_err(|err| format!("cannot upgrade from version {from}: {err}"))?;
+    }
+    // The version goes first, like when the configs are written
+    let mut upgraded = Mapping::new();
+    upgraded.insert(Value::from("version"), Value::from(CURRENT_VERSION));
+    upgraded.extend(std::mem::take(config).into_iter().filter(|(key, _)| key.as_str() != Some("version")));
+    *config = upgraded;
+    Ok(())
+}
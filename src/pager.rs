@@ -0,0 +1,215 @@
+use crossterm::cursor::{Hide, MoveTo, Show};
+use crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crossterm::style::{Print, Stylize};
+use crossterm::terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen};
+use crossterm::{execute, queue};
+use log::info;
+use std::io::{self, Write};
+
+use crate::commands::truncate_to_width;
+
+/// A line of the pager: the text that is searched and the text that is shown, which
+/// can have colors.
+pub struct Line {
+    pub plain: String,
+    pub shown: String,
+}
+
+/// Shows `lines` in the alternate screen until the user quits. The lines that don't
+/// fit in the width of the terminal are cut.
+pub fn page(title: &str, lines: &[Line]) -> io::Result<()> {
+    info!("pager::page(): Paging {} lines of {title}", lines.len());
+    let mut stdout = io::stdout();
+    terminal::enable_raw_mode()?;
+    let result = execute!(stdout, EnterAlternateScreen, Hide).and_then(|_| {
+        let mut pager = Pager { title, lines, top: 0, pattern: None, matched: None, message: None };
+        pager.run(&mut stdout)
+    });
+    let restored = execute!(stdout, Show, LeaveAlternateScreen);
+    terminal::disable_raw_mode()?;
+    result.and(restored)
+}
+
+/// The places of `pattern` in `text`. The case is ignored unless the pattern has
+/// uppercase letters.
+fn find_matches(text: &str, pattern: &str) -> Vec<(usize, usize)> {
+    if pattern.is_empty() {
+        return vec![];
+    }
+    let (text, pattern) = if pattern.chars().any(|c| c.is_uppercase()) {
+        (text.to_string(), pattern.to_string())
+    } else {
+        (text.to_ascii_lowercase(), pattern.to_ascii_lowercase())
+    };
+    text.match_indices(&pattern).map(|(start, found)| (start, start + found.len())).collect()
+}
+
+struct Pager<'a> {
+    title: &'a str,
+    lines: &'a [Line],
+    /// The first line on the screen.
+    top: usize,
+    pattern: Option<String>,
+    /// The line of the last match, the next search starts there. The last page can't
+    /// be scrolled to the top, so `top` isn't enough.
+    matched: Option<usize>,
+    /// Shown instead of the status line until the next key.
+    message: Option<String>,
+}
+
+impl Pager<'_> {
+    /// The rows for the lines, the last one is for the status line.
+    fn height() -> usize {
+        terminal::size().map_or(24, |(_, rows)| rows as usize).saturating_sub(1).max(1)
+    }
+
+    fn scroll(&mut self, lines: isize) {
+        self.matched = None;
+        let max_top = self.lines.len().saturating_sub(Pager::height());
+        self.top = self.top.saturating_add_signed(lines).min(max_top);
+    }
+
+    /// `line` with the matches of the pattern in reverse video.
+    fn marked(&self, line: &Line) -> String {
+        let Some(pattern) = &self.pattern else {
+            return line.shown.clone();
+        };
+        let matches = find_matches(&line.plain, pattern);
+        if matches.is_empty() {
+            return line.shown.clone();
+        }
+        let mut marked = String::new();
+        let mut position = 0;
+        for (start, end) in matches {
+            marked.push_str(&line.plain[position..start]);
+            marked.push_str(&line.plain[start..end].reverse().to_string());
+            position = end;
+        }
+        marked.push_str(&line.plain[position..]);
+        marked
+    }
+
+    fn status(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        let last = (self.top + Pager::height()).min(self.lines.len());
+        let percent = last * 100 / self.lines.len().max(1);
+        format!(
+            " {}  lines {}-{}/{}  {percent}%  (q: quit, /: search, n/N: next/previous match)",
+            self.title,
+            self.top + 1,
+            last,
+            self.lines.len(),
+        )
+    }
+
+    fn draw(&self, out: &mut impl Write) -> io::Result<()> {
+        let (columns, _) = terminal::size()?;
+        let height = Pager::height();
+        for row in 0..height {
+            queue!(out, MoveTo(0, row as u16), Clear(ClearType::CurrentLine))?;
+            match self.lines.get(self.top + row) {
+                Some(line) => queue!(out, Print(truncate_to_width(&self.marked(line), columns as usize)))?,
+                None => queue!(out, Print("~".dark_grey()))?,
+            }
+        }
+        let status = truncate_to_width(&self.status(), columns as usize);
+        queue!(out, MoveTo(0, height as u16), Clear(ClearType::CurrentLine), Print(status.reverse()))?;
+        out.flush()
+    }
+
+    /// Reads the pattern of a search in the status line. `None` if it was cancelled.
+    fn read_pattern(&self, out: &mut impl Write) -> io::Result<Option<String>> {
+        let mut pattern = String::new();
+        loop {
+            queue!(out, MoveTo(0, Pager::height() as u16), Clear(ClearType::CurrentLine), Print(format!("/{pattern}")), Show)?;
+            out.flush()?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind == KeyEventKind::Release {
+                continue;
+            }
+            match key.code {
+                KeyCode::Enter => break,
+                KeyCode::Esc => {
+                    pattern.clear();
+                    break;
+                }
+                KeyCode::Backspace if pattern.is_empty() => break,
+                KeyCode::Backspace => {
+                    pattern.pop();
+                }
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    pattern.clear();
+                    break;
+                }
+                KeyCode::Char(c) => pattern.push(c),
+                _ => {}
+            }
+        }
+        queue!(out, Hide)?;
+        Ok(Some(pattern).filter(|pattern| !pattern.is_empty()))
+    }
+
+    /// Moves to the next line with the pattern after the last match or the first line
+    /// on the screen, or to the previous one. The search wraps around the ends of the file.
+    fn find(&mut self, forward: bool) {
+        let Some(pattern) = &self.pattern else {
+            self.message = Some(String::from(" No previous search"));
+            return;
+        };
+        let count = self.lines.len();
+        let from = self.matched.unwrap_or(self.top);
+        let found = (1..=count)
+            .map(|step| if forward { (from + step) % count } else { (from + count - step) % count })
+            .find(|index| !find_matches(&self.lines[*index].plain, pattern).is_empty());
+        match found {
+            Some(index) => {
+                self.top = 0;
+                self.scroll(index as isize);
+                self.matched = Some(index);
+            }
+            None => self.message = Some(format!(" Pattern not found: {pattern}")),
+        }
+    }
+
+    fn run(&mut self, out: &mut impl Write) -> io::Result<()> {
+        loop {
+            self.draw(out)?;
+            let key = match event::read()? {
+                Event::Key(key) if key.kind != KeyEventKind::Release => key,
+                _ => continue,
+            };
+            self.message = None;
+            let page = Pager::height() as isize;
+            match key {
+                KeyEvent { code: KeyCode::Char('c'), modifiers, .. } if modifiers.contains(KeyModifiers::CONTROL) => {
+                    return Ok(())
+                }
+                KeyEvent { code, .. } => match code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('j') | KeyCode::Down | KeyCode::Enter => self.scroll(1),
+                    KeyCode::Char('k') | KeyCode::Up => self.scroll(-1),
+                    KeyCode::Char(' ') | KeyCode::Char('f') | KeyCode::PageDown => self.scroll(page),
+                    KeyCode::Char('b') | KeyCode::PageUp => self.scroll(-page),
+                    KeyCode::Char('d') => self.scroll(page / 2),
+                    KeyCode::Char('u') => self.scroll(-page / 2),
+                    KeyCode::Char('g') | KeyCode::Home => self.top = 0,
+                    KeyCode::Char('G') | KeyCode::End => self.scroll(self.lines.len() as isize),
+                    KeyCode::Char('/') => {
+                        if let Some(pattern) = self.read_pattern(out)? {
+                            self.pattern = Some(pattern);
+                            self.matched = None;
+                            self.find(true);
+                        }
+                    }
+                    KeyCode::Char('n') => self.find(true),
+                    KeyCode::Char('N') => self.find(false),
+                    _ => {}
+                },
+            }
+        }
+    }
+}
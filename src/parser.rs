@@ -0,0 +1,476 @@
+use std::mem;
+
+/// A word of the command line, without its quotes.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Word {
+    pub text: String,
+    /// Something before the first `/` was quoted, so a `~` at the start is kept as
+    /// it is, like in `'~'` or `"~/x"`.
+    pub quoted_prefix: bool,
+}
+
+/// Where a redirection sends the output or takes the input of a command.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirect {
+    /// `< file`
+    Input(Word),
+    /// `> file`, `>> file`, `2> file` and `2>> file`.
+    Output { fd: u32, path: Word, append: bool },
+    /// `&> file` and `&>> file`: stdout and stderr to the same file.
+    Both { path: Word, append: bool },
+    /// `2>&1` and `>&2`: `fd` writes wherever `to` writes.
+    Dup { fd: u32, to: u32 },
+}
+
+/// A command of a pipeline, with its redirections already taken out of the words.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stage {
+    pub words: Vec<Word>,
+    pub redirects: Vec<Redirect>,
+}
+
+impl Stage {
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.redirects.is_empty()
+    }
+}
+
+/// Commands joined by `|`, the output of each one is the input of the next.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pipeline {
+    pub stages: Vec<Stage>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum RedirectOp {
+    Read,
+    Write,
+    Append,
+    Dup(u32),
+    Both { append: bool },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(Word),
+    Pipe,
+    Semicolon,
+    Redirect { fd: u32, op: RedirectOp },
+}
+
+impl Token {
+    fn shown(&self) -> String {
+        match self {
+            Token::Word(word) => word.text.clone(),
+            Token::Pipe => String::from("|"),
+            Token::Semicolon => String::from(";"),
+            Token::Redirect { op: RedirectOp::Read, .. } => String::from("<"),
+            Token::Redirect { op: RedirectOp::Write, .. } => String::from(">"),
+            Token::Redirect { op: RedirectOp::Append, .. } => String::from(">>"),
+            Token::Redirect { op: RedirectOp::Dup(to), .. } => format!(">&{to}"),
+            Token::Redirect { op: RedirectOp::Both { append: false }, .. } => String::from("&>"),
+            Token::Redirect { op: RedirectOp::Both { append: true }, .. } => String::from("&>>"),
+        }
+    }
+}
+
+fn unexpected(token: Option<&Token>) -> String {
+    let shown = token.map_or(String::from("newline"), Token::shown);
+    format!("syntax error near unexpected token `{shown}'")
+}
+
+/// The word being read by `tokenize()`.
+#[derive(Default)]
+struct WordBuffer {
+    word: String,
+    /// True once something was read, `""` is an empty word and not nothing.
+    in_word: bool,
+    quoted: bool,
+    quoted_prefix: bool,
+}
+
+impl WordBuffer {
+    fn push(&mut self, ch: char) {
+        self.in_word = true;
+        self.word.push(ch);
+    }
+
+    fn start_quote(&mut self) {
+        self.in_word = true;
+        self.quoted = true;
+        if !self.word.contains('/') {
+            self.quoted_prefix = true;
+        }
+    }
+
+    /// Moves the word, if there is one, to `tokens`.
+    fn flush(&mut self, tokens: &mut Vec<Token>) {
+        if self.in_word {
+            tokens.push(Token::Word(Word { text: mem::take(&mut self.word), quoted_prefix: self.quoted_prefix }));
+        }
+        self.in_word = false;
+        self.quoted = false;
+        self.quoted_prefix = false;
+    }
+
+    /// Takes the word if it is a lone unquoted `1` or `2`, the descriptor of a `>`.
+    fn take_fd(&mut self) -> Option<u32> {
+        let fd = match self.word.as_str() {
+            "1" if !self.quoted => 1,
+            "2" if !self.quoted => 2,
+            _ => return None,
+        };
+        self.word.clear();
+        self.in_word = false;
+        Some(fd)
+    }
+}
+
+/// Splits a command line into words and operators. Quotes and backslashes work
+/// like in `sh`, and the operators inside them are just characters.
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut buffer = WordBuffer::default();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => buffer.flush(&mut tokens),
+            '\'' => {
+                buffer.start_quote();
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(ch) => buffer.push(ch),
+                        None => return Err(String::from("unclosed quote")),
+                    }
+                }
+            }
+            '"' => {
+                buffer.start_quote();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.peek() {
+                            Some(&next @ ('"' | '\\' | '$' | '`')) => {
+                                buffer.push(next);
+                                chars.next();
+                            }
+                            _ => buffer.push('\\'),
+                        },
+                        Some(ch) => buffer.push(ch),
+                        None => return Err(String::from("unclosed quote")),
+                    }
+                }
+            }
+            '\\' => {
+                buffer.start_quote();
+                if let Some(ch) = chars.next() {
+                    buffer.push(ch);
+                }
+            }
+            '|' => {
+                buffer.flush(&mut tokens);
+                tokens.push(Token::Pipe);
+            }
+            ';' => {
+                buffer.flush(&mut tokens);
+                tokens.push(Token::Semicolon);
+            }
+            '<' => {
+                buffer.flush(&mut tokens);
+                tokens.push(Token::Redirect { fd: 0, op: RedirectOp::Read });
+            }
+            '>' => {
+                let fd = buffer.take_fd().unwrap_or_else(|| {
+                    buffer.flush(&mut tokens);
+                    1
+                });
+                let op = match chars.peek() {
+                    Some('>') => {
+                        chars.next();
+                        RedirectOp::Append
+                    }
+                    Some('&') => {
+                        chars.next();
+                        match chars.next() {
+                            Some('1') => RedirectOp::Dup(1),
+                            Some('2') => RedirectOp::Dup(2),
+                            _ => return Err(String::from("only >&1 and >&2 are supported")),
+                        }
+                    }
+                    _ => RedirectOp::Write,
+                };
+                tokens.push(Token::Redirect { fd, op });
+            }
+            '&' if chars.peek() == Some(&'>') => {
+                buffer.flush(&mut tokens);
+                chars.next();
+                let append = chars.peek() == Some(&'>');
+                if append {
+                    chars.next();
+                }
+                tokens.push(Token::Redirect { fd: 1, op: RedirectOp::Both { append } });
+            }
+            c => buffer.push(c),
+        }
+    }
+    buffer.flush(&mut tokens);
+    Ok(tokens)
+}
+
+/// Splits `text` into words like the words of a command line, for the aliases. The
+/// operators can't be used there.
+pub fn split_words(text: &str) -> Result<Vec<Word>, String> {
+    tokenize(text)?
+        .into_iter()
+        .map(|token| match token {
+            Token::Word(word) => Ok(word),
+            other => Err(unexpected(Some(&other))),
+        })
+        .collect()
+}
+
+/// Parses a command line into the pipelines separated by `;`.
+pub fn parse(line: &str) -> Result<Vec<Pipeline>, String> {
+    let mut tokens = tokenize(line)?.into_iter();
+    let mut pipelines = vec![];
+    let mut stages = vec![];
+    let mut stage = Stage::default();
+
+    while let Some(token) = tokens.next() {
+        match token {
+            Token::Word(word) => stage.words.push(word),
+            Token::Redirect { fd, op: RedirectOp::Dup(to) } => {
+                stage.redirects.push(Redirect::Dup { fd, to });
+            }
+            Token::Redirect { fd, op } => {
+                let path = match tokens.next() {
+                    Some(Token::Word(path)) => path,
+                    other => return Err(unexpected(other.as_ref())),
+                };
+                stage.redirects.push(match op {
+                    RedirectOp::Read => Redirect::Input(path),
+                    RedirectOp::Write => Redirect::Output { fd, path, append: false },
+                    RedirectOp::Append => Redirect::Output { fd, path, append: true },
+                    RedirectOp::Both { append } => Redirect::Both { path, append },
+                    RedirectOp::Dup(_) => unreachable!(),
+                });
+            }
+            Token::Pipe => {
+                if stage.is_empty() {
+                    return Err(unexpected(Some(&Token::Pipe)));
+                }
+                stages.push(mem::take(&mut stage));
+            }
+            Token::Semicolon => {
+                if stage.is_empty() {
+                    return Err(unexpected(Some(&Token::Semicolon)));
+                }
+                stages.push(mem::take(&mut stage));
+                pipelines.push(Pipeline { stages: mem::take(&mut stages) });
+            }
+        }
+    }
+
+    if stage.is_empty() {
+        if !stages.is_empty() {
+            return Err(unexpected(None));
+        }
+    } else {
+        stages.push(stage);
+        pipelines.push(Pipeline { stages });
+    }
+    Ok(pipelines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(text: &str) -> Word {
+        Word { text: text.to_string(), quoted_prefix: false }
+    }
+
+    /// The texts of the words of each stage of each pipeline.
+    fn words(line: &str) -> Vec<Vec<Vec<String>>> {
+        parse(line)
+            .unwrap()
+            .into_iter()
+            .map(|pipeline| {
+                pipeline
+                    .stages
+                    .into_iter()
+                    .map(|stage| stage.words.into_iter().map(|word| word.text).collect())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The only stage of `line`.
+    fn stage(line: &str) -> Stage {
+        let mut pipelines = parse(line).unwrap();
+        assert_eq!(pipelines.len(), 1, "{line}");
+        let mut stages = pipelines.remove(0).stages;
+        assert_eq!(stages.len(), 1, "{line}");
+        stages.remove(0)
+    }
+
+    #[test]
+    fn splits_pipelines_and_stages() {
+        assert_eq!(words("ls  -l\t/tmp"), [[["ls", "-l", "/tmp"]]]);
+        assert_eq!(
+            words("a | b c;d|e ;"),
+            [vec![vec!["a"], vec!["b", "c"]], vec![vec!["d"], vec!["e"]]]
+        );
+        assert!(parse("").unwrap().is_empty());
+        assert!(parse("   ").unwrap().is_empty());
+    }
+
+    #[test]
+    fn quotes_keep_spaces_and_operators() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo 'a | b' \"c > d; e\"", &["echo", "a | b", "c > d; e"]),
+            ("echo a' 'b\"c\"d", &["echo", "a bcd"]),
+            ("echo '' \"\"", &["echo", "", ""]),
+            ("echo '$HOME \\n'", &["echo", "$HOME \\n"]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(words(line), [[expected.to_vec()]], "{line}");
+        }
+    }
+
+    #[test]
+    fn backslashes_escape_one_character() {
+        let cases: &[(&str, &[&str])] = &[
+            ("echo a\\ b \\| \\> \\\\", &["echo", "a b", "|", ">", "\\"]),
+            ("echo \"a\\\"b\" \"\\$x\" \"\\\\\" \"\\n\"", &["echo", "a\"b", "$x", "\\", "\\n"]),
+            ("echo \\'", &["echo", "'"]),
+            // A backslash at the end of the line is dropped
+            ("echo a\\", &["echo", "a"]),
+        ];
+        for (line, expected) in cases {
+            assert_eq!(words(line), [[expected.to_vec()]], "{line}");
+        }
+    }
+
+    #[test]
+    fn split_words_keeps_the_quoting() {
+        let words = split_words("echo \"~\" '~/a b' ~/c").unwrap();
+        let words: Vec<(&str, bool)> = words.iter().map(|word| (word.text.as_str(), word.quoted_prefix)).collect();
+        assert_eq!(words, [("echo", false), ("~", true), ("~/a b", true), ("~/c", false)]);
+        assert_eq!(split_words("ls | less").unwrap_err(), "syntax error near unexpected token `|'");
+        assert_eq!(split_words("echo 'x").unwrap_err(), "unclosed quote");
+    }
+
+    #[test]
+    fn remembers_a_quoted_prefix() {
+        let cases = [
+            ("~/x", false),
+            ("'~'", true),
+            ("\"~/x\"", true),
+            ("\\~/x", true),
+            ("~/'x y'", false),
+            ("a/b'c'", false),
+        ];
+        for (line, quoted_prefix) in cases {
+            assert_eq!(stage(line).words[0].quoted_prefix, quoted_prefix, "{line}");
+        }
+    }
+
+    #[test]
+    fn takes_the_redirections_out_of_the_words() {
+        let parsed = stage("sort < in > out 2> err");
+        assert_eq!(parsed.words, [word("sort")]);
+        assert_eq!(
+            parsed.redirects,
+            [
+                Redirect::Input(word("in")),
+                Redirect::Output { fd: 1, path: word("out"), append: false },
+                Redirect::Output { fd: 2, path: word("err"), append: false },
+            ]
+        );
+
+        let parsed = stage("make >>log 2>>errors 1>out &> both &>>all 2>&1 >&2");
+        assert_eq!(parsed.words, [word("make")]);
+        assert_eq!(
+            parsed.redirects,
+            [
+                Redirect::Output { fd: 1, path: word("log"), append: true },
+                Redirect::Output { fd: 2, path: word("errors"), append: true },
+                Redirect::Output { fd: 1, path: word("out"), append: false },
+                Redirect::Both { path: word("both"), append: false },
+                Redirect::Both { path: word("all"), append: true },
+                Redirect::Dup { fd: 2, to: 1 },
+                Redirect::Dup { fd: 1, to: 2 },
+            ]
+        );
+    }
+
+    #[test]
+    fn only_a_lone_unquoted_digit_is_a_descriptor() {
+        let cases: &[(&str, &[&str], u32)] = &[
+            ("echo 2>x", &["echo"], 2),
+            ("echo a2>x", &["echo", "a2"], 1),
+            ("echo '2'>x", &["echo", "2"], 1),
+            ("echo 3>x", &["echo", "3"], 1),
+        ];
+        for (line, expected, fd) in cases {
+            let parsed = stage(line);
+            let texts: Vec<&str> = parsed.words.iter().map(|word| word.text.as_str()).collect();
+            assert_eq!(texts, *expected, "{line}");
+            assert_eq!(parsed.redirects, [Redirect::Output { fd: *fd, path: word("x"), append: false }], "{line}");
+        }
+    }
+
+    #[test]
+    fn a_redirect_target_can_be_quoted() {
+        let parsed = stage("echo > 'my file' < \"~/in\"");
+        assert_eq!(
+            parsed.redirects,
+            [
+                Redirect::Output { fd: 1, path: Word { text: String::from("my file"), quoted_prefix: true }, append: false },
+                Redirect::Input(Word { text: String::from("~/in"), quoted_prefix: true }),
+            ]
+        );
+        // A stage can be only redirections
+        assert_eq!(stage("> out").redirects, [Redirect::Output { fd: 1, path: word("out"), append: false }]);
+    }
+
+    #[test]
+    fn rejects_empty_stages() {
+        let cases = [
+            ("| a", "|"),
+            ("a || b", "|"),
+            ("a |", "newline"),
+            ("a | ;", ";"),
+            ("; a", ";"),
+            ("a ;; b", ";"),
+        ];
+        for (line, token) in cases {
+            assert_eq!(parse(line), Err(format!("syntax error near unexpected token `{token}'")), "{line}");
+        }
+    }
+
+    #[test]
+    fn rejects_a_missing_redirect_target() {
+        let cases = [
+            ("cat <", "newline"),
+            ("echo >", "newline"),
+            ("echo > | cat", "|"),
+            ("echo >> ; ls", ";"),
+            ("echo > > x", ">"),
+            ("echo 2> &> x", "&>"),
+        ];
+        for (line, token) in cases {
+            assert_eq!(parse(line), Err(format!("syntax error near unexpected token `{token}'")), "{line}");
+        }
+    }
+
+    #[test]
+    fn rejects_unclosed_quotes_and_unknown_descriptors() {
+        assert_eq!(parse("echo 'a"), Err(String::from("unclosed quote")));
+        assert_eq!(parse("echo \"a\\\""), Err(String::from("unclosed quote")));
+        assert_eq!(parse("echo >&3"), Err(String::from("only >&1 and >&2 are supported")));
+    }
+}
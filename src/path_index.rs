@@ -0,0 +1,313 @@
+use is_executable::IsExecutable;
+use log::{error, info};
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    ffi::{OsStr, OsString},
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// The PATH value and its directories with their mtime. When it changes, something
+/// was installed or removed, or PATH itself changed, and the index is out of date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stamp {
+    path_var: OsString,
+    dirs: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+fn modified(dir: &Path) -> Option<SystemTime> {
+    fs::metadata(dir).and_then(|md| md.modified()).ok()
+}
+
+impl Stamp {
+    /// The stamp of PATH as it is now.
+    pub fn now() -> Self {
+        Stamp::of(env::var_os("PATH").unwrap_or_default())
+    }
+
+    /// The stamp of a PATH value.
+    fn of(path_var: OsString) -> Self {
+        let dirs = env::split_paths(&path_var)
+            .map(|dir| {
+                let mtime = modified(&dir);
+                (dir, mtime)
+            })
+            .collect();
+        Stamp { path_var, dirs }
+    }
+}
+
+/// The executables of the PATH directories of a stamp. It is made without the
+/// index, so the index isn't locked while the directories are read.
+pub struct Scan {
+    stamp: Stamp,
+    commands: HashMap<OsString, PathBuf>,
+}
+
+impl Scan {
+    /// Reads every PATH directory of `stamp`. When a name is in more than one
+    /// directory, the first directory of PATH wins. The mtimes were taken before, so
+    /// a change made while reading is seen by the next stamp.
+    pub fn new(stamp: Stamp) -> Self {
+        let mut commands = HashMap::new();
+
+        let mut dont_exists_count = 0;
+        for (dir, _) in &stamp.dirs {
+            info!("path_index::Scan::new(): Reading {}...", dir.to_string_lossy());
+            match dir.read_dir() {
+                Ok(read_obj) => {
+                    for entry in read_obj.flatten() {
+                        let path = entry.path();
+                        if path.is_executable() && !path.is_dir() {
+                            commands.entry(entry.file_name()).or_insert(path);
+                        }
+                    }
+                }
+                Err(err) if dir.exists() => {
+                    error!("path_index::Scan::new(): Error while trying to read this dir '{}' located in the PATH enviroment variable", dir.to_string_lossy());
+                    error!("path_index::Scan::new(): {err}");
+                }
+                Err(_) => dont_exists_count += 1,
+            }
+        }
+        if dont_exists_count > 0 {
+            info!("path_index::Scan::new(): {dont_exists_count} folder/folders doesn't exists in the PATH environment variable");
+        }
+        info!("path_index::Scan::new(): Indexed {} executables", commands.len());
+        Scan { stamp, commands }
+    }
+}
+
+/// Index of the executables found in the PATH directories.
+///
+/// The index is built lazily: it is scanned again when its stamp is out of date,
+/// that is when PATH changes or when the mtime of one of its directories changes
+/// (something was installed or removed), so commands installed while the shell
+/// runs are found. A file can also become executable without touching its
+/// directory (`chmod +x`), so a name missing from an up to date index scans again
+/// too, once per name until the stamp changes.
+#[derive(Debug, Default)]
+pub struct CommandIndex {
+    /// The stamp of PATH when the index was built. `None` means it was never scanned.
+    stamp: Option<Stamp>,
+    /// The file names are kept as they are, a name that isn't UTF-8 can't be typed but
+    /// must not shadow another one either.
+    commands: HashMap<OsString, PathBuf>,
+    /// How many times every command was looked up, shown by `hash`.
+    hits: HashMap<String, usize>,
+    /// The names that were still missing after scanning again for them, so a typo
+    /// doesn't scan PATH every time it is typed.
+    missed: HashSet<String>,
+}
+
+impl CommandIndex {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// True if the index was built from `stamp`.
+    pub fn is_current(&self, stamp: &Stamp) -> bool {
+        self.stamp.as_ref() == Some(stamp)
+    }
+
+    /// Replaces the executables with the ones of `scan`. The misses are forgotten
+    /// when PATH changed.
+    pub fn update(&mut self, scan: Scan) {
+        if !self.is_current(&scan.stamp) {
+            self.missed.clear();
+        }
+        self.stamp = Some(scan.stamp);
+        self.commands = scan.commands;
+    }
+
+    /// Finds `name` in the index without counting it as a use, the caller makes
+    /// sure it is up to date.
+    pub fn find(&self, name: &str) -> Option<PathBuf> {
+        // The entry may point to a file that was deleted since the last scan
+        self.commands.get(OsStr::new(name)).filter(|path| path.is_executable()).cloned()
+    }
+
+    /// Like `find`, counting a hit for `hash`.
+    pub fn lookup(&mut self, name: &str) -> Option<PathBuf> {
+        let found = self.find(name);
+        if found.is_some() {
+            *self.hits.entry(name.to_string()).or_insert(0) += 1;
+        }
+        found
+    }
+
+    /// True if `name` was missing even after scanning again for it.
+    pub fn was_missed(&self, name: &str) -> bool {
+        self.missed.contains(name)
+    }
+
+    /// Remembers that `name` is missing from an index scanned again for it.
+    pub fn note_miss(&mut self, name: &str) {
+        self.missed.insert(name.to_string());
+    }
+
+    /// Forgets the misses, the next lookup of any name may scan again.
+    pub fn forget_misses(&mut self) {
+        self.missed.clear();
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.commands
+            .keys()
+            .filter_map(|name| name.to_str())
+            .map(String::from)
+            .collect()
+    }
+
+    /// The commands that have been used, with their hits and paths, sorted by name.
+    pub fn remembered(&self) -> Vec<(String, usize, PathBuf)> {
+        let mut remembered: Vec<(String, usize, PathBuf)> = self
+            .hits
+            .iter()
+            .filter_map(|(name, hits)| {
+                self.commands
+                    .get(OsStr::new(name))
+                    .map(|path| (name.clone(), *hits, path.clone()))
+            })
+            .collect();
+        remembered.sort();
+        remembered
+    }
+
+    /// Forgets the hits of `name`. Returns false if it was not remembered.
+    pub fn forget(&mut self, name: &str) -> bool {
+        self.hits.remove(name).is_some()
+    }
+
+    /// Forgets everything, the next lookup scans PATH again.
+    pub fn clear(&mut self) {
+        self.stamp = None;
+        self.commands.clear();
+        self.hits.clear();
+        self.missed.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    fn install(dir: &Path, name: &str) {
+        let path = dir.join(name);
+        fs::write(&path, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    /// Moves the mtime of `dir` forward, a file created in the same tick could leave
+    /// it as it was.
+    fn touch(dir: &Path) {
+        let later = modified(dir).unwrap() + Duration::from_secs(1);
+        fs::File::open(dir).unwrap().set_modified(later).unwrap();
+    }
+
+    fn scratch(name: &str) -> (PathBuf, PathBuf, PathBuf) {
+        let root = scratch_dir(&format!("path-index-{name}"));
+        let (first, second) = (root.join("first"), root.join("second"));
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        (root, first, second)
+    }
+
+    fn path_var(dirs: &[&Path]) -> OsString {
+        env::join_paths(dirs).unwrap()
+    }
+
+    #[test]
+    fn an_unchanged_stamp_keeps_the_index() {
+        let (root, first, second) = scratch("unchanged");
+        install(&first, "tool");
+        let var = path_var(&[&first, &second]);
+        let mut index = CommandIndex::new();
+        assert!(!index.is_current(&Stamp::of(var.clone())));
+
+        index.update(Scan::new(Stamp::of(var.clone())));
+        assert!(index.is_current(&Stamp::of(var.clone())));
+        // Reading a directory doesn't change its mtime
+        assert_eq!(index.lookup("tool"), Some(first.join("tool")));
+        assert!(index.is_current(&Stamp::of(var)));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_new_command_makes_the_stamp_change() {
+        let (root, first, second) = scratch("install");
+        let var = path_var(&[&first, &second]);
+        let mut index = CommandIndex::new();
+        index.update(Scan::new(Stamp::of(var.clone())));
+        assert_eq!(index.lookup("tool"), None);
+
+        install(&second, "tool");
+        touch(&second);
+        let stamp = Stamp::of(var);
+        assert!(!index.is_current(&stamp));
+        index.update(Scan::new(stamp));
+        assert_eq!(index.lookup("tool"), Some(second.join("tool")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_new_path_makes_the_stamp_change() {
+        let (root, first, second) = scratch("path");
+        install(&first, "tool");
+        install(&second, "tool");
+        let mut index = CommandIndex::new();
+        index.update(Scan::new(Stamp::of(path_var(&[&first, &second]))));
+        assert_eq!(index.lookup("tool"), Some(first.join("tool")));
+
+        // The same directories in another order
+        let stamp = Stamp::of(path_var(&[&second, &first]));
+        assert!(!index.is_current(&stamp));
+        index.update(Scan::new(stamp));
+        assert_eq!(index.lookup("tool"), Some(second.join("tool")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn the_misses_last_until_the_stamp_changes() {
+        let (root, first, second) = scratch("misses");
+        let var = path_var(&[&first, &second]);
+        let mut index = CommandIndex::new();
+        index.update(Scan::new(Stamp::of(var.clone())));
+        index.note_miss("tool");
+
+        // Scanning again with the same stamp keeps the miss
+        index.update(Scan::new(Stamp::of(var.clone())));
+        assert!(index.was_missed("tool"));
+
+        install(&first, "tool");
+        touch(&first);
+        index.update(Scan::new(Stamp::of(var)));
+        assert!(!index.was_missed("tool"));
+        assert_eq!(index.lookup("tool"), Some(first.join("tool")));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn a_directory_that_appears_makes_the_stamp_change() {
+        let (root, first, _) = scratch("appears");
+        let missing = root.join("missing");
+        let var = path_var(&[&first, &missing]);
+        let mut index = CommandIndex::new();
+        index.update(Scan::new(Stamp::of(var.clone())));
+        assert!(index.is_current(&Stamp::of(var.clone())));
+
+        fs::create_dir(&missing).unwrap();
+        assert!(!index.is_current(&Stamp::of(var)));
+
+        let _ = fs::remove_dir_all(&root);
+    }
+}
@@ -0,0 +1,344 @@
+use log::{error, info};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, PipeReader};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::Child;
+use std::sync::atomic::Ordering;
+use std::thread::{self, JoinHandle};
+
+use crate::builtins::{registry, Builtin, ExitStatus, Input, Io, Output, ShellState};
+use crate::commands::{ExecError, ExternalCommands};
+use crate::parser::{self, Pipeline, Redirect, Word};
+use crate::resolve::{CommandKind, Lookup};
+use crate::{expand, script_loader, suggest};
+
+/// What a stage of a pipeline runs.
+enum Job {
+    Builtin(&'static dyn Builtin),
+    Function,
+    External(PathBuf),
+}
+
+/// The reason of a failed file operation, without the "(os error N)" of `io::Error`.
+pub fn describe(err: &io::Error) -> String {
+    match err.kind() {
+        io::ErrorKind::NotFound => String::from("No such file or directory"),
+        io::ErrorKind::PermissionDenied => String::from("Permission denied"),
+        io::ErrorKind::IsADirectory => String::from("Is a directory"),
+        io::ErrorKind::NotADirectory => String::from("Not a directory"),
+        _ => err.to_string(),
+    }
+}
+
+fn open_output(path: &Word, append: bool) -> Result<File, String> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(expand::expand_word(path))
+        .map_err(|err| format!("{}: {}", path.text, describe(&err)))
+}
+
+/// Points the handles of `io` to the files of the redirections, from left to right
+/// like `sh` does, so `> file 2>&1` sends both outputs to the file.
+fn apply_redirects(redirects: &[Redirect], io: &mut Io) -> Result<(), String> {
+    for redirect in redirects {
+        match redirect {
+            Redirect::Input(path) => {
+                let file = File::open(expand::expand_word(path))
+                    .map_err(|err| format!("{}: {}", path.text, describe(&err)))?;
+                io.stdin = Input::File(file);
+            }
+            Redirect::Output { fd, path, append } => {
+                let file = Output::File(open_output(path, *append)?);
+                if *fd == 2 {
+                    io.stderr = file;
+                } else {
+                    io.stdout = file;
+                }
+            }
+            Redirect::Both { path, append } => {
+                let file = open_output(path, *append)?;
+                let copy = file.try_clone().map_err(|err| format!("{}: {}", path.text, describe(&err)))?;
+                io.stdout = Output::File(file);
+                io.stderr = Output::File(copy);
+            }
+            Redirect::Dup { fd, to } => {
+                let target = if *to == 2 { &io.stderr } else { &io.stdout };
+                let target = target.try_clone().map_err(|err| format!("{fd}>&{to}: {}", describe(&err)))?;
+                if *fd == 2 {
+                    io.stderr = target;
+                } else {
+                    io.stdout = target;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Expands aliases and `~`, and takes out the `command` and `builtin` words, which
+/// only change how the next word is looked up.
+fn expand_stage(words: Vec<Word>, state: &ShellState) -> (Vec<OsString>, Lookup) {
+    let words = expand::expand_alias(words, &state.configs.terminal_config.alias);
+    let mut words = expand::expand_words(words);
+
+    let mut lookup = Lookup::NO_ALIASES;
+    loop {
+        match words.first().and_then(|word| word.to_str()) {
+            Some("command") if !words.get(1).is_some_and(|arg| arg.as_bytes().starts_with(b"-")) => {
+                words.remove(0);
+                lookup = Lookup::COMMAND;
+            }
+            Some("builtin") => {
+                words.remove(0);
+                lookup = Lookup::BUILTIN;
+            }
+            _ => break,
+        }
+    }
+    (words, lookup)
+}
+
+/// Tells the user `words` is not a command. A single command can be handled by the
+/// command-not-found handler of the scripts instead.
+fn command_not_found(words: &[String], single: bool, state: &ShellState) {
+    let name = &words[0];
+    let handled = single
+        && state.lua.as_ref().is_some_and(|lua| {
+            script_loader::run_command_not_found_handler(
+                lua,
+                &state.configs.terminal_config.command_not_found_handler,
+                words,
+            )
+        });
+    if handled {
+        return;
+    }
+    eprintln!("yarp: unknown command: {name}");
+    // The names of the shell come before the commands of PATH, they are more likely
+    let mut candidates: Vec<String> = registry().names().into_iter().map(String::from).collect();
+    candidates.extend(state.configs.terminal_config.alias.keys().cloned());
+    if let Some(lua) = &state.lua {
+        candidates.extend(script_loader::function_names(lua));
+    }
+    candidates.extend(state.external.command_names());
+    if let Some(hint) = suggest::did_you_mean(name, &candidates) {
+        eprintln!("yarp: {hint}");
+    }
+}
+
+/// The words given to a builtin or a function, which only take text.
+fn texts(words: Vec<OsString>) -> Vec<String> {
+    words
+        .into_iter()
+        .map(|word| word.into_string().unwrap_or_else(|word| word.to_string_lossy().into_owned()))
+        .collect()
+}
+
+/// Finds what `words` runs. The errors are reported here and turned into the status
+/// of the stage. `single` is true when the stage is the whole command line.
+fn find_job(mut words: Vec<OsString>, lookup: Lookup, single: bool, state: &ShellState) -> Result<(Job, Vec<OsString>), ExitStatus> {
+    let name = words[0].to_string_lossy().into_owned();
+    // PATH is searched below, so the hits of `hash` are only counted once
+    match state.resolver().resolve(&name, Lookup { external: false, ..lookup }) {
+        Some(CommandKind::Function) => return Ok((Job::Function, words)),
+        Some(CommandKind::Builtin) => {
+            if let Some(builtin) = registry().get(&name) {
+                return Ok((Job::Builtin(builtin), words));
+            }
+        }
+        _ => {}
+    }
+    if !lookup.external {
+        eprintln!("builtin: {name}: not a shell builtin");
+        return Err(ExitStatus::FAILURE);
+    }
+
+    // With auto_cd, a lone directory name that isn't a command works like cd
+    if single
+        && words.len() == 1
+        && state.configs.terminal_config.auto_cd
+        && Path::new(&words[0]).is_dir()
+        && state
+            .external
+            .locate_command(&name)
+            .is_none_or(|found| found.is_dir())
+    {
+        if let Some(cd) = registry().get("cd") {
+            words.insert(0, OsString::from("cd"));
+            return Ok((Job::Builtin(cd), words));
+        }
+    }
+
+    // Only a path can fail to be text, after `~` is expanded to a home that isn't UTF-8
+    let resolved = match words[0].to_str() {
+        Some(name) => state.external.resolve_command(name),
+        None => ExternalCommands::resolve_path(Path::new(&words[0])),
+    };
+    match resolved {
+        Ok(path) => Ok((Job::External(path), words)),
+        Err(ExecError::NotFound(_)) => {
+            command_not_found(&texts(words), single, state);
+            Err(ExitStatus(127))
+        }
+        Err(err) => {
+            eprintln!("yarp: {err}");
+            Err(ExitStatus(126))
+        }
+    }
+}
+
+/// Waits for `child` and returns its status, `128 + signal` if it was killed.
+pub fn wait_child(child: &mut Child) -> ExitStatus {
+    match child.wait() {
+        Ok(status) => ExitStatus(
+            status
+                .code()
+                .or_else(|| status.signal().map(|signal| 128 + signal))
+                .unwrap_or(1),
+        ),
+        Err(err) => {
+            error!("pipeline::wait_child(): Cannot wait for the command");
+            error!("pipeline::wait_child(): {err}");
+            eprintln!("yarp: Cannot wait for the command...");
+            ExitStatus::FAILURE
+        }
+    }
+}
+
+/// Runs every stage of `pipeline` connected by pipes and returns the status of the
+/// last one. External commands run as processes, builtins that are not the last
+/// stage run in a thread with a copy of the state, like a subshell.
+pub fn run_pipeline(pipeline: Pipeline, state: &mut ShellState) -> ExitStatus {
+    let count = pipeline.stages.len();
+    let single = count == 1;
+    let mut previous: Option<PipeReader> = None;
+    let mut children: Vec<Child> = vec![];
+    let mut threads: Vec<JoinHandle<ExitStatus>> = vec![];
+    let mut last_child = None;
+    let mut status = ExitStatus::SUCCESS;
+
+    for (index, stage) in pipeline.stages.into_iter().enumerate() {
+        let last = index + 1 == count;
+        let stdin = previous.take().map_or(Input::Stdin, Input::Pipe);
+        let stdout = if last {
+            Output::Stdout
+        } else {
+            match io::pipe() {
+                Ok((reader, writer)) => {
+                    previous = Some(reader);
+                    Output::Pipe(writer)
+                }
+                Err(err) => {
+                    error!("pipeline::run_pipeline(): Cannot create a pipe: {err}");
+                    eprintln!("yarp: Cannot create a pipe: {err}");
+                    status = ExitStatus::FAILURE;
+                    break;
+                }
+            }
+        };
+        let mut io = Io { stdin, stdout, stderr: Output::Stderr };
+        if let Err(err) = apply_redirects(&stage.redirects, &mut io) {
+            eprintln!("yarp: {err}");
+            status = ExitStatus::FAILURE;
+            continue;
+        }
+
+        let (words, lookup) = expand_stage(stage.words, state);
+        if words.is_empty() {
+            // Only redirections, the files were created and that's all
+            status = ExitStatus::SUCCESS;
+            continue;
+        }
+        let whole_line = single && stage.redirects.is_empty();
+        let (job, words) = match find_job(words, lookup, whole_line, state) {
+            Ok(found) => found,
+            Err(failed) => {
+                status = failed;
+                continue;
+            }
+        };
+
+        status = ExitStatus::SUCCESS;
+        match job {
+            Job::Function => {
+                let words = texts(words);
+                let Some(lua) = state.lua.as_ref().filter(|_| whole_line) else {
+                    eprintln!("yarp: {}: functions can't be used in pipelines or with redirections", words[0]);
+                    status = ExitStatus::FAILURE;
+                    continue;
+                };
+                if let Err(err) = script_loader::call_function(lua, &words[0], &words[1..]) {
+                    eprintln!("yarp: {}: {err}", words[0]);
+                    status = ExitStatus::FAILURE;
+                }
+            }
+            Job::Builtin(builtin) if last => {
+                info!("pipeline::run_pipeline(): Running the builtin {}", builtin.name());
+                status = builtin.run(texts(words), &mut io, state);
+                io.flush();
+            }
+            Job::Builtin(builtin) => {
+                info!("pipeline::run_pipeline(): Running the builtin {} in a thread", builtin.name());
+                let mut subshell = state.subshell();
+                let words = texts(words);
+                threads.push(thread::spawn(move || {
+                    let status = builtin.run(words, &mut io, &mut subshell);
+                    io.flush();
+                    status
+                }));
+            }
+            Job::External(path) => match ExternalCommands::spawn(&path, &words[1..], &io) {
+                Ok(child) => {
+                    state.foreground_pid.store(child.id(), Ordering::SeqCst);
+                    if last {
+                        last_child = Some(children.len());
+                    }
+                    children.push(child);
+                }
+                Err(err) => {
+                    eprintln!("yarp: {err}");
+                    status = ExitStatus(126);
+                }
+            },
+        }
+    }
+
+    for (index, child) in children.iter_mut().enumerate() {
+        let child_status = wait_child(child);
+        if last_child == Some(index) {
+            status = child_status;
+        }
+    }
+    state.foreground_pid.store(0, Ordering::SeqCst);
+    for thread in threads {
+        if thread.join().is_err() {
+            error!("pipeline::run_pipeline(): A builtin of the pipeline panicked");
+        }
+    }
+    status
+}
+
+/// Runs a command line: every pipeline separated by `;`, one after the other.
+pub fn run_line(line: &str, state: &mut ShellState) -> ExitStatus {
+    let pipelines = match parser::parse(line) {
+        Ok(pipelines) => pipelines,
+        Err(err) => {
+            eprintln!("yarp: {err}");
+            return ExitStatus::USAGE;
+        }
+    };
+    let mut status = ExitStatus::SUCCESS;
+    for pipeline in pipelines {
+        status = run_pipeline(pipeline, state);
+        if state.exit_requested {
+            break;
+        }
+    }
+    status
+}
@@ -0,0 +1,93 @@
+use rlua::Lua;
+use std::{collections::HashMap, path::PathBuf};
+
+use crate::builtins::registry;
+use crate::commands::ExternalCommands;
+use crate::script_loader;
+
+/// What a command name runs.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CommandKind {
+    Alias(String),
+    Function,
+    Builtin,
+    External(PathBuf),
+}
+
+/// Which kinds of commands a lookup may find.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Lookup {
+    pub aliases: bool,
+    pub functions: bool,
+    pub builtins: bool,
+    pub external: bool,
+}
+
+impl Lookup {
+    pub const ALL: Lookup = Lookup { aliases: true, functions: true, builtins: true, external: true };
+    /// Used by the dispatcher, the aliases have already been expanded at that point.
+    pub const NO_ALIASES: Lookup = Lookup { aliases: false, ..Lookup::ALL };
+    /// `command name`: skips aliases and functions.
+    pub const COMMAND: Lookup = Lookup { aliases: false, functions: false, ..Lookup::ALL };
+    /// `builtin name`: only builtins.
+    pub const BUILTIN: Lookup = Lookup { aliases: false, functions: false, builtins: true, external: false };
+}
+
+/// The kinds of commands, in the order a name is looked up.
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Alias,
+    Function,
+    Builtin,
+    External,
+}
+
+const ORDER: [Step; 4] = [Step::Alias, Step::Function, Step::Builtin, Step::External];
+
+/// Resolves command names in the same order the dispatcher in `pipeline.rs` runs them:
+/// aliases, then functions registered by the scripts, then builtins, then PATH.
+/// Resolving only reports, it doesn't count as a use in the `hash` table.
+pub struct Resolver<'a> {
+    aliases: &'a HashMap<String, String>,
+    /// `None` when resolving from a script, the functions are not reachable there.
+    lua: Option<&'a Lua>,
+    external: &'a ExternalCommands,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(aliases: &'a HashMap<String, String>, lua: Option<&'a Lua>, external: &'a ExternalCommands) -> Self {
+        Resolver { aliases, lua, external }
+    }
+
+    /// What `name` is as the kind of command of `step`. With `every_file`, all the
+    /// files of PATH named `name` are given instead of only the first one.
+    fn find(&self, step: Step, name: &str, lookup: Lookup, every_file: bool) -> Vec<CommandKind> {
+        match step {
+            Step::Alias if lookup.aliases => {
+                self.aliases.get(name).map(|value| CommandKind::Alias(value.clone())).into_iter().collect()
+            }
+            Step::Function if lookup.functions && self.lua.is_some_and(|lua| script_loader::has_function(lua, name)) => {
+                vec![CommandKind::Function]
+            }
+            Step::Builtin if lookup.builtins && registry().contains(name) => vec![CommandKind::Builtin],
+            Step::External if lookup.external && every_file => {
+                self.external.find_all(name).into_iter().map(CommandKind::External).collect()
+            }
+            Step::External if lookup.external => {
+                self.external.locate_command(name).map(CommandKind::External).into_iter().collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// What `name` runs, the first of `resolve_all`.
+    pub fn resolve(&self, name: &str, lookup: Lookup) -> Option<CommandKind> {
+        ORDER.iter().find_map(|step| self.find(*step, name, lookup, false).into_iter().next())
+    }
+
+    /// Every way `name` can be run, in the order they are tried. The first one is
+    /// the one that runs, the rest are shadowed by it.
+    pub fn resolve_all(&self, name: &str, lookup: Lookup) -> Vec<CommandKind> {
+        ORDER.iter().flat_map(|step| self.find(*step, name, lookup, true)).collect()
+    }
+}
@@ -1,20 +1,25 @@
-use rlua::{Lua,UserData, UserDataMethods, Error};
-use crate::{setup::{self, YamlConfiguration}, commands::ExternalCommands};
-use log::{error, info};
-use std::{fs::File, io::{Read}, path::{PathBuf}, process::Child, sync::Arc};
+use rlua::{AnyUserData, Context, Error, Function, Lua, Table, UserData, UserDataMethods, Variadic};
+use crate::{setup::{self, YamlConfiguration}, builtins::{self, DirStack, ShellState}, commands::ExternalCommands, expand, frecency, pipeline};
+use log::error;
+use std::{collections::BTreeMap, fs::File, io::{Read}, path::{PathBuf}, sync::{atomic::AtomicU32, Arc, Mutex}};
 
+/// The aliases are saved in the config file, the shell reads them from there when it
+/// changes.
 #[derive(Clone)]
-pub struct Alias {
-    config: YamlConfiguration,
-}
+pub struct Alias {}
 
 impl Alias {
     pub fn new() -> Self {
-        Alias { config: setup::load_conf() }
+        Alias {}
     }
-    pub fn set_alias(&mut self, cmd: &str, alias: &str) {
-        self.config.terminal_config.alias.insert(String::from(cmd), String::from(alias));
-        setup::write_conf(self.config.clone());
+    pub fn set_alias(&self, cmd: &str, alias: &str) {
+        let written = setup::update_conf(|configs| {
+            configs.terminal_config.alias.insert(String::from(cmd), String::from(alias));
+            Ok(())
+        });
+        if let Err(err) = written {
+            error!("script_loader::Alias::set_alias(): Cannot save the alias {cmd}: {err}");
+        }
     }
 }
 
@@ -25,8 +30,8 @@ impl UserData for Alias {
             Ok(Alias::new())
         });
         methods.add_method("set_alias", |_, this, (cmd, alias): (String, String)| {
-            let mut self_clone = this.clone();
-            Ok(self_clone.set_alias(&cmd, &alias))
+            this.set_alias(&cmd, &alias);
+            Ok(())
         });
     }
 }
@@ -46,14 +51,14 @@ pub fn read_file(file: PathBuf) -> Result<String, String> {
                         Ok(buffer)
                     },
                     Err(err) => {
-                        error!("script_loader::read_file(): Error while trying to read {}", file.file_name().unwrap().to_str().unwrap());
+                        error!("script_loader::read_file(): Error while trying to read {}", file.to_string_lossy());
                         error!("script_loader::read_file(): {err}");
                         Err(err.to_string())
                     }
                 }
             },
             Err(err) => {
-                error!("script_loader::read_file(): Error while trying to read {}", file.file_name().unwrap().to_str().unwrap());
+                error!("script_loader::read_file(): Error while trying to read {}", file.to_string_lossy());
                 error!("script_loader::read_file(): {err}");
                 Err(err.to_string())
             }
@@ -63,71 +68,282 @@ pub fn read_file(file: PathBuf) -> Result<String, String> {
     }
 }
 
-pub fn load(files: Vec<PathBuf>, external_cmds_obj: ExternalCommands) -> () {
+/// Registry key of the table holding the functions registered with `register_function()`.
+const FUNCTIONS_KEY: &str = "yarsh_functions";
+/// Registry key of the table with the description of the registered functions.
+const DOCS_KEY: &str = "yarsh_function_docs";
+/// Registry key of the callback set with `on_command_not_found()`.
+const NOT_FOUND_KEY: &str = "yarsh_command_not_found";
+/// Registry key of the configs of the shell, set with `set_configs()`.
+const CONFIGS_KEY: &str = "yarsh_configs";
+
+/// Set when a command run by `exec()` asked for `config reload`. The shell reads
+/// it with `take_reload_request()`.
+const RELOAD_KEY: &str = "yarsh_reload_requested";
+
+/// The configs the shell is using, kept in the lua state for the commands the
+/// scripts run.
+struct Configs(YamlConfiguration);
+
+impl UserData for Configs {}
+
+/// Gives the scripts the configs the shell is using now, called each time they are
+/// read again.
+pub fn set_configs(lua: &Lua, configs: &YamlConfiguration) {
+    lua.context(|ctx| {
+        let set = ctx
+            .create_userdata(Configs(configs.clone()))
+            .and_then(|configs| ctx.set_named_registry_value(CONFIGS_KEY, configs));
+        if let Err(err) = set {
+            error!("script_loader::set_configs(): Cannot give the configs to the scripts: {err}");
+        }
+    });
+}
+
+fn set_global_function<'lua>(ctx: Context<'lua>, name: &str, func: rlua::Result<Function<'lua>>) {
+    match func {
+        Ok(func) => {
+            if let Err(err) = ctx.globals().set(name, func) {
+                error!("script_loader::load(): Error while trying to set the {name}() command to the lua context");
+                error!("script_loader::load(): {err}");
+            }
+        }
+        Err(err) => {
+            error!("script_loader::load(): Error while trying to create the {name}() command");
+            error!("script_loader::load(): {err}");
+        }
+    }
+}
+
+/// Runs a command line from `exec()` and returns its exit status. The command runs
+/// like in the prompt, except that the functions of the scripts are not reachable.
+fn exec_line(ctx: Context, line: &str, external: &ExternalCommands, dir_stack: &Arc<Mutex<DirStack>>) -> rlua::Result<i32> {
+    let configs = ctx.named_registry_value::<_, AnyUserData>(CONFIGS_KEY)?.borrow::<Configs>()?.0.clone();
+    let mut state = ShellState {
+        configs,
+        external: external.clone(),
+        dir_stack: dir_stack.clone(),
+        lua: None,
+        exit_requested: false,
+        reload_requested: false,
+        foreground_pid: Arc::new(AtomicU32::new(0)),
+        subshell: false,
+        function_docs: BTreeMap::new(),
+    };
+    let status = pipeline::run_line(line, &mut state).0;
+    if state.reload_requested {
+        ctx.set_named_registry_value(RELOAD_KEY, true)?;
+    }
+    Ok(status)
+}
+
+/// Whether a command run by the scripts asked for `config reload` since the last
+/// call.
+pub fn take_reload_request(lua: &Lua) -> bool {
+    lua.context(|ctx| {
+        let requested = ctx.named_registry_value::<_, Option<bool>>(RELOAD_KEY).ok().flatten().unwrap_or(false);
+        if requested {
+            if let Err(err) = ctx.unset_named_registry_value(RELOAD_KEY) {
+                error!("script_loader::take_reload_request(): {err}");
+            }
+        }
+        requested
+    })
+}
+
+/// Creates the lua state shared by every script and runs the files of `configs` in
+/// it. The state is returned so the functions and callbacks registered by the
+/// scripts can be used later.
+pub fn load(configs: &YamlConfiguration, external_cmds_obj: ExternalCommands, dir_stack: Arc<Mutex<DirStack>>) -> Lua {
     let lua_obj = Lua::new();
-    
-    for file in files {
-        lua_obj.context(|ctx| {
-            let globals = ctx.globals();
-
-            let external = external_cmds_obj.clone();
-            let execute_function = ctx.create_function(move |_, exec_name: String| {
-                let child_result_obj = external.run_external_command(&exec_name);
-                match child_result_obj {
-                    Ok(obj) => {
-                        if let Some(mut child) = obj {
-                            child.wait();
-                            Ok(())
-                        } else {
-                            Err(Error::RuntimeError(String::from("The run_external_command() returned None")))
-                        }
-                    }
-                    Err(err) => {
-                        Err(Error::RuntimeError(err.to_string()))
-                    }
-                }
-            });
+    set_configs(&lua_obj, configs);
 
-            match execute_function {
-                Ok(func) => {
-                    if let Err(err) = globals.set("exec", func) {
-                        error!("script_loader::load(): Error while trying to set the exec() command to the lua context");
-                        error!("script_loader::load(): {err}");
-                    }
+    lua_obj.context(|ctx| {
+        for key in [FUNCTIONS_KEY, DOCS_KEY] {
+            let created = ctx.create_table().and_then(|table| ctx.set_named_registry_value(key, table));
+            if let Err(err) = created {
+                error!("script_loader::load(): Error while trying to create the {key} table");
+                error!("script_loader::load(): {err}");
+            }
+        }
+
+        let external = external_cmds_obj.clone();
+        let execute_function = ctx.create_function(move |ctx, exec_name: String| {
+            exec_line(ctx, &exec_name, &external, &dir_stack)
+        });
+        set_global_function(ctx, "exec", execute_function);
+
+        let cd_function = ctx.create_function(|_, dir: String| {
+            let target = PathBuf::from(expand::expand_tilde(&dir));
+            match builtins::change_dir(&target, false) {
+                Ok(new_dir) => {
+                    frecency::record_visit(&new_dir);
+                    Ok(())
                 }
-                Err(err) => {
-                    error!("script_loader::load(): Error while trying to create the exec() command");
+                Err(err) => Err(Error::RuntimeError(err)),
+            }
+        });
+        set_global_function(ctx, "cd", cd_function);
+
+        let register_function = ctx.create_function(|ctx, (name, func, doc): (String, Function, Option<String>)| {
+            if let Some(doc) = doc {
+                let docs: Table = ctx.named_registry_value(DOCS_KEY)?;
+                docs.set(name.clone(), doc)?;
+            }
+            let functions: Table = ctx.named_registry_value(FUNCTIONS_KEY)?;
+            functions.set(name, func)
+        });
+        set_global_function(ctx, "register_function", register_function);
+
+        let not_found_function = ctx.create_function(|ctx, func: Function| {
+            ctx.set_named_registry_value(NOT_FOUND_KEY, func)
+        });
+        set_global_function(ctx, "on_command_not_found", not_found_function);
+
+        match ctx.create_userdata(Alias::new()) {
+            Ok(alias) => {
+                if let Err(err) = ctx.globals().set("Alias", alias) {
+                    error!("script_loader::load(): Error while trying to set the Alias class to the lua context");
                     error!("script_loader::load(): {err}");
                 }
             }
+            Err(err) => {
+                println!("yarsh: Cannot load lua scripts because this error: {err}");
+            }
+        }
+    });
 
-            let alias_result = ctx.create_userdata(Alias::new());
-            match alias_result {
-                Ok(alias) => {
-                    if let Err(err) = globals.set("Alias", alias) {
-                        error!("script_loader::load(): Error while trying to set the Alias class to the lua context");
-                        error!("script_loader::load(): {err}");
-                        ()
+    for file in configs.scripts_config.files.clone() {
+        lua_obj.context(|ctx| {
+            match read_file(file.clone()) {
+                Ok(source) => {
+                    if let Err(err) = ctx.load(&source).exec() {
+                        error!("script_loader::load(): Exception ocurred in file: {}", file.to_string_lossy());
+                        error!("{}", err);
+                        println!("Failed to run scripts");
                     }
-
-                    match read_file(file) {
-                        Ok(source) => {
-                            if let Err(err) = ctx.load(&source).exec() {
-                                error!("script_loader::load(): Exception ocurred in file: {}", "placeholder");
-                                error!("{}", err);
-                                println!("Failed to run scripts");
+                    // A description given to register_function() wins over the comments
+                    let Ok(docs) = ctx.named_registry_value::<_, Table>(DOCS_KEY) else {
+                        return;
+                    };
+                    for (name, doc) in doc_comments(&source) {
+                        if !docs.contains_key(name.clone()).unwrap_or(true) {
+                            if let Err(err) = docs.set(name, doc) {
+                                error!("script_loader::load(): Cannot save the description of a function: {err}");
                             }
                         }
-                        Err(_) => {
-                            println!("yarp: Failed to read script file");
-                            ()
-                        }
                     }
                 }
-                Err(err) => {
-                    println!("yarsh: Cannot load lua scripts because this error: {err}");
+                Err(_) => {
+                    println!("yarp: Failed to read script file");
                 }
             }
         });
     }
-}
\ No newline at end of file
+    lua_obj
+}
+
+/// Finds the `---` comments written right above a `register_function("name", ...)`
+/// call and returns them with the name of the function.
+fn doc_comments(source: &str) -> Vec<(String, String)> {
+    let mut found = vec![];
+    let mut comment: Vec<&str> = vec![];
+    for line in source.lines() {
+        let line = line.trim();
+        if let Some(text) = line.strip_prefix("---") {
+            comment.push(text.trim());
+            continue;
+        }
+        if !comment.is_empty() {
+            let name = line
+                .strip_prefix("register_function(")
+                .map(str::trim_start)
+                .and_then(|rest| {
+                    let quote = rest.chars().next().filter(|ch| *ch == '"' || *ch == '\'')?;
+                    rest[1..].split(quote).next()
+                });
+            if let Some(name) = name {
+                found.push((name.to_string(), comment.join("\n")));
+            }
+        }
+        comment.clear();
+    }
+    found
+}
+
+/// Description of a registered function, from `register_function()` or the `---`
+/// comments above it.
+pub fn function_doc(lua: &Lua, name: &str) -> Option<String> {
+    lua.context(|ctx| {
+        ctx.named_registry_value::<_, Table>(DOCS_KEY)
+            .and_then(|docs| docs.get::<_, Option<String>>(name))
+            .ok()
+            .flatten()
+    })
+}
+
+/// Names of the functions registered by the scripts with `register_function()`.
+pub fn function_names(lua: &Lua) -> Vec<String> {
+    lua.context(|ctx| {
+        let Ok(functions) = ctx.named_registry_value::<_, Table>(FUNCTIONS_KEY) else {
+            return vec![];
+        };
+        functions
+            .pairs::<String, Function>()
+            .flatten()
+            .map(|(name, _)| name)
+            .collect()
+    })
+}
+
+pub fn has_function(lua: &Lua, name: &str) -> bool {
+    lua.context(|ctx| {
+        ctx.named_registry_value::<_, Table>(FUNCTIONS_KEY)
+            .and_then(|functions| functions.get::<_, Option<Function>>(name))
+            .map(|func| func.is_some())
+            .unwrap_or(false)
+    })
+}
+
+/// Calls the function registered as `name`, passing every argument as a string.
+pub fn call_function(lua: &Lua, name: &str, args: &[String]) -> Result<(), String> {
+    lua.context(|ctx| {
+        let functions: Table = ctx
+            .named_registry_value(FUNCTIONS_KEY)
+            .map_err(|err| err.to_string())?;
+        let func: Function = functions.get(name).map_err(|err| err.to_string())?;
+        func.call::<_, ()>(Variadic::from_iter(args.iter().cloned()))
+            .map_err(|err| {
+                error!("script_loader::call_function(): Exception ocurred in function {name}");
+                error!("script_loader::call_function(): {err}");
+                err.to_string()
+            })
+    })
+}
+
+/// Runs the command-not-found handler with the words of the command that failed.
+/// The function named in the config is preferred over the `on_command_not_found()`
+/// callback. Returns false if there is no handler, so the caller reports the error.
+pub fn run_command_not_found_handler(lua: &Lua, configured: &Option<String>, words: &[String]) -> bool {
+    if let Some(name) = configured {
+        if has_function(lua, name) {
+            if let Err(err) = call_function(lua, name, words) {
+                println!("yarp: {name}: {err}");
+            }
+            return true;
+        }
+        error!("script_loader::run_command_not_found_handler(): The handler '{name}' is not a registered function");
+    }
+
+    lua.context(|ctx| {
+        let Ok(Some(func)) = ctx.named_registry_value::<_, Option<Function>>(NOT_FOUND_KEY) else {
+            return false;
+        };
+        if let Err(err) = func.call::<_, ()>(Variadic::from_iter(words.iter().cloned())) {
+            error!("script_loader::run_command_not_found_handler(): Exception ocurred in the callback");
+            error!("script_loader::run_command_not_found_handler(): {err}");
+            println!("yarp: command not found handler: {err}");
+        }
+        true
+    })
+}
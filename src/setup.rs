@@ -1,69 +1,185 @@
 use chrono::Utc;
 use directories::ProjectDirs;
-use log::error;
-use serde::{Deserialize, Serialize};
-use serde_yaml;
+use log::{error, info};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_path_to_error::{Path, Segment};
+use serde_yaml::{Mapping, Value};
 use std::collections::HashMap;
-use std::fs::{create_dir_all, File};
-use std::path::PathBuf;
-use std::io::{Read, Write};
+use std::fs::{self, create_dir_all, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path as FilePath, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
+use crate::layers;
+use crate::migrations::{self, CURRENT_VERSION};
+use crate::yaml_edit;
+
+use log::{Log, Metadata, Record};
 use simplelog::{
-    ColorChoice, CombinedLogger, Config, LevelFilter, TermLogger, TerminalMode, WriteLogger,
+    ColorChoice, CombinedLogger, Config, LevelFilter, SharedLogger, TermLogger, TerminalMode, WriteLogger,
 };
 
+/// The missing fields of the config file take their default values, in every
+/// section.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct YamlConfiguration {
+    /// The layout of the file, the older ones are upgraded when they are read.
+    pub version: u64,
     pub logs_configurations: LogConfig,
     pub terminal_config: TermConfig,
     pub scripts_config: ScriptsConfig,
+    pub theme: ThemeConfig,
+}
+
+impl Default for YamlConfiguration {
+    fn default() -> Self {
+        YamlConfiguration {
+            version: CURRENT_VERSION,
+            logs_configurations: LogConfig::default(),
+            terminal_config: TermConfig::default(),
+            scripts_config: ScriptsConfig::default(),
+            theme: ThemeConfig::default(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct TermConfig {
     pub prompt: String,
-    pub alias: HashMap<String, String>
+    pub alias: HashMap<String, String>,
+    pub auto_cd: bool,
+    pub command_not_found_handler: Option<String>,
+    /// The key bindings of the command line.
+    pub edit_mode: EditMode,
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug)]
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum EditMode {
+    #[default]
+    Emacs,
+    Vi,
+}
+
+impl Default for TermConfig {
+    fn default() -> Self {
+        TermConfig {
+            prompt: String::from("{}"),
+            alias: HashMap::new(),
+            auto_cd: false,
+            command_not_found_handler: None,
+            edit_mode: EditMode::default(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
 pub struct ScriptsConfig {
     pub files: Vec<PathBuf>
 }
 
+/// Colors of the file names in `ls`, the completion list and the command line.
 #[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ThemeConfig {
+    /// Read the colors of the `LS_COLORS` environment variable too.
+    pub use_ls_colors: bool,
+    /// File kinds (`directory`, `symlink`, `executable`...), `dircolors` codes
+    /// (`di`, `ln`, `ex`...) or patterns like `*.tar`, and their colors.
+    pub colors: HashMap<String, String>,
+}
+
+impl Default for ThemeConfig {
+    fn default() -> Self {
+        ThemeConfig {
+            use_ls_colors: true,
+            colors: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
 pub struct LogConfig {
     pub write_to_file: bool,
     pub write_to_stdout: bool,
+    /// The least important messages that are logged: `off`, `error`, `warn`, `info`,
+    /// `debug` or `trace`.
+    pub level: LevelFilter,
 }
 
-impl Default for YamlConfiguration {
+impl Default for LogConfig {
     fn default() -> Self {
-        YamlConfiguration {
-            logs_configurations: LogConfig {
-                write_to_file: true,
-                write_to_stdout: false,
-            },
-            terminal_config: TermConfig { 
-                prompt: String::from("{}"), 
-                alias: HashMap::new(),
-            },
-            scripts_config: ScriptsConfig { 
-                files: vec![],
-            }
+        LogConfig {
+            write_to_file: true,
+            write_to_stdout: false,
+            level: LevelFilter::Info,
         }
     }
 }
 
-pub fn setup() -> () {
-    let base_dir = ProjectDirs::from("", "", "yarp");
-    if base_dir.is_none() {
+static LOG_TO_FILE: AtomicBool = AtomicBool::new(false);
+static LOG_TO_STDOUT: AtomicBool = AtomicBool::new(false);
+
+/// A logger that only writes while `on` is set.
+struct Switch<L: SharedLogger> {
+    logger: Box<L>,
+    on: &'static AtomicBool,
+}
+
+impl<L: SharedLogger> Log for Switch<L> {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.on.load(Ordering::Relaxed) && self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if self.on.load(Ordering::Relaxed) {
+            self.logger.log(record);
+        }
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
+
+impl<L: SharedLogger + 'static> SharedLogger for Switch<L> {
+    fn level(&self) -> LevelFilter {
+        self.logger.level()
+    }
+
+    fn config(&self) -> Option<&Config> {
+        self.logger.config()
+    }
+
+    fn as_log(self: Box<Self>) -> Box<dyn Log> {
+        Box::new(*self)
+    }
+}
+
+/// Switches the loggers and sets the level of the messages, when the shell starts
+/// and when the configs are read again.
+pub fn apply_log_config(configs: &LogConfig) {
+    LOG_TO_FILE.store(configs.write_to_file, Ordering::Relaxed);
+    LOG_TO_STDOUT.store(configs.write_to_stdout, Ordering::Relaxed);
+    log::set_max_level(configs.level);
+}
+
+/// Creates the directories of the shell and the loggers. Returns the configs and
+/// the problems found in the config file, which are shown when the shell starts.
+pub fn setup() -> (YamlConfiguration, Vec<String>) {
+    let Some(base_dir) = ProjectDirs::from("", "", "yarp") else {
         println!("Couldn't initialize log file");
         error!("Default data directory is null");
-        ()
+        return read_conf();
     };
 
-    let mut log_dir = base_dir.clone().unwrap().data_dir().to_path_buf();
-    let config_dir = base_dir.unwrap().config_dir().to_path_buf();
+    let mut log_dir = base_dir.data_dir().to_path_buf();
+    let config_dir = base_dir.config_dir().to_path_buf();
     log_dir.push("logs");
 
     let mut preferences_file = config_dir.clone();
@@ -72,12 +188,10 @@ pub fn setup() -> () {
     if let Err(err) = create_dir_all(log_dir.clone()) {
         error!("Couldn't initialize log file");
         error!("{}", err);
-        ()
     }
     if let Err(err) = create_dir_all(config_dir) {
         error!("Couldn't initialize log file");
         error!("{}", err);
-        ()
     }
 
     if !preferences_file.exists() {
@@ -98,138 +212,472 @@ pub fn setup() -> () {
         //         ()
         //     }
         // }
-        write_conf(Default::default());
+        let written = serde_yaml::to_string(&YamlConfiguration::default())
+            .map_err(|err| err.to_string())
+            .and_then(|text| {
+                let _lock = lock_conf(&preferences_file)?;
+                replace_conf(&preferences_file, &text)
+            });
+        if let Err(err) = written {
+            error!("setup::setup(): Cannot write the default config: {err}");
+        }
     }
     let time = Utc::now();
     let mut log_filename = log_dir.clone();
     log_filename.push(format!("yarp-{}.log", time.format("%Y-%m-%d_%H_%M_%S")));
 
+    // Read before the loggers are ready, the problems are logged again below
+    let (configs, issues) = read_conf();
+
     // Configurar el logger de archivo
     match File::create(log_filename) {
         Ok(log_file) => {
-            let configs: YamlConfiguration = load_conf();
 
-            let mut loggers: Vec<Box<dyn simplelog::SharedLogger>> = Vec::new();
-
-            if configs.logs_configurations.write_to_stdout {
-                loggers.push(TermLogger::new(
-                    LevelFilter::Info,
-                    Config::default(),
-                    TerminalMode::Mixed,
-                    ColorChoice::Auto,
-                ));
-            }
-
-            if configs.logs_configurations.write_to_file {
-                loggers.push(WriteLogger::new(
-                    LevelFilter::Info,
-                    Config::default(),
-                    log_file,
-                ));
-            }
+            // Both loggers are created and switched on and off by the configs, so
+            // they can change while the shell runs. The level is the global one
+            let loggers: Vec<Box<dyn SharedLogger>> = vec![
+                Box::new(Switch {
+                    logger: TermLogger::new(LevelFilter::Trace, Config::default(), TerminalMode::Mixed, ColorChoice::Auto),
+                    on: &LOG_TO_STDOUT,
+                }),
+                Box::new(Switch {
+                    logger: WriteLogger::new(LevelFilter::Trace, Config::default(), log_file),
+                    on: &LOG_TO_FILE,
+                }),
+            ];
 
             // Combinar ambos loggers
             if let Err(err) = CombinedLogger::init(loggers) {
                 error!("Couldn't initialize log file");
                 error!("{}", err);
-                ()
             };
+            apply_log_config(&configs.logs_configurations);
+            for issue in &issues {
+                error!("setup::setup(): {issue}");
+            }
         }
         Err(err) => {
             error!("Couldn't initialize log file");
             error!("{}", err);
-            ()
         }
     }
+    (configs, issues)
 }
 
-pub fn load_conf() -> YamlConfiguration {
-    let base_dir = ProjectDirs::from("", "", "yarp");
-    let default_values = Default::default();
-    if base_dir.is_none() {
-        error!("Couldn't read config file. Defaulting config values...");
-        error!("Base dir object is null");
-        return default_values;
-    } else {
-        let mut config_dir = base_dir.unwrap().config_dir().to_path_buf();
-        config_dir.push("preferences.yml");
-
-        match File::open(config_dir) {
-            Ok(mut file) => {
-                let mut buffer = String::new();
-                let content = file.read_to_string(&mut buffer);
-                if let Err(err) = content {
-                    error!("Couldn't read config file. Defaulting config values...");
-                    error!("Cannot read config file: {}", err);
-                    return default_values;
+/// The path of `preferences.yml`.
+pub fn config_path() -> Option<PathBuf> {
+    let base_dir = ProjectDirs::from("", "", "yarp")?;
+    Some(base_dir.config_dir().join("preferences.yml"))
+}
+
+/// Reads the configs with a deserializer of the text or of a tree of it. The keys
+/// that aren't fields are added to `ignored`.
+pub fn deserialize_conf<'de, D: Deserializer<'de>>(
+    deserializer: D,
+    ignored: &mut Vec<String>,
+) -> Result<YamlConfiguration, serde_path_to_error::Error<D::Error>> {
+    let mut callback = |path: serde_ignored::Path| ignored.push(path.to_string());
+    serde_path_to_error::deserialize(serde_ignored::Deserializer::new(deserializer, &mut callback))
+}
+
+/// Removes the value at `path` from the tree, so its field takes the default value.
+fn remove_path(tree: &mut Value, path: &Path) -> bool {
+    let segments: Vec<&Segment> = path.iter().collect();
+    let Some((last, parents)) = segments.split_last() else {
+        return false;
+    };
+    let mut node = tree;
+    for segment in parents {
+        let child = match (segment, node) {
+            (Segment::Map { key }, Value::Mapping(map)) => map.get_mut(key.as_str()),
+            (Segment::Seq { index }, Value::Sequence(list)) => list.get_mut(*index),
+            _ => None,
+        };
+        match child {
+            Some(child) => node = child,
+            None => return false,
+        }
+    }
+    match (last, node) {
+        (Segment::Map { key }, Value::Mapping(map)) => map.remove(key.as_str()).is_some(),
+        (Segment::Seq { index }, Value::Sequence(list)) if *index < list.len() => {
+            list.remove(*index);
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `file:line:column` of `path`, or only the file if it isn't found.
+pub fn place_of(text: &str, file: &str, path: &[String]) -> String {
+    match yaml_edit::locate(text, path) {
+        Some((line, column)) => format!("{file}:{line}:{column}"),
+        None => file.to_string(),
+    }
+}
+
+/// Reads the configs from `text`, the content of `file`. The fields that are
+/// missing or have a wrong value take their default values, and the problems are
+/// returned as messages with the file, line and column.
+pub fn parse_conf(text: &str, file: &str) -> (YamlConfiguration, Vec<String>) {
+    let (tree, issues) = parse_layer(text, file);
+    (serde_yaml::from_value(tree).unwrap_or_default(), issues)
+}
+
+/// Reads `text`, the content of `file`, as a tree of configs without the values
+/// that have a wrong type, so their fields take the default values. The problems
+/// are returned as messages with the file, line and column.
+pub fn parse_layer(text: &str, file: &str) -> (Value, Vec<String>) {
+    let mut issues = vec![];
+    let empty = Value::Mapping(Mapping::new());
+    if text.trim().is_empty() {
+        return (empty, issues);
+    }
+    // The first attempt reads the text, so the errors have their place in the file.
+    // The wrong values are removed from a tree of it for the next ones
+    let mut tree: Option<Value> = None;
+    loop {
+        let mut ignored = vec![];
+        let result = match &tree {
+            None => deserialize_conf(serde_yaml::Deserializer::from_str(text), &mut ignored),
+            Some(tree) => deserialize_conf(tree.clone(), &mut ignored),
+        };
+        let err = match result {
+            Ok(_) => {
+                for key in ignored {
+                    let steps: Vec<String> = key.split('.').map(String::from).collect();
+                    let place = place_of(text, file, &steps);
+                    issues.push(format!("{place}: unknown key `{key}`, it is ignored"));
+                }
+                let tree = tree.or_else(|| serde_yaml::from_str(text).ok()).filter(Value::is_mapping);
+                return (tree.unwrap_or(empty), issues);
+            }
+            Err(err) => err,
+        };
+
+        let path = err.path().clone();
+        let inner = err.into_inner();
+        let mut message = inner.to_string();
+        // The messages of the text start with the path and end with the place
+        if let Some(rest) = message.strip_prefix(&format!("{path}: ")) {
+            message = rest.to_string();
+        }
+        let place = match inner.location() {
+            Some(location) => {
+                if let Some((start, _)) = message.rsplit_once(" at line ") {
+                    message = start.to_string();
                 }
-                let confs: YamlConfiguration =
-                    serde_yaml::from_str(&buffer).unwrap_or_else(|err| {
-                        error!("Couldn't read config file. Defaulting config values...");
-                        error!("{}", err);
-                        return default_values;
-                    });
-                return confs;
+                format!("{file}:{}:{}", location.line(), location.column())
             }
-            Err(err) => {
-                error!("Couldn't read config file. Defaulting config values...");
-                error!("Cannot open config file: {}", err);
-                return default_values;
+            None => {
+                let steps: Vec<String> = path
+                    .iter()
+                    .map(|segment| match segment {
+                        Segment::Map { key } => key.clone(),
+                        Segment::Seq { index } => index.to_string(),
+                        _ => String::new(),
+                    })
+                    .collect();
+                place_of(text, file, &steps)
             }
+        };
+
+        let tree = match &mut tree {
+            Some(tree) => tree,
+            None => match serde_yaml::from_str::<Value>(text) {
+                Ok(parsed) => tree.insert(parsed),
+                Err(_) => {
+                    issues.push(format!("{place}: {message}, using the default configs"));
+                    return (empty, issues);
+                }
+            },
+        };
+        if !remove_path(tree, &path) {
+            issues.push(format!("{place}: {message}, using the default configs"));
+            return (empty, issues);
+        }
+        let fallback = match path.iter().next_back() {
+            Some(Segment::Seq { .. }) => "the item is left out",
+            _ => "using the default value",
+        };
+        issues.push(format!("{place}: {path}: {message}, {fallback}"));
+    }
+}
+
+/// The tree of `text` and its version, if its layout is older than the current one.
+fn outdated(text: &str) -> Result<Option<(Value, u64)>, String> {
+    // The errors of the files that can't be read are reported by `parse_conf`
+    let Ok(tree) = serde_yaml::from_str::<Value>(text) else {
+        return Ok(None);
+    };
+    if !tree.is_mapping() {
+        return Ok(None);
+    }
+    let version = migrations::version_of(&tree)?;
+    Ok((version != CURRENT_VERSION).then_some((tree, version)))
+}
+
+/// Upgrades the config file if its layout is older than the current one, after
+/// copying it to `preferences.yml.vN.bak`. Returns the text of the file, and the
+/// path of the copy when it was upgraded now.
+fn upgrade_conf(path: &FilePath, text: &str) -> Result<(String, Option<PathBuf>), String> {
+    // An up to date file isn't locked just to be read
+    if outdated(text)?.is_none() {
+        return Ok((text.to_string(), None));
+    }
+    // It is read again under the lock, another shell may have changed it meanwhile
+    let _lock = lock_conf(path)?;
+    let text = fs::read_to_string(path).map_err(|err| format!("cannot read it again: {err}"))?;
+    let Some((mut tree, version)) = outdated(&text)? else {
+        return Ok((text, None));
+    };
+    migrations::migrate(&mut tree)?;
+    let upgraded = serde_yaml::to_string(&tree).map_err(|err| err.to_string())?;
+
+    let backup = path.with_file_name(format!("preferences.yml.v{version}.bak"));
+    info!("setup::upgrade_conf(): Upgrading the config file from version {version}, the old one is at {}", backup.to_string_lossy());
+    fs::copy(path, &backup).map_err(|err| format!("cannot back it up to {}: {err}", backup.to_string_lossy()))?;
+    replace_conf(path, &upgraded)?;
+    Ok((upgraded, Some(backup)))
+}
+
+/// Reads the configs of all the layers, see `layers::read_layers`. The problems
+/// found in them are returned to be shown to the user, and logged.
+pub fn read_conf() -> (YamlConfiguration, Vec<String>) {
+    let (layers, issues) = layers::read_layers();
+    for issue in &issues {
+        error!("setup::read_conf(): {issue}");
+    }
+    (layers::merge(&layers), issues)
+}
+
+/// Reads the config file of the user, upgraded if it has an older layout. The
+/// problems are added to `issues`.
+pub fn read_user_conf(issues: &mut Vec<String>) -> Option<(PathBuf, String)> {
+    let Some(path) = config_path() else {
+        error!("setup::read_user_conf(): Couldn't read config file. Defaulting config values...");
+        error!("setup::read_user_conf(): Base dir object is null");
+        return None;
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("setup::read_user_conf(): Couldn't read config file. Defaulting config values...");
+            error!("setup::read_user_conf(): Cannot read config file: {}", err);
+            issues.push(format!("{}: {err}, using the default configs", path.to_string_lossy()));
+            return None;
+        }
+    };
+    info!("setup::read_user_conf(): Reading {}", path.to_string_lossy());
+    let text = match upgrade_conf(&path, &text) {
+        Ok((upgraded, Some(backup))) => {
+            issues.push(format!(
+                "{}: upgraded to the version {CURRENT_VERSION} of the config, the old file is at {}",
+                path.to_string_lossy(),
+                backup.to_string_lossy()
+            ));
+            upgraded
+        }
+        Ok((text, None)) => text,
+        Err(err) => {
+            issues.push(format!("{}: {err}, it is read as it is", path.to_string_lossy()));
+            text
         }
+    };
+    Some((path, text))
+}
+
+pub fn load_conf() -> YamlConfiguration {
+    read_conf().0
+}
+
+/// Locks `preferences.yml.lock` until the file that is returned is closed, so two
+/// shells don't write the config at the same time.
+fn lock_conf(path: &FilePath) -> Result<File, String> {
+    let lock_path = path.with_file_name("preferences.yml.lock");
+    let lock = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|err| format!("Cannot open {}: {err}", lock_path.to_string_lossy()))?;
+    if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(format!("Cannot lock {}: {}", lock_path.to_string_lossy(), io::Error::last_os_error()));
     }
+    Ok(lock)
 }
 
-pub fn write_conf(configs: YamlConfiguration) {
-    let base_dir = ProjectDirs::from("", "", "yarp");
-    if let Some(config_path) = base_dir {
-        let mut conf = config_path.config_dir().to_path_buf();
-        conf.push("preferences.yml");
-
-        match File::create(conf) {
-            Ok(mut config_file) => {
-                let configs_to_str = serde_yaml::to_string(&configs);
-                match configs_to_str {
-                    Ok(conf_buff) => {
-                        if let Err(err) = config_file.write_all(conf_buff.as_bytes()) {
-                            error!(
-                                "setup::write_conf: Cannot write to the config file: {}",
-                                err
-                            );
-                            error!(
-                                "setup::write_conf Debug Info: Ocurred in setup.rs in line {}",
-                                line!()
-                            );
-                            error!("setup::write_conf: No changes has been applyed");
-                            ()
-                        }
-                    }
-                    Err(err) => {
-                        error!(
-                            "setup::write_conf: Cannot write to the config file: {}",
-                            err
-                        );
-                        error!(
-                            "setup::write_conf Debug Info: Ocurred in setup.rs in line {}",
-                            line!()
-                        );
-                        error!("setup::write_conf: No changes has been applyed");
-                        ()
-                    }
+/// How many old versions of the config file are kept, as `preferences.yml.bak.1`
+/// (the newest) to `preferences.yml.bak.3`.
+const BACKUPS: usize = 3;
+
+/// Replaces the config file with `text`. It is written to a temporary file that is
+/// renamed over the config, so a crash never leaves it half written, and the
+/// previous one is kept as a backup. The new file gets the permissions of the old
+/// one. The caller holds the lock.
+fn replace_conf(path: &FilePath, text: &str) -> Result<(), String> {
+    let temp = path.with_file_name(format!(".preferences.yml.{}.tmp", std::process::id()));
+    let written = File::create(&temp).and_then(|mut file| {
+        match fs::metadata(path) {
+            Ok(metadata) => file.set_permissions(metadata.permissions())?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        file.write_all(text.as_bytes())?;
+        file.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp);
+        return Err(format!("Cannot write {}: {err}", temp.to_string_lossy()));
+    }
+
+    if path.exists() {
+        let backup = |number: usize| path.with_file_name(format!("preferences.yml.bak.{number}"));
+        for number in (1..BACKUPS).rev() {
+            if backup(number).exists() {
+                if let Err(err) = fs::rename(backup(number), backup(number + 1)) {
+                    error!("setup::replace_conf(): Cannot rotate the backups of the config: {err}");
                 }
             }
-            Err(err) => {
-                error!(
-                    "setup::write_conf: Cannot write to the config file: {}",
-                    err
-                );
-                error!(
-                    "setup::write_conf Debug Info: Ocurred in setup.rs in line {}",
-                    line!()
-                );
-                error!("setup::write_conf: No changes has been applyed");
-                ()
-            }
+        }
+        if let Err(err) = fs::copy(path, backup(1)) {
+            error!("setup::replace_conf(): Cannot back up the config: {err}");
+        }
+    }
+    fs::rename(&temp, path).map_err(|err| {
+        let _ = fs::remove_file(&temp);
+        format!("Cannot replace {}: {err}", path.to_string_lossy())
+    })
+}
+
+/// Reads the config file, lets `change` modify the configs and writes them back.
+/// Only the values that changed are edited in the text, so the comments and the
+/// order of the keys stay. The file is locked meanwhile, so the changes made by
+/// other shells are kept.
+pub fn update_conf(change: impl FnOnce(&mut YamlConfiguration) -> Result<(), String>) -> Result<(), String> {
+    let path = config_path().ok_or_else(|| String::from("Cannot find the config directory"))?;
+    update_conf_at(&path, change)
+}
+
+/// Like `update_conf`, for the config file at `path`.
+pub fn update_conf_at(path: &FilePath, change: impl FnOnce(&mut YamlConfiguration) -> Result<(), String>) -> Result<(), String> {
+    let _lock = lock_conf(path)?;
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(format!("Cannot read {}: {err}", path.to_string_lossy())),
+    };
+    let file = path.to_string_lossy();
+    let (mut configs, _) = parse_conf(&text, &file);
+    let to_tree = |configs: &YamlConfiguration| serde_yaml::to_value(configs).map_err(|err| err.to_string());
+    let old = to_tree(&configs)?;
+    change(&mut configs)?;
+    let new = to_tree(&configs)?;
+    if old == new {
+        return Ok(());
+    }
+
+    // The edited text must give the same configs, or it is written again from them
+    let edited = yaml_edit::edit(&text, &old, &new)
+        .filter(|edited| to_tree(&parse_conf(edited, &file).0).is_ok_and(|tree| tree == new));
+    let text = match edited {
+        Some(edited) => edited,
+        None => {
+            info!("setup::update_conf(): The config file can't be edited, it is written again without its comments");
+            serde_yaml::to_string(&configs).map_err(|err| err.to_string())?
+        }
+    };
+    replace_conf(path, &text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (YamlConfiguration, Vec<String>) {
+        parse_conf(text, "preferences.yml")
+    }
+
+    #[test]
+    fn an_empty_file_has_the_default_configs() {
+        for text in ["", "\n  \n"] {
+            let (tree, issues) = parse_layer(text, "preferences.yml");
+            assert_eq!(tree, Value::Mapping(Mapping::new()));
+            assert!(issues.is_empty());
+        }
+    }
+
+    #[test]
+    fn a_partial_section_keeps_the_defaults_of_the_other_fields() {
+        let (configs, issues) = parse("terminal_config:\n  auto_cd: true\ntheme:\n  colors:\n    di: red\n");
+        assert!(issues.is_empty(), "{issues:?}");
+        assert!(configs.terminal_config.auto_cd);
+        assert_eq!(configs.terminal_config.prompt, "{}");
+        assert_eq!(configs.terminal_config.edit_mode, EditMode::Emacs);
+        assert!(configs.theme.use_ls_colors);
+        assert_eq!(configs.theme.colors.get("di").map(String::as_str), Some("red"));
+        assert_eq!(configs.version, CURRENT_VERSION);
+        assert!(configs.logs_configurations.write_to_file);
+    }
+
+    #[test]
+    fn a_wrong_value_takes_the_default_of_its_field_only() {
+        let text = "terminal_config:\n  auto_cd: maybe\n  prompt: \"> \"\n  edit_mode: nano\n";
+        let (configs, issues) = parse(text);
+        assert!(!configs.terminal_config.auto_cd);
+        assert_eq!(configs.terminal_config.prompt, "> ");
+        assert_eq!(configs.terminal_config.edit_mode, EditMode::Emacs);
+        assert_eq!(
+            issues,
+            [
+                "preferences.yml:2:12: terminal_config.auto_cd: invalid type: string \"maybe\", expected a boolean, using the default value",
+                "preferences.yml:4:3: terminal_config.edit_mode: unknown variant `nano`, expected `emacs` or `vi`, using the default value",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_wrong_section_takes_its_defaults() {
+        let (configs, issues) = parse("terminal_config: [1, 2]\ntheme:\n  use_ls_colors: false\n");
+        assert_eq!(configs.terminal_config.prompt, "{}");
+        assert!(!configs.theme.use_ls_colors);
+        assert_eq!(
+            issues,
+            ["preferences.yml:1:18: terminal_config: invalid type: sequence, expected struct TermConfig, using the default value"]
+        );
+    }
+
+    #[test]
+    fn a_wrong_list_item_is_left_out() {
+        let (configs, issues) = parse("scripts_config:\n  files:\n  - a.lua\n  - [b]\n  - c.lua\n");
+        assert_eq!(configs.scripts_config.files, [PathBuf::from("a.lua"), PathBuf::from("c.lua")]);
+        assert_eq!(
+            issues,
+            ["preferences.yml:4:5: scripts_config.files[1]: invalid type: sequence, expected path string, the item is left out"]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_are_reported_with_their_place() {
+        let (configs, issues) = parse("terminal_config:\n  prompt: x\n  colour: red\nthemes: {}\n");
+        assert_eq!(configs.terminal_config.prompt, "x");
+        assert_eq!(
+            issues,
+            [
+                "preferences.yml:3:3: unknown key `terminal_config.colour`, it is ignored",
+                "preferences.yml:4:1: unknown key `themes`, it is ignored",
+            ]
+        );
+    }
+
+    #[test]
+    fn a_file_that_isnt_a_map_has_the_default_configs() {
+        for text in ["- a\n- b\n", "terminal_config: {prompt: [\n"] {
+            let (tree, issues) = parse_layer(text, "preferences.yml");
+            assert_eq!(tree, Value::Mapping(Mapping::new()), "{text}");
+            assert_eq!(issues.len(), 1, "{text}");
+            assert!(issues[0].starts_with("preferences.yml:"), "{}", issues[0]);
+            assert!(issues[0].ends_with(", using the default configs"), "{}", issues[0]);
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,200 @@
+use log::info;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// Edit distance between two words, counting a swap of two adjacent characters as
+/// a single edit (optimal string alignment distance).
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut table = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+
+    for (i, row) in table.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in table[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            table[i][j] = (table[i - 1][j] + 1)
+                .min(table[i][j - 1] + 1)
+                .min(table[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                table[i][j] = table[i][j].min(table[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    table[a.len()][b.len()]
+}
+
+/// The biggest distance at which `word` is still considered a misspelling.
+pub fn max_distance(word: &str) -> usize {
+    (word.chars().count() / 3).max(1)
+}
+
+/// Returns the candidates closest to `word`, best first: the fewest edits, then the
+/// ones starting with the same letter, then the ones of the same length, then in the
+/// order of `candidates`. Only candidates within `max_distance(word)` edits are returned.
+pub fn closest<'a, I>(word: &str, candidates: I) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let limit = max_distance(word);
+    let first = word.chars().next();
+    let length = word.chars().count();
+    let mut found: Vec<(usize, bool, usize, usize, &str)> = candidates
+        .into_iter()
+        .enumerate()
+        .filter(|(_, candidate)| *candidate != word)
+        .map(|(index, candidate)| {
+            (
+                edit_distance(word, candidate),
+                candidate.chars().next() != first,
+                candidate.chars().count().abs_diff(length),
+                index,
+                candidate,
+            )
+        })
+        .filter(|(distance, ..)| *distance <= limit)
+        .collect();
+    found.sort();
+    let mut seen = HashSet::new();
+    found
+        .into_iter()
+        .map(|(.., candidate)| candidate)
+        .filter(|candidate| seen.insert(*candidate))
+        .collect()
+}
+
+/// Fixes a mistyped directory path one component at a time, replacing every component
+/// that doesn't exist with the closest directory name found next to it.
+/// Returns `None` if some component can't be corrected or nothing had to change.
+pub fn correct_dir_path(path: &Path) -> Option<PathBuf> {
+    let mut corrected = PathBuf::new();
+    let mut changed = false;
+
+    for component in path.components() {
+        let Component::Normal(name) = component else {
+            corrected.push(component);
+            continue;
+        };
+        let candidate = corrected.join(name);
+        if candidate.is_dir() {
+            corrected = candidate;
+            continue;
+        }
+
+        let parent = if corrected.as_os_str().is_empty() {
+            PathBuf::from(".")
+        } else {
+            corrected.clone()
+        };
+        let mut names: Vec<String> = parent
+            .read_dir()
+            .ok()?
+            .flatten()
+            .filter(|entry| entry.path().is_dir())
+            // A name that isn't UTF-8 can't be pushed back as it was compared
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .collect();
+        names.sort();
+        let name = name.to_string_lossy();
+        let best = closest(&name, names.iter().map(String::as_str)).first()?.to_string();
+        info!("suggest::correct_dir_path(): Correcting '{name}' to '{best}'");
+        corrected.push(best);
+        changed = true;
+    }
+
+    if changed {
+        Some(corrected)
+    } else {
+        None
+    }
+}
+
+/// Builds the "did you mean" hint shown when `name` is not a command, with up to
+/// three of the closest `candidates`. Among equally close ones, the earlier
+/// candidates are preferred.
+pub fn did_you_mean(name: &str, candidates: &[String]) -> Option<String> {
+    let mut found = closest(name, candidates.iter().map(String::as_str));
+    found.truncate(3);
+    match found.as_slice() {
+        [] => None,
+        [single] => Some(format!("did you mean `{single}`?")),
+        many => Some(format!(
+            "did you mean one of these? {}",
+            many.iter()
+                .map(|candidate| format!("`{candidate}`"))
+                .collect::<Vec<String>>()
+                .join(", ")
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::scratch_dir;
+    use std::fs;
+
+    #[test]
+    fn edit_distance_counts_the_edits() {
+        let cases = [
+            ("ls", "ls", 0),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("sl", "ls", 1),
+            ("gti", "git", 1),
+            ("cargo", "crago", 1),
+            ("kitten", "sitting", 3),
+            ("ca", "abc", 3),
+            ("héllo", "hello", 1),
+        ];
+        for (a, b, distance) in cases {
+            assert_eq!(edit_distance(a, b), distance, "{a} -> {b}");
+        }
+    }
+
+    #[test]
+    fn max_distance_grows_with_the_word() {
+        let cases = [("a", 1), ("ls", 1), ("cargo", 1), ("python", 2), ("systemctl", 3)];
+        for (word, distance) in cases {
+            assert_eq!(max_distance(word), distance, "{word}");
+        }
+    }
+
+    #[test]
+    fn closest_prefers_the_same_first_letter_and_length() {
+        let candidates = ["ex", "ld", "ls", "ln", "lsof", "ls", "lx", "cat"];
+        assert_eq!(closest("lx", candidates), ["ld", "ls", "ln", "ex"]);
+        assert_eq!(closest("gti", ["git", "gtk", "tig"]), ["git", "gtk"]);
+        assert!(closest("zzzz", candidates).is_empty());
+    }
+
+    #[test]
+    fn did_you_mean_keeps_the_earlier_candidates() {
+        let candidates: Vec<String> = ["ls", "ex", "ld", "ln", "lz"].iter().map(|name| name.to_string()).collect();
+        assert_eq!(
+            did_you_mean("lx", &candidates).as_deref(),
+            Some("did you mean one of these? `ls`, `ld`, `ln`")
+        );
+        assert_eq!(did_you_mean("sl", &candidates[..1]).as_deref(), Some("did you mean `ls`?"));
+        assert_eq!(did_you_mean("cargo", &candidates), None);
+    }
+
+    #[test]
+    fn correct_dir_path_fixes_each_component() {
+        let root = scratch_dir("suggest");
+        fs::create_dir_all(root.join("Documents/projects")).unwrap();
+        fs::write(root.join("Documents/project"), "").unwrap();
+
+        assert_eq!(correct_dir_path(&root.join("Documnets/projcts")), Some(root.join("Documents/projects")));
+        // Files are not directories to go to
+        assert_eq!(correct_dir_path(&root.join("Documents/projec")), Some(root.join("Documents/projects")));
+        assert_eq!(correct_dir_path(&root.join("Documents/projects")), None);
+        assert_eq!(correct_dir_path(&root.join("Documents/music")), None);
+        let _ = fs::remove_dir_all(&root);
+    }
+}
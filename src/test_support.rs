@@ -0,0 +1,11 @@
+use std::fs;
+use std::path::PathBuf;
+
+/// An empty directory of its own for each test, named after it and the process so
+/// two runs of the tests don't share it.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("yarsh-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,404 @@
+use serde_yaml::{Mapping, Value};
+
+/// A change of the config: the value at a path is set or removed.
+#[derive(Debug)]
+enum Change {
+    Set(Vec<String>, Value),
+    Remove(Vec<String>),
+}
+
+/// The indentation and the content of a line, without the indentation.
+fn split_indent(line: &str) -> (usize, &str) {
+    let content = line.trim_start_matches(' ');
+    (line.len() - content.len(), content)
+}
+
+fn is_item(content: &str) -> bool {
+    content == "-" || content.starts_with("- ")
+}
+
+/// Whether the line is the key `step` of a map, written with or without quotes.
+fn is_key(content: &str, step: &str) -> bool {
+    [format!("{step}:"), format!("\"{step}\":"), format!("'{step}':")].iter().any(|key| {
+        content
+            .strip_prefix(key.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with(' '))
+    })
+}
+
+/// The position of the colon after the key of a line, outside of quotes.
+fn key_end(content: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut chars = content.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ':') if chars.peek().is_none_or(|(_, next)| *next == ' ') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Where the comment at the end of a value starts, with the spaces before it.
+fn comment_start(value: &str) -> Option<usize> {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in value.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '"' | '\'') => quote = Some(c),
+            (None, '#') if previous == ' ' => return Some(value[..index].trim_end().len()),
+            _ => {}
+        }
+        previous = c;
+    }
+    None
+}
+
+/// The line of the key or the list item at `path` and its indentation. Only the
+/// block style is understood.
+fn find(lines: &[String], path: &[String]) -> Option<(usize, usize)> {
+    let mut parent: Option<(usize, usize)> = None;
+    for step in path {
+        let mut index = parent.map_or(0, |(line, _)| line + 1);
+        let mut child_indent = None;
+        let mut item = 0;
+        loop {
+            let (indent, content) = split_indent(lines.get(index)?);
+            if content.is_empty() || content.starts_with('#') {
+                index += 1;
+                continue;
+            }
+            // The items of a list can be as indented as its key
+            let inside = match parent {
+                Some((_, parent_indent)) => indent > parent_indent || (is_item(content) && indent == parent_indent),
+                None => true,
+            };
+            if !inside {
+                return None;
+            }
+            let child_indent = *child_indent.get_or_insert(indent);
+            if indent < child_indent {
+                return None;
+            }
+            if indent == child_indent {
+                if is_item(content) {
+                    if step.parse() == Ok(item) {
+                        break;
+                    }
+                    item += 1;
+                } else if is_key(content, step) {
+                    break;
+                }
+            }
+            index += 1;
+        }
+        parent = Some((index, split_indent(&lines[index]).0));
+    }
+    parent
+}
+
+/// The line and column of the key or the list item at `path`, counted from 1.
+pub fn locate(text: &str, path: &[String]) -> Option<(usize, usize)> {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    find(&lines, path).map(|(line, indent)| (line + 1, indent + 1))
+}
+
+/// The line after the value of the key at `line`: its nested lines. The comments
+/// and empty lines at the end are left for what follows.
+fn block_end(lines: &[String], line: usize, indent: usize) -> usize {
+    let mut end = line + 1;
+    let mut index = line + 1;
+    while let Some(next) = lines.get(index) {
+        let (next_indent, content) = split_indent(next);
+        if content.is_empty() || content.starts_with('#') {
+            index += 1;
+            continue;
+        }
+        if next_indent < indent || (next_indent == indent && !is_item(content)) {
+            break;
+        }
+        index += 1;
+        end = index;
+    }
+    end
+}
+
+fn scalar(value: &Value) -> String {
+    match value {
+        Value::Mapping(map) if map.is_empty() => String::from("{}"),
+        Value::Sequence(list) if list.is_empty() => String::from("[]"),
+        _ => serde_yaml::to_string(value).unwrap_or_default().trim_end().to_string(),
+    }
+}
+
+fn is_block(value: &Value) -> bool {
+    match value {
+        Value::Mapping(map) => !map.is_empty(),
+        Value::Sequence(list) => !list.is_empty(),
+        _ => false,
+    }
+}
+
+/// The indentation of the lines nested in the key at `line`, which end at `end`.
+/// Two spaces more than the key if there are none.
+fn child_indent(lines: &[String], line: usize, end: usize, indent: usize) -> usize {
+    lines[line + 1..end]
+        .iter()
+        .map(|next| split_indent(next))
+        .find(|(_, content)| !content.is_empty() && !content.starts_with('#'))
+        .map_or(indent + 2, |(child_indent, _)| child_indent)
+}
+
+/// The lines of `key: value` indented by `indent`, and its nested lines by
+/// `child_indent`. `comment` goes after a value that fits in the line.
+fn render(key: &str, value: &Value, indent: usize, child_indent: usize, comment: &str) -> Vec<String> {
+    let padding = " ".repeat(indent);
+    if !is_block(value) {
+        return vec![format!("{padding}{key}: {}{comment}", scalar(value))];
+    }
+    let nested = serde_yaml::to_string(value).unwrap_or_default();
+    let child_padding = " ".repeat(child_indent);
+    let mut lines = vec![format!("{padding}{key}:{comment}")];
+    lines.extend(nested.lines().map(|line| format!("{child_padding}{line}")));
+    lines
+}
+
+fn render_key(key: &str) -> String {
+    scalar(&Value::from(key))
+}
+
+/// The keys at `path` and after it, with `value` at the end: `a.b` and `1` are
+/// written as `a:` and `  b: 1` under it.
+fn nest(path: &[String], value: Value) -> Value {
+    path.iter().rev().fold(value, |value, key| {
+        let mut map = Mapping::new();
+        map.insert(Value::from(key.as_str()), value);
+        Value::Mapping(map)
+    })
+}
+
+/// The changes that turn `old` into `new`. The maps are compared key by key, and
+/// the other values as a whole.
+fn diff(old: &Value, new: &Value, path: &mut Vec<String>, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Mapping(old_map), Value::Mapping(new_map)) => {
+            for (key, value) in new_map {
+                let Some(key) = key.as_str() else { continue };
+                path.push(key.to_string());
+                match old_map.get(key) {
+                    Some(old_value) => diff(old_value, value, path, changes),
+                    None => changes.push(Change::Set(path.clone(), value.clone())),
+                }
+                path.pop();
+            }
+            for key in old_map.keys().filter_map(Value::as_str) {
+                if !new_map.contains_key(key) {
+                    let mut removed = path.clone();
+                    removed.push(key.to_string());
+                    changes.push(Change::Remove(removed));
+                }
+            }
+        }
+        _ if old != new => changes.push(Change::Set(path.clone(), new.clone())),
+        _ => {}
+    }
+}
+
+fn set(lines: &mut Vec<String>, path: &[String], value: Value) -> Option<()> {
+    // The deepest key of the path that is in the file
+    let found = (1..=path.len()).rev().find_map(|depth| find(lines, &path[..depth]).map(|found| (depth, found)));
+    let Some((depth, (line, indent))) = found else {
+        // A section that isn't in the file goes at the end
+        let key = render_key(&path[0]);
+        lines.extend(render(&key, &nest(&path[1..], value), 0, 2, ""));
+        return Some(());
+    };
+    let content = &lines[line][indent..];
+    let colon = key_end(content)?;
+    let key = content[..colon].to_string();
+    let rest = &content[colon + 1..];
+    let comment = comment_start(rest).map_or(String::new(), |start| rest[start..].to_string());
+    let inline_value = rest[..comment_start(rest).unwrap_or(rest.len())].trim();
+    let end = block_end(lines, line, indent);
+
+    let child_indent = child_indent(lines, line, end, indent);
+    if depth == path.len() {
+        lines.splice(line..end, render(&key, &value, indent, child_indent, &comment));
+        return Some(());
+    }
+    let missing = nest(&path[depth + 1..], value);
+    match inline_value {
+        // An empty map becomes a block with the new key
+        "{}" | "" => {
+            lines[line] = format!("{}{key}:{comment}", " ".repeat(indent));
+            lines.splice(end..end, render(&render_key(&path[depth]), &missing, child_indent, child_indent + 2, ""));
+            Some(())
+        }
+        // Flow style isn't edited
+        _ => None,
+    }
+}
+
+fn remove(lines: &mut Vec<String>, path: &[String]) -> Option<()> {
+    let Some((line, indent)) = find(lines, path) else {
+        // Not in the file, it had the default value
+        return Some(());
+    };
+    let end = block_end(lines, line, indent);
+    lines.drain(line..end);
+    // A map without keys would be read as null
+    if let Some((parent, parent_indent)) = path.len().checked_sub(1).and_then(|len| find(lines, &path[..len])) {
+        if block_end(lines, parent, parent_indent) == parent + 1 {
+            let content = &lines[parent][parent_indent..];
+            let colon = key_end(content)?;
+            let rest = &content[colon + 1..];
+            if rest[..comment_start(rest).unwrap_or(rest.len())].trim().is_empty() {
+                let comment = comment_start(rest).map_or(String::new(), |start| rest[start..].to_string());
+                lines[parent] = format!("{} {{}}{comment}", &lines[parent][..parent_indent + colon + 1]);
+            }
+        }
+    }
+    Some(())
+}
+
+/// Applies the changes from `old` to `new`, the values read from `text`, to the
+/// text itself, so its comments, its empty lines and the order of its keys stay.
+/// `None` if the file uses something this can't edit, like the flow style.
+pub fn edit(text: &str, old: &Value, new: &Value) -> Option<String> {
+    let mut changes = vec![];
+    diff(old, new, &mut vec![], &mut changes);
+    let mut lines: Vec<String> = text.lines().map(String::from).collect();
+    for change in changes {
+        match change {
+            Change::Set(path, value) => set(&mut lines, &path, value)?,
+            Change::Remove(path) => remove(&mut lines, &path)?,
+        }
+    }
+    let mut edited = lines.join("\n");
+    edited.push('\n');
+    Some(edited)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(text: &str) -> Vec<String> {
+        text.split('.').map(String::from).collect()
+    }
+
+    /// Edits `text` with `change` applied to its values, and checks that the edited
+    /// text is read back as the changed values.
+    fn edited(text: &str, change: impl FnOnce(&mut Value)) -> Option<String> {
+        let old: Value = serde_yaml::from_str(text).unwrap();
+        let mut new = old.clone();
+        change(&mut new);
+        let edited = edit(text, &old, &new)?;
+        assert_eq!(serde_yaml::from_str::<Value>(&edited).unwrap(), new, "{edited}");
+        Some(edited)
+    }
+
+    fn at<'a>(tree: &'a mut Value, path: &str) -> &'a mut Value {
+        path.split('.').fold(tree, |node, step| match node {
+            Value::Sequence(list) => &mut list[step.parse::<usize>().unwrap()],
+            node => &mut node[step],
+        })
+    }
+
+    const CONFIG: &str = "# The configs of the shell
+version: 1
+
+terminal_config:
+  prompt: '{} >' # the working directory and an arrow
+  alias:
+    ll: ls -l
+  # Off until I get used to it
+  auto_cd: false
+scripts_config:
+  files:
+  - a.lua
+  - b.lua
+";
+
+    #[test]
+    fn keeps_the_comments_and_the_other_lines() {
+        let text = edited(CONFIG, |tree| *at(tree, "terminal_config.auto_cd") = Value::from(true)).unwrap();
+        assert_eq!(text, CONFIG.replace("auto_cd: false", "auto_cd: true"));
+
+        let text = edited(CONFIG, |tree| *at(tree, "terminal_config.prompt") = Value::from(">")).unwrap();
+        assert_eq!(text, CONFIG.replace("'{} >' #", "'>' #"));
+    }
+
+    #[test]
+    fn adds_nested_keys() {
+        let text = edited(CONFIG, |tree| {
+            *at(tree, "terminal_config.alias") = serde_yaml::from_str("{ll: ls -l, la: ls -a}").unwrap();
+        })
+        .unwrap();
+        assert_eq!(text, CONFIG.replace("    ll: ls -l\n", "    ll: ls -l\n    la: ls -a\n"));
+
+        // A section that isn't in the file goes at the end
+        let text = edited(CONFIG, |tree| {
+            tree["theme"] = serde_yaml::from_str("colors: {dir: blue}").unwrap();
+        })
+        .unwrap();
+        assert_eq!(text, format!("{CONFIG}theme:\n  colors:\n    dir: blue\n"));
+    }
+
+    #[test]
+    fn changes_list_items() {
+        let text = edited(CONFIG, |tree| *at(tree, "scripts_config.files.1") = Value::from("c.lua")).unwrap();
+        assert_eq!(text, CONFIG.replace("- b.lua", "- c.lua"));
+        assert_eq!(locate(CONFIG, &path("scripts_config.files.1")), Some((13, 3)));
+        assert_eq!(locate(CONFIG, &path("scripts_config.files.2")), None);
+    }
+
+    #[test]
+    fn fills_and_empties_maps() {
+        let empty = CONFIG.replace("  alias:\n    ll: ls -l\n", "  alias: {}\n");
+        let text = edited(&empty, |tree| *at(tree, "terminal_config.alias") = serde_yaml::from_str("{la: ls -a}").unwrap());
+        assert_eq!(text.unwrap(), CONFIG.replace("ll: ls -l", "la: ls -a"));
+
+        // The last key removed leaves an empty map, and not a null
+        let text = edited(CONFIG, |tree| {
+            at(tree, "terminal_config.alias").as_mapping_mut().unwrap().remove("ll");
+        });
+        assert_eq!(text.unwrap(), empty);
+    }
+
+    #[test]
+    fn removes_keys_with_their_values() {
+        let text = edited(CONFIG, |tree| {
+            tree["scripts_config"].as_mapping_mut().unwrap().remove("files");
+            tree["terminal_config"].as_mapping_mut().unwrap().remove("prompt");
+        })
+        .unwrap();
+        let expected = CONFIG
+            .replace("  prompt: '{} >' # the working directory and an arrow\n", "")
+            .replace("scripts_config:\n  files:\n  - a.lua\n  - b.lua\n", "scripts_config: {}\n");
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn gives_up_on_the_flow_style() {
+        let flow = CONFIG.replace("  alias:\n    ll: ls -l\n", "  alias: {ll: ls -l}\n");
+        let text = edited(&flow, |tree| *at(tree, "terminal_config.alias.la") = Value::from("ls -a"));
+        assert_eq!(text, None);
+        // The values around it can still be edited
+        let text = edited(&flow, |tree| *at(tree, "terminal_config.auto_cd") = Value::from(true));
+        assert_eq!(text.unwrap(), flow.replace("auto_cd: false", "auto_cd: true"));
+    }
+
+    #[test]
+    fn locates_quoted_and_nested_keys() {
+        let text = "a:\n  \"b.c\": 1\n  d:\n    e: 2\n";
+        assert_eq!(locate(text, &[String::from("a"), String::from("b.c")]), Some((2, 3)));
+        assert_eq!(locate(text, &path("a.d.e")), Some((4, 5)));
+        assert_eq!(locate(text, &path("a.e")), None);
+    }
+}
@@ -0,0 +1,124 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+
+fn install(dir: &Path, name: &str, output: &str) {
+    let path = dir.join(name);
+    fs::write(&path, format!("#!/bin/sh\necho {output}\n")).unwrap();
+    fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+}
+
+/// A shell with two PATH directories, an alias and a function. `tool` is a function
+/// and a file in both directories, `greet` an alias and a file, `echo` a builtin and
+/// a file.
+fn lookup_shell(name: &str, lines: &str) -> (PathBuf, Vec<String>, String) {
+    let scratch = scratch_dir(name);
+    let (first, second) = (scratch.join("first"), scratch.join("second"));
+    fs::create_dir(&first).unwrap();
+    fs::create_dir(&second).unwrap();
+    install(&first, "tool", "first tool");
+    install(&second, "tool", "second tool");
+    install(&first, "greet", "greet file");
+    install(&first, "echo", "echo file");
+
+    let script = scratch.join("tool.lua");
+    fs::write(&script, "register_function(\"tool\", function() print(\"tool function\") end)\n").unwrap();
+    let config_dir = scratch.join("home/.config/yarp");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config = format!(
+        "version: 1\nterminal_config:\n  alias:\n    greet: echo alias\nscripts_config:\n  files:\n  - {}\n",
+        script.to_string_lossy()
+    );
+    fs::write(config_dir.join("preferences.yml"), config).unwrap();
+
+    let path = std::env::join_paths([&first, &second]).unwrap().into_string().unwrap();
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[("PATH", path.as_str())], lines);
+    let stdout = String::from_utf8(stdout).unwrap().lines().map(String::from).collect();
+    (scratch, stdout, stderr)
+}
+
+#[test]
+fn type_and_which_follow_the_order_of_the_dispatcher() {
+    let lines = "type tool\ntype greet\ntype echo\ntype -t tool greet echo\nwhich tool greet echo\nexit\n";
+    let (scratch, stdout, stderr) = lookup_shell("lookup-order", lines);
+    assert_eq!(
+        stdout,
+        [
+            "tool is a function",
+            "greet is aliased to `echo alias'",
+            "echo is a shell builtin",
+            "function",
+            "alias",
+            "builtin",
+            "tool: shell function",
+            "greet: aliased to echo alias",
+            "echo: shell built-in command",
+        ],
+        "{stderr}"
+    );
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn all_lists_the_shadowed_matches() {
+    let lines = "type -a tool\nwhich -a echo\ntype -p -a tool\nhash\nexit\n";
+    let (scratch, stdout, stderr) = lookup_shell("lookup-all", lines);
+    let first_tool = scratch.join("first/tool").to_string_lossy().into_owned();
+    let second_tool = scratch.join("second/tool").to_string_lossy().into_owned();
+    let first_echo = scratch.join("first/echo").to_string_lossy().into_owned();
+    assert_eq!(
+        stdout,
+        [
+            "tool is a function".to_string(),
+            format!("tool is {first_tool}"),
+            format!("tool is {second_tool}"),
+            "echo: shell built-in command".to_string(),
+            first_echo,
+            first_tool,
+            second_tool,
+        ],
+        "{stderr}"
+    );
+    // Looking commands up to report them doesn't count as using them
+    assert_eq!(stderr.trim_end(), "hash: hash table empty");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn command_skips_functions_and_aliases() {
+    let lines = "tool\ncommand tool\ngreet\ncommand greet\ncommand -v tool greet echo\nbuiltin echo builtin\nexit\n";
+    let (scratch, stdout, stderr) = lookup_shell("lookup-command", lines);
+    assert_eq!(
+        stdout,
+        [
+            "tool function",
+            "first tool",
+            "alias",
+            "greet file",
+            "tool",
+            "alias greet='echo alias'",
+            "echo",
+            "builtin",
+        ],
+        "{stderr}"
+    );
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn command_reports_files_that_cannot_run() {
+    // The dispatcher runs `command NAME` itself, after an option the builtin runs it
+    let lines = "command -- missing\ncommand -- ./first\nexit\n";
+    let (scratch, stdout, stderr) = lookup_shell("lookup-errors", lines);
+    assert!(stdout.is_empty(), "{stdout:?}");
+    assert!(stderr.contains("command: unknown command: missing"), "{stderr}");
+    assert!(stderr.contains("command: ./first: Is a directory"), "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
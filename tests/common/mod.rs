@@ -0,0 +1,44 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[path = "../../src/test_support.rs"]
+mod test_support;
+
+/// A directory of its own for each test, with a home so the config and the logs
+/// of the shell don't touch the real ones.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = test_support::scratch_dir(name);
+    fs::create_dir(dir.join("home")).unwrap();
+    dir
+}
+
+/// Runs the lines in the shell and returns its stdout and stderr.
+pub fn run_shell(home: &Path, lines: &str) -> (Vec<u8>, String) {
+    run_shell_in(home, Path::new("."), &[], lines)
+}
+
+/// Like `run_shell`, starting the shell in `dir` with the variables in `vars`.
+pub fn run_shell_in(home: &Path, dir: &Path, vars: &[(&str, &str)], lines: &str) -> (Vec<u8>, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_yarsh"))
+        .current_dir(dir)
+        .env("HOME", home)
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("XDG_DATA_HOME")
+        .envs(vars.iter().copied())
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(lines.as_bytes()).unwrap();
+    let output = child.wait_with_output().unwrap();
+    (output.stdout, String::from_utf8_lossy(&output.stderr).into_owned())
+}
+
+pub fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|window| window == needle)
+}
@@ -0,0 +1,193 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+use std::path::PathBuf;
+
+/// A scratch directory with a system config, a user config and a project with a
+/// `.yarsh.yml`, written from the texts given.
+struct Layers {
+    scratch: PathBuf,
+    system: String,
+}
+
+impl Layers {
+    fn new(name: &str, system: &str, user: &str, project: &str) -> Layers {
+        let scratch = scratch_dir(name);
+        fs::write(scratch.join("system.yml"), system).unwrap();
+        let config_dir = scratch.join("home/.config/yarp");
+        fs::create_dir_all(&config_dir).unwrap();
+        fs::write(config_dir.join("preferences.yml"), user).unwrap();
+        fs::create_dir_all(scratch.join("project/src")).unwrap();
+        fs::write(scratch.join("project/.yarsh.yml"), project).unwrap();
+        let system = scratch.join("system.yml").to_string_lossy().into_owned();
+        Layers { scratch, system }
+    }
+
+    fn project(&self) -> PathBuf {
+        self.scratch.join("project")
+    }
+
+    /// Runs the lines in a subdirectory of the project, so the `.yarsh.yml` is found
+    /// in a parent.
+    fn run(&self, vars: &[(&str, &str)], lines: &str) -> (String, String) {
+        let mut all = vec![("YARSH_SYSTEM_CONFIG", self.system.as_str())];
+        all.extend_from_slice(vars);
+        let (stdout, stderr) = run_shell_in(&self.scratch.join("home"), &self.project().join("src"), &all, lines);
+        (String::from_utf8(stdout).unwrap(), stderr)
+    }
+
+    /// The values of the fields at `paths`, one for each.
+    fn get(&self, vars: &[(&str, &str)], paths: &[&str]) -> (Vec<String>, String) {
+        let mut lines: String = paths.iter().map(|path| format!("config get {path}\n")).collect();
+        lines.push_str("exit\n");
+        let (stdout, stderr) = self.run(vars, &lines);
+        (stdout.lines().map(String::from).collect(), stderr)
+    }
+}
+
+impl Drop for Layers {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.scratch);
+    }
+}
+
+const SYSTEM: &str = "terminal_config:
+  prompt: system
+  alias:
+    a: system
+    b: system
+    c: system
+    d: system
+";
+const USER: &str = "version: 1
+terminal_config:
+  prompt: user
+  alias:
+    a: user
+    b: user
+    c: user
+";
+const PROJECT: &str = "terminal_config:
+  prompt: project
+  alias:
+    a: project
+    b: project
+";
+
+fn trust(layers: &Layers) {
+    let (stdout, stderr) = layers.run(&[], "config trust\nexit\n");
+    assert!(stdout.contains("Trusted"), "{stdout}{stderr}");
+}
+
+#[test]
+fn later_layers_override_the_earlier_ones() {
+    let layers = Layers::new("layers-precedence", SYSTEM, USER, PROJECT);
+    trust(&layers);
+    let (values, stderr) = layers.get(
+        &[("YARSH_TERMINAL_CONFIG__ALIAS__A", "env")],
+        &[
+            "terminal_config.alias.a",
+            "terminal_config.alias.b",
+            "terminal_config.alias.c",
+            "terminal_config.alias.d",
+            "terminal_config.prompt",
+        ],
+    );
+    assert_eq!(values, ["env", "project", "user", "system", "project"], "{stderr}");
+    assert!(!stderr.contains("ignored"), "{stderr}");
+
+    let (stdout, _) = layers.run(&[("YARSH_TERMINAL_CONFIG__ALIAS__A", "env")], "config --show-origin\nexit\n");
+    let project = fs::canonicalize(layers.project().join(".yarsh.yml")).unwrap();
+    let origins = [
+        ("terminal_config.alias.a=env", String::from("$YARSH_TERMINAL_CONFIG__ALIAS__A")),
+        ("terminal_config.alias.b=project", format!("{}:5:5", project.to_string_lossy())),
+        ("terminal_config.alias.d=system", format!("{}:7:5", layers.system)),
+    ];
+    for (value, origin) in origins {
+        assert!(stdout.lines().any(|line| line == format!("{origin}\t{value}")), "no {origin} for {value} in:\n{stdout}");
+    }
+}
+
+#[test]
+fn a_project_config_is_only_read_when_trusted() {
+    let layers = Layers::new("layers-trust", SYSTEM, USER, PROJECT);
+    let (values, stderr) = layers.get(&[], &["terminal_config.alias.b"]);
+    assert_eq!(values, ["user"]);
+    assert!(stderr.contains(".yarsh.yml: not trusted, it is ignored"), "{stderr}");
+
+    trust(&layers);
+    let (values, stderr) = layers.get(&[], &["terminal_config.alias.b"]);
+    assert_eq!(values, ["project"]);
+    assert!(!stderr.contains("trusted"), "{stderr}");
+
+    // A change has to be trusted again
+    fs::write(layers.project().join(".yarsh.yml"), PROJECT.replace("a: project", "a: changed")).unwrap();
+    let (values, stderr) = layers.get(&[], &["terminal_config.alias.a", "terminal_config.alias.b"]);
+    assert_eq!(values, ["user", "user"]);
+    assert!(stderr.contains(".yarsh.yml: changed since it was trusted"), "{stderr}");
+
+    trust(&layers);
+    let (values, _) = layers.get(&[], &["terminal_config.alias.a"]);
+    assert_eq!(values, ["changed"]);
+
+    layers.run(&[], "config untrust\nexit\n");
+    let (values, stderr) = layers.get(&[], &["terminal_config.alias.a"]);
+    assert_eq!(values, ["user"]);
+    assert!(stderr.contains("not trusted"), "{stderr}");
+}
+
+#[test]
+fn environment_values_are_read_as_the_type_of_the_field() {
+    let layers = Layers::new("layers-env", "", USER, "");
+    for script in ["a.lua", "b.lua"] {
+        fs::write(layers.project().join("src").join(script), "").unwrap();
+    }
+    let vars = [
+        ("YARSH_TERMINAL_CONFIG__AUTO_CD", "true"),
+        // A string field keeps text that looks like another type
+        ("YARSH_TERMINAL_CONFIG__PROMPT", "123"),
+        ("YARSH_SCRIPTS_CONFIG__FILES", "[a.lua, b.lua]"),
+        ("YARSH_LOGS_CONFIGURATIONS__LEVEL", "debug"),
+    ];
+    let (values, stderr) = layers.get(
+        &vars,
+        &[
+            "terminal_config.auto_cd",
+            "terminal_config.prompt",
+            "scripts_config.files.1",
+            "logs_configurations.level",
+        ],
+    );
+    assert_eq!(values, ["true", "123", "b.lua", "DEBUG"], "{stderr}");
+}
+
+#[test]
+fn wrong_environment_values_are_ignored() {
+    let layers = Layers::new("layers-env-errors", "", USER, "");
+    let vars = [
+        ("YARSH_TERMINAL_CONFIG__AUTO_CD", "maybe"),
+        ("YARSH_TERMINAL_CONFIG__PROMT", "x"),
+        ("YARSH_TERMINAL_CONFIG____PROMPT", "x"),
+    ];
+    let (values, stderr) = layers.get(&vars, &["terminal_config.auto_cd", "terminal_config.prompt"]);
+    assert_eq!(values, ["false", "user"]);
+    let expected = [
+        "yarp: $YARSH_TERMINAL_CONFIG__AUTO_CD: ",
+        "yarp: $YARSH_TERMINAL_CONFIG__PROMT: unknown key `terminal_config.promt`, it is ignored",
+        "yarp: $YARSH_TERMINAL_CONFIG____PROMPT: not a path of the configs, it is ignored",
+    ];
+    for line in expected {
+        assert!(stderr.contains(line), "no {line} in:\n{stderr}");
+    }
+}
+
+#[test]
+fn the_system_config_is_optional() {
+    let layers = Layers::new("layers-no-system", "", USER, "");
+    let missing = layers.scratch.join("missing.yml").to_string_lossy().into_owned();
+    let vars = [("YARSH_SYSTEM_CONFIG", missing.as_str())];
+    let (stdout, stderr) = run_shell_in(&layers.scratch.join("home"), &layers.scratch, &vars, "config get terminal_config.prompt\nexit\n");
+    assert_eq!(String::from_utf8(stdout).unwrap(), "user\n");
+    assert!(stderr.is_empty(), "{stderr}");
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{run_shell, scratch_dir};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The config directory in the home of the scratch directory, with `preferences.yml`
+/// written from `text`.
+fn write_config(scratch: &Path, text: &str) -> PathBuf {
+    let config_dir = scratch.join("home/.config/yarp");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("preferences.yml"), text).unwrap();
+    config_dir
+}
+
+/// Starts the shell with a config file written before there was a version, and
+/// checks that it was upgraded, backed up, and that the fields in `expected` kept
+/// their values.
+fn check_upgrade(name: &str, fixture: &str, expected: &[(&str, &str)]) {
+    let scratch = scratch_dir(name);
+    let original = fs::read_to_string(Path::new("tests/fixtures/config").join(fixture)).unwrap();
+    let config_dir = write_config(&scratch, &original);
+
+    let mut lines: String = expected.iter().map(|(path, _)| format!("config get {path}\n")).collect();
+    lines.push_str("config get version\nexit\n");
+    let (stdout, stderr) = run_shell(&scratch.join("home"), &lines);
+
+    assert!(stderr.contains("upgraded to the version 1 of the config"), "no upgrade in: {stderr}");
+    assert!(!stderr.contains("unknown key") && !stderr.contains("invalid type"), "{stderr}");
+    let values: Vec<(&str, &str)> = expected.iter().copied().chain([("version", "1")]).collect();
+    let stdout = String::from_utf8(stdout).unwrap();
+    let printed: Vec<&str> = stdout.lines().collect();
+    assert_eq!(printed, values.iter().map(|(_, value)| *value).collect::<Vec<_>>());
+
+    // The original is kept as it was, and the new file starts with the version
+    assert_eq!(fs::read_to_string(config_dir.join("preferences.yml.v0.bak")).unwrap(), original);
+    let upgraded = fs::read_to_string(config_dir.join("preferences.yml")).unwrap();
+    assert!(upgraded.starts_with("version: 1\n"), "{upgraded}");
+
+    // Only once
+    let (_, stderr) = run_shell(&scratch.join("home"), "exit\n");
+    assert!(!stderr.contains("upgraded"), "{stderr}");
+    fs::remove_dir_all(&scratch).unwrap();
+}
+
+#[test]
+fn upgrades_the_first_layout() {
+    check_upgrade(
+        "config-baseline",
+        "v0_baseline.yml",
+        &[
+            ("terminal_config.prompt", "{} >"),
+            ("terminal_config.alias.ll", "ls -l"),
+            ("logs_configurations.write_to_file", "false"),
+            ("terminal_config.auto_cd", "false"),
+        ],
+    );
+}
+
+#[test]
+fn upgrades_the_layout_with_auto_cd() {
+    check_upgrade(
+        "config-auto-cd",
+        "v0_auto_cd.yml",
+        &[("terminal_config.alias.ll", "ls -l"), ("terminal_config.auto_cd", "true")],
+    );
+}
+
+#[test]
+fn upgrades_the_layout_with_a_command_not_found_handler() {
+    check_upgrade(
+        "config-not-found",
+        "v0_command_not_found_handler.yml",
+        &[
+            ("terminal_config.auto_cd", "true"),
+            ("terminal_config.command_not_found_handler", "not_found"),
+            ("theme.use_ls_colors", "true"),
+        ],
+    );
+}
+
+#[test]
+fn upgrades_the_layout_with_a_theme() {
+    check_upgrade(
+        "config-theme",
+        "v0_theme.yml",
+        &[
+            ("terminal_config.command_not_found_handler", "not_found"),
+            ("theme.use_ls_colors", "false"),
+            ("theme.colors.directory", "bold blue"),
+        ],
+    );
+}
+
+#[test]
+fn leaves_the_current_version_alone() {
+    let scratch = scratch_dir("config-current");
+    let text = "version: 1\nterminal_config:\n  prompt: '{} >'\n";
+    let config_dir = write_config(&scratch, text);
+
+    let (stdout, stderr) = run_shell(&scratch.join("home"), "config get terminal_config.prompt\nexit\n");
+    assert!(!stderr.contains("upgraded"), "{stderr}");
+    assert_eq!(String::from_utf8(stdout).unwrap(), "{} >\n");
+    assert_eq!(fs::read_to_string(config_dir.join("preferences.yml")).unwrap(), text);
+    fs::remove_dir_all(&scratch).unwrap();
+}
+
+#[test]
+fn leaves_newer_versions_alone() {
+    let scratch = scratch_dir("config-newer");
+    let text = "version: 99\nterminal_config:\n  auto_cd: true\n";
+    let config_dir = write_config(&scratch, text);
+
+    let (stdout, stderr) = run_shell(&scratch.join("home"), "config get terminal_config.auto_cd\nexit\n");
+    assert!(stderr.contains("the version 99 is newer"), "{stderr}");
+    assert_eq!(String::from_utf8(stdout).unwrap(), "true\n");
+    assert_eq!(fs::read_to_string(config_dir.join("preferences.yml")).unwrap(), text);
+    assert!(!config_dir.join("preferences.yml.v99.bak").exists());
+    fs::remove_dir_all(&scratch).unwrap();
+}
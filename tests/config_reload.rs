@@ -0,0 +1,66 @@
+mod common;
+
+use common::{run_shell, scratch_dir};
+use std::fs;
+use std::path::Path;
+
+/// Writes `text` as the config of the home of `scratch`.
+fn write_config(scratch: &Path, text: &str) {
+    let config_dir = scratch.join("home/.config/yarp");
+    fs::create_dir_all(&config_dir).unwrap();
+    fs::write(config_dir.join("preferences.yml"), text).unwrap();
+}
+
+const CONFIG: &str = "version: 1
+terminal_config:
+  alias:
+    hi: echo one
+";
+
+#[test]
+fn a_changed_config_applies_before_the_next_prompt() {
+    let scratch = scratch_dir("config-watch");
+    write_config(&scratch, CONFIG);
+    let config = scratch.join("home/.config/yarp/preferences.yml");
+    // The new file gets a later modification time than the one the shell read
+    let change = format!(
+        "sh -c \"sleep 0.1; sed -i s/one/two/ '{}'\"\n",
+        config.to_string_lossy()
+    );
+    let (stdout, stderr) = run_shell(&scratch.join("home"), &format!("hi\n{change}hi\nexit\n"));
+    assert_eq!(String::from_utf8(stdout).unwrap(), "one\ntwo\n", "{stderr}");
+    fs::remove_dir_all(&scratch).unwrap();
+}
+
+#[test]
+fn config_reload_shows_the_problems_again() {
+    let scratch = scratch_dir("config-reload");
+    write_config(&scratch, &format!("{CONFIG}  promt: x\n"));
+    let problem = "unknown key `terminal_config.promt`";
+
+    let (_, stderr) = run_shell(&scratch.join("home"), "hi\nhi\nexit\n");
+    assert_eq!(stderr.matches(problem).count(), 1, "{stderr}");
+
+    let (_, stderr) = run_shell(&scratch.join("home"), "config reload\nhi\nexit\n");
+    assert_eq!(stderr.matches(problem).count(), 2, "{stderr}");
+
+    let (_, stderr) = run_shell(&scratch.join("home"), "config reload | cat\nhi\nexit\n");
+    assert_eq!(stderr.matches(problem).count(), 1, "{stderr}");
+    assert!(stderr.contains("The configs can't be reloaded from a pipeline"), "{stderr}");
+    fs::remove_dir_all(&scratch).unwrap();
+}
+
+#[test]
+fn a_script_can_ask_for_a_reload() {
+    let scratch = scratch_dir("config-reload-script");
+    let script = scratch.join("reload.lua");
+    fs::write(&script, "exec(\"config reload\")\n").unwrap();
+    write_config(
+        &scratch,
+        &format!("{CONFIG}  promt: x\nscripts_config:\n  files:\n  - {}\n", script.to_string_lossy()),
+    );
+
+    let (_, stderr) = run_shell(&scratch.join("home"), "hi\nexit\n");
+    assert_eq!(stderr.matches("unknown key `terminal_config.promt`").count(), 2, "{stderr}");
+    fs::remove_dir_all(&scratch).unwrap();
+}
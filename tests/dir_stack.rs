@@ -0,0 +1,76 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory with the directories `a`, `b` and `c`, and `link` pointing
+/// to `a`. The path is canonical, so the shell shows it as it is.
+fn dirs_scratch(name: &str) -> (PathBuf, String) {
+    let scratch = fs::canonicalize(scratch_dir(name)).unwrap();
+    for dir in ["a", "b", "c"] {
+        fs::create_dir(scratch.join(dir)).unwrap();
+    }
+    std::os::unix::fs::symlink("a", scratch.join("link")).unwrap();
+    let shown = scratch.to_string_lossy().into_owned();
+    (scratch, shown)
+}
+
+fn run_lines(scratch: &Path, start: &str, lines: &str) -> (Vec<String>, String) {
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), Path::new(start), &[("PWD", start)], lines);
+    let stdout = String::from_utf8(stdout).unwrap().lines().map(String::from).collect();
+    (stdout, stderr)
+}
+
+#[test]
+fn pushd_and_popd_rotate_the_stack() {
+    let (scratch, s) = dirs_scratch("dir-stack-rotate");
+    let lines = "pushd a\npushd ../b\npushd ../c\ndirs -v\npushd +2\npushd -1\npopd +1\npopd -0\npopd\npwd\nexit\n";
+    let (stdout, stderr) = run_lines(&scratch, &s, lines);
+    assert_eq!(
+        stdout,
+        [
+            format!("{s}/a {s}"),
+            format!("{s}/b {s}/a {s}"),
+            format!("{s}/c {s}/b {s}/a {s}"),
+            format!(" 0  {s}/c"),
+            format!(" 1  {s}/b"),
+            format!(" 2  {s}/a"),
+            format!(" 3  {s}"),
+            // +2 brings the third entry to the top, the ones above it go to the bottom
+            format!("{s}/a {s} {s}/c {s}/b"),
+            // -1 counts from the bottom
+            format!("{s}/c {s}/b {s}/a {s}"),
+            format!("{s}/c {s}/a {s}"),
+            format!("{s}/c {s}/a"),
+            format!("{s}/a"),
+            format!("{s}/a"),
+        ],
+        "{stderr}"
+    );
+    assert!(stderr.is_empty(), "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn popping_an_empty_stack_fails() {
+    let (scratch, s) = dirs_scratch("dir-stack-empty");
+    let (stdout, stderr) = run_lines(&scratch, &s, "popd\npushd\npushd +3\ndirs -v\nexit\n");
+    assert_eq!(stdout, [format!(" 0  {s}")]);
+    assert!(stderr.contains("popd: directory stack empty"), "{stderr}");
+    assert!(stderr.contains("pushd: no other directory"), "{stderr}");
+    assert!(stderr.contains("pushd: +3: directory stack index out of range"), "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn pwd_physical_resolves_the_symlinks() {
+    let (scratch, s) = dirs_scratch("dir-stack-pwd");
+    let link = format!("{s}/link");
+    let (stdout, stderr) = run_lines(&scratch, &link, "pwd\npwd -P\ncd ..\ncd link\npwd\npwd -P\nexit\n");
+    assert_eq!(stdout, [link.clone(), format!("{s}/a"), link, format!("{s}/a")], "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
@@ -0,0 +1,38 @@
+mod common;
+
+use common::{contains, run_shell, scratch_dir};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+
+#[test]
+fn ls_and_commands_keep_non_utf8_names() {
+    let scratch = scratch_dir("non-utf8");
+    let files = scratch.join("files");
+    fs::create_dir(&files).unwrap();
+    let latin1 = b"caf\xe9.txt";
+    let invalid = b"bad\xffname";
+    fs::write(files.join(OsStr::from_bytes(latin1)), "x").unwrap();
+    fs::create_dir(files.join(OsStr::from_bytes(invalid))).unwrap();
+    fs::write(files.join("plain.txt"), "x").unwrap();
+
+    let dir = files.to_str().unwrap();
+    let out = scratch.join("out.txt");
+    let lines = format!(
+        "ls {dir}\nls -l -R {dir}\nls --tree {dir}\nls {dir} | cat > {out}\nexit\n",
+        out = out.to_str().unwrap(),
+    );
+    let (stdout, stderr) = run_shell(&scratch.join("home"), &lines);
+
+    assert!(!stderr.contains("panicked"), "the shell panicked: {stderr}");
+    // Not a terminal, so the names are written as they are
+    assert!(contains(&stdout, latin1));
+    assert!(contains(&stdout, invalid));
+    assert!(contains(&stdout, &[files.as_os_str().as_bytes(), b"/", invalid, b":"].concat()));
+
+    // `cat` got the same bytes through the pipe
+    let piped = fs::read(&out).unwrap();
+    assert_eq!(piped, [&invalid[..], b"\n", latin1, b"\n", b"plain.txt\n"].concat());
+
+    fs::remove_dir_all(&scratch).unwrap();
+}
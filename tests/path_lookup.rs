@@ -0,0 +1,25 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+#[test]
+fn a_file_made_executable_is_found_without_rehash() {
+    let scratch = scratch_dir("path-lookup-chmod");
+    let bin = scratch.join("bin");
+    fs::create_dir(&bin).unwrap();
+    fs::write(bin.join("present"), "#!/bin/sh\n").unwrap();
+    fs::set_permissions(bin.join("present"), fs::Permissions::from_mode(0o755)).unwrap();
+    fs::write(bin.join("tool"), "#!/bin/sh\necho tool ran\n").unwrap();
+    fs::set_permissions(bin.join("tool"), fs::Permissions::from_mode(0o644)).unwrap();
+    let path = bin.to_string_lossy().into_owned();
+
+    // The first lookup builds the index, chmod doesn't change the mtime of bin
+    let lines = "hash -t present\n/bin/chmod +x bin/tool\ntool\nexit\n";
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[("PATH", path.as_str())], lines);
+    let stdout = String::from_utf8(stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), [format!("{path}/present").as_str(), "tool ran"], "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
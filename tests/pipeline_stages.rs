@@ -0,0 +1,40 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+
+#[test]
+fn builtins_before_the_last_stage_leave_the_shell_alone() {
+    let scratch = scratch_dir("pipeline-stages");
+    let start = fs::canonicalize(&scratch).unwrap();
+    let other = start.join("other");
+    fs::create_dir(&other).unwrap();
+    let start = start.to_string_lossy().into_owned();
+    let other = other.to_string_lossy().into_owned();
+
+    let lines = format!("cd {other} | cat\npwd\npushd {other} | cat\ndirs\ncd /nonexistent | cat\nexit\n");
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[("PWD", start.as_str())], &lines);
+    let stdout = String::from_utf8(stdout).unwrap();
+    assert_eq!(stdout.lines().collect::<Vec<_>>(), [start.clone(), format!("{other} {start}"), start.clone()]);
+    assert!(stderr.contains("cd: /nonexistent: No such file or directory"), "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn help_in_a_pipeline_lists_the_functions_of_the_scripts() {
+    let scratch = scratch_dir("pipeline-help");
+    let script = scratch.join("greet.lua");
+    fs::write(&script, "register_function(\"greet\", function() print(\"hi\") end, \"Says hi\\nTo everyone\")\n").unwrap();
+    let config_dir = scratch.join("home/.config/yarp");
+    fs::create_dir_all(&config_dir).unwrap();
+    let config = format!("version: 1\nscripts_config:\n  files:\n  - {}\n", script.to_string_lossy());
+    fs::write(config_dir.join("preferences.yml"), config).unwrap();
+
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[], "help | cat\nhelp greet | cat\nexit\n");
+    let stdout = String::from_utf8(stdout).unwrap();
+    assert!(stdout.lines().any(|line| line.trim_start().starts_with("greet") && line.ends_with("Says hi")), "{stdout}{stderr}");
+    assert!(stdout.contains("greet: function registered by a script\n\nSays hi\nTo everyone\n"), "{stdout}{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}
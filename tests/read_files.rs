@@ -0,0 +1,39 @@
+mod common;
+
+use common::{run_shell_in, scratch_dir};
+use std::fs;
+
+#[test]
+fn hex_dumps_are_streamed_through_pipes() {
+    let scratch = scratch_dir("read-hex");
+    // More than one block of the stream, with a NUL so it is seen as binary
+    let mut content = vec![0u8];
+    content.extend((0..200_000u32).map(|index| (index % 251) as u8));
+    fs::write(scratch.join("data.bin"), &content).unwrap();
+
+    let lines = "read data.bin | cat\nread --range 4097:4098 data.bin | cat\nexit\n";
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[], lines);
+    let stdout = String::from_utf8(stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    let rows = content.len().div_ceil(16);
+    assert_eq!(lines.len(), rows + 2, "{stderr}");
+    assert!(lines[0].starts_with("00000000  00 00 01 02 "), "{}", lines[0]);
+    assert!(lines[rows - 1].starts_with(&format!("{:08x}  ", (rows - 1) * 16)), "{}", lines[rows - 1]);
+    // The lines of the range have the offsets they have in the whole dump
+    assert_eq!(lines[rows], lines[4096]);
+    assert_eq!(lines[rows + 1], lines[4097]);
+    assert!(lines[rows].starts_with("00010000  "), "{}", lines[rows]);
+
+    let _ = fs::remove_dir_all(&scratch);
+}
+
+#[test]
+fn read_errors_name_the_file() {
+    let scratch = scratch_dir("read-errors");
+    let (stdout, stderr) = run_shell_in(&scratch.join("home"), &scratch, &[], "read missing.txt\nread home\nexit\n");
+    assert!(stdout.is_empty());
+    assert!(stderr.contains("read: missing.txt: No such file or directory"), "{stderr}");
+    assert!(stderr.contains("read: home: Is a directory"), "{stderr}");
+
+    let _ = fs::remove_dir_all(&scratch);
+}